@@ -0,0 +1,258 @@
+//! Animated GIF / WebP encoding for `capture --animated`.
+//!
+//! The `image` crate can encode animated GIFs, but its WebP encoder only
+//! writes still images. For WebP every frame is encoded as a lossless still
+//! and its `VP8L` bitstream is wrapped in the `ANMF` frame chunks of an
+//! animated WebP container (see the WebP container specification).
+
+use image::codecs::gif::{GifEncoder, Repeat};
+use image::codecs::webp::WebPEncoder;
+use image::{Delay, DynamicImage, ExtendedColorType, Frame};
+use std::fmt::{Display, Formatter};
+use std::io;
+use std::io::Write;
+use std::str::FromStr;
+
+/// GIF quantization speed, 1 (best) to 30 (fastest). Quantizing large frames
+/// at the best quality takes minutes.
+const GIF_SPEED: i32 = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AnimatedFormat {
+    /// Animated WebP, lossless. The default, far better looking than gif.
+    #[default]
+    Webp,
+    /// 256 colors per frame, but supported everywhere.
+    Gif,
+}
+
+impl AnimatedFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            AnimatedFormat::Webp => "webp",
+            AnimatedFormat::Gif => "gif",
+        }
+    }
+}
+
+impl Display for AnimatedFormat {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.extension())
+    }
+}
+
+impl FromStr for AnimatedFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "webp" => Ok(AnimatedFormat::Webp),
+            "gif" => Ok(AnimatedFormat::Gif),
+            other => Err(format!(
+                "Unknown animated format '{other}', expected webp or gif"
+            )),
+        }
+    }
+}
+
+/// Encode `frames`, shown `frame_ms` milliseconds each, as an animation.
+/// The animation plays `loop_count` times, 0 loops forever.
+pub fn encode_animation<W: Write>(
+    writer: W,
+    frames: &[DynamicImage],
+    format: AnimatedFormat,
+    frame_ms: u32,
+    loop_count: u16,
+) -> io::Result<()> {
+    match format {
+        AnimatedFormat::Gif => encode_gif(writer, frames, frame_ms, loop_count),
+        AnimatedFormat::Webp => encode_webp(writer, frames, frame_ms, loop_count),
+    }
+}
+
+fn encode_gif<W: Write>(
+    writer: W,
+    frames: &[DynamicImage],
+    frame_ms: u32,
+    loop_count: u16,
+) -> io::Result<()> {
+    let mut encoder = GifEncoder::new_with_speed(writer, GIF_SPEED);
+    // the NETSCAPE extension counts repetitions after the first play, the
+    // WebP ANIM chunk counts plays
+    let repeat = match loop_count {
+        0 => Repeat::Infinite,
+        n => Repeat::Finite(n - 1),
+    };
+    encoder.set_repeat(repeat).map_err(io::Error::other)?;
+    let delay = Delay::from_numer_denom_ms(frame_ms, 1);
+    encoder
+        .encode_frames(
+            frames
+                .iter()
+                .map(|image| Frame::from_parts(image.to_rgba8(), 0, 0, delay)),
+        )
+        .map_err(io::Error::other)
+}
+
+fn encode_webp<W: Write>(
+    mut writer: W,
+    frames: &[DynamicImage],
+    frame_ms: u32,
+    loop_count: u16,
+) -> io::Result<()> {
+    let (Some(width), Some(height)) = (
+        frames.iter().map(DynamicImage::width).max(),
+        frames.iter().map(DynamicImage::height).max(),
+    ) else {
+        return Err(io::Error::other("No frames to encode"));
+    };
+
+    // VP8X: animation and alpha flags, canvas size
+    let mut vp8x = vec![0x02 | 0x10, 0, 0, 0];
+    vp8x.extend_from_slice(&u24(width - 1));
+    vp8x.extend_from_slice(&u24(height - 1));
+    // ANIM: transparent background, loop count
+    let mut anim = vec![0, 0, 0, 0];
+    anim.extend_from_slice(&loop_count.to_le_bytes());
+
+    let mut body = b"WEBP".to_vec();
+    push_chunk(&mut body, b"VP8X", &vp8x);
+    push_chunk(&mut body, b"ANIM", &anim);
+    for image in frames {
+        let rgba = image.to_rgba8();
+        let mut still = Vec::new();
+        WebPEncoder::new_lossless(&mut still)
+            .encode(
+                rgba.as_raw(),
+                rgba.width(),
+                rgba.height(),
+                ExtendedColorType::Rgba8,
+            )
+            .map_err(io::Error::other)?;
+        // frame at 0,0, no blending with the previous frame, no disposal
+        let mut anmf = Vec::new();
+        anmf.extend_from_slice(&u24(0));
+        anmf.extend_from_slice(&u24(0));
+        anmf.extend_from_slice(&u24(rgba.width() - 1));
+        anmf.extend_from_slice(&u24(rgba.height() - 1));
+        anmf.extend_from_slice(&u24(frame_ms));
+        anmf.push(0x02);
+        anmf.extend_from_slice(bitstream_chunk(&still)?);
+        push_chunk(&mut body, b"ANMF", &anmf);
+    }
+
+    writer.write_all(b"RIFF")?;
+    writer.write_all(&(body.len() as u32).to_le_bytes())?;
+    writer.write_all(&body)
+}
+
+fn u24(value: u32) -> [u8; 3] {
+    let bytes = value.to_le_bytes();
+    [bytes[0], bytes[1], bytes[2]]
+}
+
+fn push_chunk(out: &mut Vec<u8>, fourcc: &[u8; 4], payload: &[u8]) {
+    out.extend_from_slice(fourcc);
+    out.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+    out.extend_from_slice(payload);
+    if payload.len() % 2 == 1 {
+        out.push(0);
+    }
+}
+
+/// The `VP8L` chunk, header and padding included, of a still WebP file.
+fn bitstream_chunk(webp: &[u8]) -> io::Result<&[u8]> {
+    let mut offset = 12;
+    while offset + 8 <= webp.len() {
+        let fourcc = &webp[offset..offset + 4];
+        let size = u32::from_le_bytes(webp[offset + 4..offset + 8].try_into().unwrap()) as usize;
+        let end = (offset + 8 + size + size % 2).min(webp.len());
+        if fourcc == b"VP8L" {
+            return Ok(&webp[offset..end]);
+        }
+        offset = end;
+    }
+    Err(io::Error::other("Encoded WebP frame has no VP8L chunk"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::codecs::gif::GifDecoder;
+    use image::codecs::webp::WebPDecoder;
+    use image::{AnimationDecoder, Rgba, RgbaImage};
+    use pretty_assertions::assert_eq;
+    use std::io::Cursor;
+
+    fn frames() -> Vec<DynamicImage> {
+        [
+            Rgba([255, 0, 0, 255]),
+            Rgba([0, 255, 0, 255]),
+            Rgba([0, 0, 255, 255]),
+        ]
+        .into_iter()
+        .map(|color| DynamicImage::ImageRgba8(RgbaImage::from_pixel(5, 3, color)))
+        .collect()
+    }
+
+    #[test]
+    fn encodes_animated_webp() {
+        let mut bytes = Vec::new();
+        encode_animation(&mut bytes, &frames(), AnimatedFormat::Webp, 50, 0).unwrap();
+
+        let decoder = WebPDecoder::new(Cursor::new(&bytes)).unwrap();
+        assert!(decoder.has_animation());
+        let decoded = decoder.into_frames().collect_frames().unwrap();
+        assert_eq!(decoded.len(), 3);
+        assert_eq!(decoded[1].buffer().get_pixel(4, 2), &Rgba([0, 255, 0, 255]));
+        assert_eq!(decoded[2].delay().numer_denom_ms(), (50, 1));
+    }
+
+    #[test]
+    fn encodes_animated_gif() {
+        let mut bytes = Vec::new();
+        encode_animation(&mut bytes, &frames(), AnimatedFormat::Gif, 100, 3).unwrap();
+
+        let decoded = GifDecoder::new(Cursor::new(&bytes))
+            .unwrap()
+            .into_frames()
+            .collect_frames()
+            .unwrap();
+        assert_eq!(decoded.len(), 3);
+        assert_eq!(decoded[0].buffer().get_pixel(0, 0), &Rgba([255, 0, 0, 255]));
+    }
+
+    /// The loop count as a number of plays, 0 for forever.
+    fn plays(bytes: &[u8], format: AnimatedFormat) -> u16 {
+        let find = |needle: &[u8]| bytes.windows(needle.len()).position(|w| w == needle);
+        let u16_at = |i: usize| u16::from_le_bytes([bytes[i], bytes[i + 1]]);
+        match format {
+            // "NETSCAPE2.0", sub-block size, sub-block id, repetitions
+            AnimatedFormat::Gif => match find(b"NETSCAPE2.0") {
+                Some(i) => match u16_at(i + 13) {
+                    0 => 0,
+                    repetitions => repetitions + 1,
+                },
+                None => 1,
+            },
+            // "ANIM", chunk size, background color, loop count
+            AnimatedFormat::Webp => u16_at(find(b"ANIM").unwrap() + 12),
+        }
+    }
+
+    #[test]
+    fn loop_count_means_plays_in_both_formats() {
+        for format in [AnimatedFormat::Gif, AnimatedFormat::Webp] {
+            for loop_count in [0, 1, 3] {
+                let mut bytes = Vec::new();
+                encode_animation(&mut bytes, &frames(), format, 50, loop_count).unwrap();
+                assert_eq!(plays(&bytes, format), loop_count, "{format:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_no_frames() {
+        assert!(encode_animation(Vec::new(), &[], AnimatedFormat::Webp, 50, 0).is_err());
+    }
+}
@@ -1,11 +1,15 @@
+use crate::animated::{AnimatedFormat, encode_animation};
 use crate::config::ResolvedConfig;
-use crate::describe_exit;
+use crate::imagediff::diff_images;
+use crate::placeholders::LaunchContext;
+use crate::{describe_exit, strip_cr_lf};
+use base64::Engine;
 use image::codecs::jpeg::JpegEncoder;
 use image::imageops::FilterType;
-use image::{ImageFormat, ImageReader};
+use image::{DynamicImage, ImageFormat, ImageReader};
 use std::fmt::{Display, Formatter};
 use std::fs::File;
-use std::io::{BufWriter, Write};
+use std::io::{BufReader, BufWriter, Write};
 use std::path::{Path, PathBuf};
 use std::process::{Child, Command, ExitStatus, Stdio};
 use std::str::FromStr;
@@ -31,6 +35,13 @@ pub enum CaptureFormat {
 }
 
 impl CaptureFormat {
+    pub const ALL: [CaptureFormat; 4] = [
+        CaptureFormat::Jpg,
+        CaptureFormat::Png,
+        CaptureFormat::Webp,
+        CaptureFormat::Qoi,
+    ];
+
     /// File extension (without leading dot) used for the generated image.
     pub fn extension(&self) -> &'static str {
         match self {
@@ -64,6 +75,77 @@ impl FromStr for CaptureFormat {
     }
 }
 
+/// A vpinball output window that can be captured.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CaptureWindow {
+    Playfield,
+    Backglass,
+    /// The score view / DMD window.
+    Dmd,
+    Topper,
+}
+
+impl CaptureWindow {
+    pub const ALL: [CaptureWindow; 4] = [
+        CaptureWindow::Playfield,
+        CaptureWindow::Backglass,
+        CaptureWindow::Dmd,
+        CaptureWindow::Topper,
+    ];
+
+    pub const VALUES: [&'static str; 4] = ["playfield", "backglass", "dmd", "topper"];
+
+    /// Basename of the media file written for this window.
+    fn media_basename(&self) -> &'static str {
+        match self {
+            CaptureWindow::Playfield => PLAYFIELD_MEDIA_BASENAME,
+            CaptureWindow::Backglass => "backglass",
+            CaptureWindow::Dmd => "dmd",
+            CaptureWindow::Topper => "topper",
+        }
+    }
+
+    /// File name prefixes of the frames vpinball writes for this window. The
+    /// DMD is called the score view in vpinball.
+    fn frame_prefixes(&self) -> &'static [&'static str] {
+        match self {
+            CaptureWindow::Playfield => &["Playfield_"],
+            CaptureWindow::Backglass => &["Backglass_"],
+            CaptureWindow::Dmd => &["ScoreView_", "DMD_"],
+            CaptureWindow::Topper => &["Topper_"],
+        }
+    }
+}
+
+impl Display for CaptureWindow {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            CaptureWindow::Playfield => "playfield",
+            CaptureWindow::Backglass => "backglass",
+            CaptureWindow::Dmd => "dmd",
+            CaptureWindow::Topper => "topper",
+        };
+        f.write_str(s)
+    }
+}
+
+impl FromStr for CaptureWindow {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "playfield" => Ok(CaptureWindow::Playfield),
+            "backglass" => Ok(CaptureWindow::Backglass),
+            "dmd" => Ok(CaptureWindow::Dmd),
+            "topper" => Ok(CaptureWindow::Topper),
+            other => Err(format!(
+                "Unknown window '{other}', expected one of {}",
+                Self::VALUES.join(", ")
+            )),
+        }
+    }
+}
+
 /// Options controlling a screenshot capture run.
 #[derive(Debug, Clone)]
 pub struct CaptureOptions {
@@ -73,7 +155,8 @@ pub struct CaptureOptions {
     /// Number of frames vpinball captures. The first one is used as the
     /// screenshot. More than one is rarely useful for a still image.
     pub frames: u32,
-    /// Capture framerate passed to vpinball (irrelevant for a single frame).
+    /// Capture framerate passed to vpinball (irrelevant for a single frame),
+    /// also the frame rate of an animation.
     pub fps: u32,
     /// When set, downscale the image so its width does not exceed this many
     /// pixels (keeping aspect ratio). `None` keeps the native window resolution.
@@ -81,6 +164,17 @@ pub struct CaptureOptions {
     /// Kill vpinball and fail the capture if it runs longer than this, so a
     /// hanging table does not stall a batch. `None` waits indefinitely.
     pub timeout: Option<Duration>,
+    /// The windows to write media for, `media/<window>.<ext>`.
+    pub windows: Vec<CaptureWindow>,
+    /// Encode all frames as an animation instead of a still of the first.
+    pub animation: Option<AnimationOptions>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AnimationOptions {
+    pub format: AnimatedFormat,
+    /// How often the animation plays, 0 loops forever.
+    pub loop_count: u16,
 }
 
 impl Default for CaptureOptions {
@@ -92,6 +186,8 @@ impl Default for CaptureOptions {
             fps: 1,
             max_width: None,
             timeout: Some(Duration::from_secs(60)),
+            windows: vec![CaptureWindow::Playfield],
+            animation: None,
         }
     }
 }
@@ -99,16 +195,59 @@ impl Default for CaptureOptions {
 /// Result of a single table capture.
 #[derive(Debug)]
 pub enum CaptureOutcome {
-    /// A new image was written to the given path.
-    Captured(PathBuf),
+    /// New images were written to the given paths.
+    Captured(Vec<PathBuf>),
     /// vpinball hung and was killed after the timeout, but it had already
-    /// written the frame, so the image was salvaged. The table is slow/flaky.
-    CapturedAfterHang(PathBuf),
-    /// An image already existed and `force` was not set.
-    Skipped(PathBuf),
+    /// written the frames, so the images were salvaged. The table is slow/flaky.
+    CapturedAfterHang(Vec<PathBuf>),
+    /// All images already existed and `force` was not set.
+    Skipped(Vec<PathBuf>),
+}
+
+/// A failed capture. Keeps the end of the vpinball output apart from the
+/// message so the batch report can store it, displayed below the message.
+#[derive(Debug)]
+pub struct CaptureFailure {
+    pub message: String,
+    pub log_tail: Vec<String>,
+}
+
+impl CaptureFailure {
+    fn new(message: String, log: &str) -> Self {
+        CaptureFailure {
+            message,
+            log_tail: log_tail(log),
+        }
+    }
+
+    /// The failure behind an error returned by [`capture_table`], if any.
+    pub fn from_error(error: &io::Error) -> Option<&CaptureFailure> {
+        error.get_ref()?.downcast_ref::<CaptureFailure>()
+    }
+}
+
+impl Display for CaptureFailure {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)?;
+        for line in &self.log_tail {
+            write!(f, "\n{line}")?;
+        }
+        Ok(())
+    }
 }
 
-/// Subfolder (next to the `.vpx`) the playfield image is written into.
+impl std::error::Error for CaptureFailure {}
+
+/// Comma separated paths, for reporting a [`CaptureOutcome`].
+pub fn display_paths(paths: &[PathBuf]) -> String {
+    paths
+        .iter()
+        .map(|p| p.display().to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Subfolder (next to the `.vpx`) the media images are written into.
 const MEDIA_DIR: &str = "media";
 
 /// Basename used for the playfield image.
@@ -117,31 +256,165 @@ const PLAYFIELD_MEDIA_BASENAME: &str = "table";
 /// Path where the playfield screenshot for the given table is written:
 /// `<table dir>/media/table.<ext>`.
 pub fn capture_image_path(vpx_path: &Path, format: CaptureFormat) -> PathBuf {
+    capture_media_path(vpx_path, CaptureWindow::Playfield, format)
+}
+
+/// Path where the animation of a window is written:
+/// `<table dir>/media/<window>_animated.<ext>`, next to the stills.
+pub fn animated_media_path(
+    vpx_path: &Path,
+    window: CaptureWindow,
+    format: AnimatedFormat,
+) -> PathBuf {
+    let dir = vpx_path.parent().unwrap_or_else(|| Path::new(""));
+    dir.join(MEDIA_DIR).join(format!(
+        "{}_animated.{}",
+        window.media_basename(),
+        format.extension()
+    ))
+}
+
+/// Path where the screenshot of a window is written:
+/// `<table dir>/media/<window>.<ext>`, `table.<ext>` for the playfield.
+pub fn capture_media_path(
+    vpx_path: &Path,
+    window: CaptureWindow,
+    format: CaptureFormat,
+) -> PathBuf {
     let dir = vpx_path.parent().unwrap_or_else(|| Path::new(""));
-    dir.join(MEDIA_DIR)
-        .join(format!("{PLAYFIELD_MEDIA_BASENAME}.{}", format.extension()))
+    dir.join(MEDIA_DIR).join(format!(
+        "{}.{}",
+        window.media_basename(),
+        format.extension()
+    ))
+}
+
+/// The playfield image of a table, whatever the format it was captured in.
+pub(crate) fn existing_playfield_image(vpx_path: &Path) -> Option<(CaptureFormat, PathBuf)> {
+    CaptureFormat::ALL
+        .into_iter()
+        .map(|format| (format, capture_image_path(vpx_path, format)))
+        .find(|(_, path)| path.is_file())
+}
+
+/// Path the previous playfield image is kept at by `--compare`:
+/// `<table dir>/media/table.baseline.<ext>`.
+pub fn baseline_image_path(vpx_path: &Path, format: CaptureFormat) -> PathBuf {
+    capture_image_path(vpx_path, format).with_extension(format!("baseline.{format}"))
+}
+
+/// Path of the `--compare` heatmap: `<table dir>/media/table.diff.png`.
+pub fn diff_image_path(vpx_path: &Path) -> PathBuf {
+    capture_image_path(vpx_path, CaptureFormat::Png).with_extension("diff.png")
+}
+
+/// A new playfield capture compared with the previous one.
+#[derive(Debug)]
+pub struct Comparison {
+    pub baseline: PathBuf,
+    pub heatmap: PathBuf,
+    /// Mean pixel difference in percent, see [`crate::imagediff`].
+    pub difference: f64,
+}
+
+/// Capture the table like [`capture_table`], always recapturing, and compare
+/// the new playfield image with the previous one. The previous image is kept
+/// as the baseline, an earlier baseline is replaced. When the capture fails
+/// the previous image is put back.
+///
+/// Without a previous image there is nothing to compare and the comparison is
+/// `None`.
+pub fn capture_and_compare(
+    config: &ResolvedConfig,
+    vpx_path: &Path,
+    options: &CaptureOptions,
+) -> io::Result<(CaptureOutcome, Option<Comparison>)> {
+    let previous = existing_playfield_image(vpx_path);
+    let mut baseline = None;
+    if let Some((format, previous)) = &previous {
+        for format in CaptureFormat::ALL {
+            let _ = fs::remove_file(baseline_image_path(vpx_path, format));
+        }
+        let baseline_path = baseline_image_path(vpx_path, *format);
+        fs::rename(previous, &baseline_path)?;
+        baseline = Some(baseline_path);
+    }
+
+    let options = CaptureOptions {
+        force: true,
+        ..options.clone()
+    };
+    let outcome = match capture_table(config, vpx_path, &options) {
+        Ok(outcome) => outcome,
+        Err(e) => {
+            if let (Some((_, previous)), Some(baseline)) = (&previous, &baseline)
+                && !capture_image_path(vpx_path, options.format).exists()
+            {
+                fs::rename(baseline, previous)?;
+            }
+            return Err(e);
+        }
+    };
+    let Some(baseline) = baseline else {
+        return Ok((outcome, None));
+    };
+
+    let current = decode_frame(&capture_image_path(vpx_path, options.format))?;
+    let diff = diff_images(&decode_frame(&baseline)?, &current);
+    let heatmap = diff_image_path(vpx_path);
+    diff.heatmap
+        .save_with_format(&heatmap, ImageFormat::Png)
+        .map_err(|e| io::Error::other(format!("Unable to write {}: {e}", heatmap.display())))?;
+    Ok((
+        outcome,
+        Some(Comparison {
+            baseline,
+            heatmap,
+            difference: diff.difference,
+        }),
+    ))
 }
 
-/// Capture a single playfield screenshot for `vpx_path` using vpinball's
-/// `-CaptureAttract` mode and write it to `<table dir>/media/table.<ext>`.
+/// Capture screenshots of the requested windows for `vpx_path` using
+/// vpinball's `-CaptureAttract` mode and write them to
+/// `<table dir>/media/<window>.<ext>`.
+///
+/// vpinball renders every enabled window into a `Capture/` folder next to the
+/// table as lossless `.qoi` frames, fast-forwarding ~30s of attract play before
+/// grabbing the frame. We convert the first frame of each window to the
+/// requested format and clean up. A backglass or DMD without a live frame
+/// (window disabled, B2S server not running) falls back to the image in the
+/// table's `.directb2s`.
 ///
-/// vpinball renders the playfield into a `Capture/` folder next to the table as
-/// lossless `.qoi` frames, fast-forwarding ~30s of attract play before grabbing
-/// the frame. We convert the first frame to the requested format and clean up.
+/// Failing to capture the playfield fails the capture, other windows that
+/// could not be captured are reported and skipped.
 pub fn capture_table(
     config: &ResolvedConfig,
     vpx_path: &Path,
     options: &CaptureOptions,
 ) -> io::Result<CaptureOutcome> {
-    let output_path = capture_image_path(vpx_path, options.format);
-    if output_path.exists() && !options.force {
-        return Ok(CaptureOutcome::Skipped(output_path));
+    let outputs: Vec<(CaptureWindow, PathBuf)> = options
+        .windows
+        .iter()
+        .map(|window| {
+            let path = match options.animation {
+                Some(animation) => animated_media_path(vpx_path, *window, animation.format),
+                None => capture_media_path(vpx_path, *window, options.format),
+            };
+            (*window, path)
+        })
+        .collect();
+    let pending: Vec<&(CaptureWindow, PathBuf)> = outputs
+        .iter()
+        .filter(|(_, path)| options.force || !path.exists())
+        .collect();
+    if pending.is_empty() {
+        return Ok(CaptureOutcome::Skipped(
+            outputs.into_iter().map(|(_, path)| path).collect(),
+        ));
     }
 
-    let capture_dir = vpx_path
-        .parent()
-        .map(|p| p.join("Capture"))
-        .unwrap_or_else(|| PathBuf::from("Capture"));
+    let capture_dir = capture_dir(vpx_path);
 
     // Start from a clean slate so we never pick up frames from a previous run.
     remove_capture_dir(&capture_dir);
@@ -158,35 +431,19 @@ pub fn capture_table(
         && !status.success()
     {
         remove_capture_dir(&capture_dir);
-        return Err(io::Error::other(format!(
-            "vpinball {} while capturing {}{}",
-            describe_exit(status),
-            vpx_path.display(),
-            log_tail(&run.log)
+        return Err(io::Error::other(CaptureFailure::new(
+            format!(
+                "vpinball {} while capturing {}",
+                describe_exit(status),
+                vpx_path.display()
+            ),
+            &run.log,
         )));
     }
 
-    // On timeout we still try to use the frame: many tables only hang on
-    // shutdown, after the playfield has already been written.
-    let frame = match first_playfield_frame(&capture_dir) {
-        Ok(frame) => frame,
-        Err(e) => {
-            remove_capture_dir(&capture_dir);
-            if timed_out {
-                let secs = options.timeout.map(|t| t.as_secs()).unwrap_or(0);
-                return Err(io::Error::other(format!(
-                    "vpinball did not finish within {secs}s and was terminated before \
-                     capturing a frame for {}{}",
-                    vpx_path.display(),
-                    log_tail(&run.log)
-                )));
-            }
-            return Err(e);
-        }
-    };
-
-    // Ensure the media/ folder exists before writing the image into it.
-    if let Some(parent) = output_path.parent()
+    // Ensure the media/ folder exists before writing the images into it.
+    if let Some((_, output_path)) = pending.first()
+        && let Some(parent) = output_path.parent()
         && let Err(e) = fs::create_dir_all(parent)
     {
         remove_capture_dir(&capture_dir);
@@ -194,46 +451,153 @@ pub fn capture_table(
     }
 
     crate::println!(
-        "Capture finished in {:.1}s, converting image",
+        "Capture finished in {:.1}s, converting images",
         elapsed.as_secs_f32()
     )?;
-    let convert_result = convert_frame(&frame, &output_path, options.format, options.max_width);
+    // On timeout we still try to use the frames: many tables only hang on
+    // shutdown, after the windows have already been written.
+    let mut written = Vec::new();
+    let mut failures = Vec::new();
+    for (window, output_path) in pending {
+        match write_window_media(&capture_dir, vpx_path, *window, output_path, options) {
+            Ok(()) => written.push(output_path.clone()),
+            Err(e) => failures.push((*window, e)),
+        }
+    }
 
     // Always clean up the intermediate qoi frames, even on conversion errors.
     remove_capture_dir(&capture_dir);
-    convert_result?;
+
+    let playfield_failed = failures
+        .iter()
+        .any(|(window, _)| *window == CaptureWindow::Playfield);
+    if playfield_failed || written.is_empty() {
+        let (_, e) = failures
+            .into_iter()
+            .find(|(window, _)| *window == CaptureWindow::Playfield || !playfield_failed)
+            .expect("a failure when nothing was written");
+        if timed_out {
+            let secs = options.timeout.map(|t| t.as_secs()).unwrap_or(0);
+            return Err(io::Error::other(CaptureFailure::new(
+                format!(
+                    "vpinball did not finish within {secs}s and was terminated before \
+                     capturing a frame for {}",
+                    vpx_path.display()
+                ),
+                &run.log,
+            )));
+        }
+        return Err(e);
+    }
+    for (window, e) in failures {
+        crate::eprintln!(
+            "Unable to capture the {window} for {}: {e}",
+            vpx_path.display()
+        )?;
+    }
 
     let table_file = vpx_path
         .file_name()
         .and_then(|n| n.to_str())
         .unwrap_or("table");
-    crate::println!(
-        "Capture complete for {} at {}",
-        table_file,
-        output_path.display()
-    )?;
+    for output_path in &written {
+        crate::println!(
+            "Capture complete for {} at {}",
+            table_file,
+            output_path.display()
+        )?;
+    }
 
     if timed_out {
-        Ok(CaptureOutcome::CapturedAfterHang(output_path))
+        Ok(CaptureOutcome::CapturedAfterHang(written))
     } else {
-        Ok(CaptureOutcome::Captured(output_path))
+        Ok(CaptureOutcome::Captured(written))
+    }
+}
+
+/// Write the media for one window from its first frame, or from the
+/// directb2s when vpinball did not render the window.
+fn write_window_media(
+    capture_dir: &Path,
+    vpx_path: &Path,
+    window: CaptureWindow,
+    output_path: &Path,
+    options: &CaptureOptions,
+) -> io::Result<()> {
+    if let Some(animation) = options.animation {
+        return write_window_animation(
+            capture_dir,
+            vpx_path,
+            window,
+            output_path,
+            options,
+            animation,
+        );
     }
+    match window_frames(capture_dir, window) {
+        Ok(frames) => convert_frame(&frames[0], output_path, options.format, options.max_width),
+        Err(e) => match b2s_fallback_image(vpx_path, window)? {
+            Some(image) => write_image(image, output_path, options.format, options.max_width),
+            None => Err(e),
+        },
+    }
+}
+
+/// Encode all frames of the window as an animation. A directb2s fallback
+/// becomes a single frame animation.
+fn write_window_animation(
+    capture_dir: &Path,
+    vpx_path: &Path,
+    window: CaptureWindow,
+    output_path: &Path,
+    options: &CaptureOptions,
+    animation: AnimationOptions,
+) -> io::Result<()> {
+    let images = match window_frames(capture_dir, window) {
+        Ok(frames) => frames
+            .iter()
+            .map(|frame| decode_frame(frame).map(|image| downscale(image, options.max_width)))
+            .collect::<io::Result<Vec<_>>>()?,
+        Err(e) => match b2s_fallback_image(vpx_path, window)? {
+            Some(image) => vec![downscale(image, options.max_width)],
+            None => return Err(e),
+        },
+    };
+    let frame_ms = 1000 / options.fps.max(1);
+    let mut writer = BufWriter::new(File::create(output_path)?);
+    encode_animation(
+        &mut writer,
+        &images,
+        animation.format,
+        frame_ms,
+        animation.loop_count,
+    )
+    .map_err(|e| io::Error::other(format!("Unable to write {}: {e}", output_path.display())))?;
+    writer.flush()
+}
+
+/// The `Capture/` folder vpinball writes its frames to, next to the table.
+pub(crate) fn capture_dir(vpx_path: &Path) -> PathBuf {
+    vpx_path
+        .parent()
+        .map(|p| p.join("Capture"))
+        .unwrap_or_else(|| PathBuf::from("Capture"))
 }
 
 /// Result of a vpinball capture run.
-struct CaptureRun {
+pub(crate) struct CaptureRun {
     /// `Some(status)` if vpinball exited on its own; `None` if it hit the
     /// timeout and we killed it.
-    status: Option<ExitStatus>,
+    pub status: Option<ExitStatus>,
     /// vpinball's (verbose) stdout/stderr, surfaced only on failure.
-    log: String,
+    pub log: String,
 }
 
 /// Spawn vpinball in capture mode and wait for it to exit (or be killed after
 /// the timeout). vpinball's verbose stdout/stderr is redirected to a temp file
 /// (avoiding pipe-buffer deadlocks if it stalls mid-output) and read back so the
 /// caller can surface a tail on failure.
-fn run_capture(
+pub(crate) fn run_capture(
     config: &ResolvedConfig,
     vpx_path: &Path,
     options: &CaptureOptions,
@@ -246,8 +610,9 @@ fn run_capture(
     // Reuse the environment from the first launch template (e.g. SDL_VIDEO_DRIVER
     // on Wayland) so capture behaves like a normal launch.
     if let Some(env) = config.launch_templates.first().and_then(|t| t.env.as_ref()) {
+        let context = LaunchContext::for_path(vpx_path);
         for (key, value) in env.iter() {
-            cmd.env(key, value);
+            cmd.env(key, context.substitute(value).map_err(io::Error::other)?);
         }
     }
     cmd.arg("-Ini");
@@ -291,19 +656,15 @@ fn wait_with_timeout(
     }
 }
 
-/// Keep only the last few lines of vpinball output for an error message,
-/// prefixed with a blank line, or an empty string when there is nothing.
-fn log_tail(output: &str) -> String {
+/// Keep only the last few non-empty lines of vpinball output.
+pub(crate) fn log_tail(output: &str) -> Vec<String> {
     let lines: Vec<&str> = output.lines().filter(|l| !l.trim().is_empty()).collect();
-    if lines.is_empty() {
-        return String::new();
-    }
     let start = lines.len().saturating_sub(10);
-    format!("\n{}", lines[start..].join("\n"))
+    lines[start..].iter().map(|l| l.to_string()).collect()
 }
 
-/// Find the lowest-indexed `Playfield_*.qoi` frame in the capture folder.
-fn first_playfield_frame(capture_dir: &Path) -> io::Result<PathBuf> {
+/// The frames of the window in the capture folder, in order, at least one.
+fn window_frames(capture_dir: &Path, window: CaptureWindow) -> io::Result<Vec<PathBuf>> {
     if !capture_dir.is_dir() {
         return Err(io::Error::other(format!(
             "vpinball did not produce a capture folder at {}",
@@ -313,29 +674,60 @@ fn first_playfield_frame(capture_dir: &Path) -> io::Result<PathBuf> {
     let mut frames: Vec<PathBuf> = fs::read_dir(capture_dir)?
         .filter_map(Result::ok)
         .map(|e| e.path())
-        .filter(|p| is_playfield_frame(p))
+        .filter(|p| is_window_frame(p, window))
         .collect();
     frames.sort();
-    frames.into_iter().next().ok_or_else(|| {
-        io::Error::other(format!(
-            "no playfield frame found in {}",
+    if frames.is_empty() {
+        return Err(io::Error::other(format!(
+            "no {window} frame found in {}",
             capture_dir.display()
-        ))
-    })
+        )));
+    }
+    Ok(frames)
 }
 
-/// Whether the path is a `Playfield_*.qoi` frame written by vpinball. The
-/// `noloop` capture keeps a `_tmp` suffix, so match on the prefix and extension.
-fn is_playfield_frame(path: &Path) -> bool {
+/// Whether the path is a `.qoi` frame written by vpinball for the window,
+/// e.g. `Playfield_00001.qoi`. The `noloop` capture keeps a `_tmp` suffix, so
+/// match on the prefix and extension.
+fn is_window_frame(path: &Path, window: CaptureWindow) -> bool {
     let is_qoi = path
         .extension()
         .and_then(|e| e.to_str())
         .is_some_and(|e| e.eq_ignore_ascii_case("qoi"));
-    let is_playfield = path
+    let is_window = path
         .file_name()
         .and_then(|n| n.to_str())
-        .is_some_and(|n| n.starts_with("Playfield_"));
-    is_qoi && is_playfield
+        .is_some_and(|n| window.frame_prefixes().iter().any(|p| n.starts_with(p)));
+    is_qoi && is_window
+}
+
+/// The backglass or DMD image from the `.directb2s` next to the table, if
+/// there is one.
+fn b2s_fallback_image(vpx_path: &Path, window: CaptureWindow) -> io::Result<Option<DynamicImage>> {
+    let b2s_path = vpx_path.with_extension("directb2s");
+    if !matches!(window, CaptureWindow::Backglass | CaptureWindow::Dmd) || !b2s_path.is_file() {
+        return Ok(None);
+    }
+    let reader = BufReader::new(File::open(&b2s_path)?);
+    let b2s = directb2s::read(reader)
+        .map_err(|e| io::Error::other(format!("Unable to read {}: {e}", b2s_path.display())))?;
+    let base64data = match window {
+        CaptureWindow::Backglass => b2s
+            .images
+            .backglass_image
+            .map(|i| i.value)
+            .or(b2s.images.backglass_off_image.map(|i| i.value)),
+        _ => b2s.images.dmd_image.map(|i| i.value),
+    };
+    let Some(base64data) = base64data else {
+        return Ok(None);
+    };
+    let bytes = base64::engine::general_purpose::STANDARD
+        .decode(strip_cr_lf(&base64data))
+        .map_err(|e| io::Error::other(format!("Invalid image in {}: {e}", b2s_path.display())))?;
+    let image = image::load_from_memory(&bytes)
+        .map_err(|e| io::Error::other(format!("Invalid image in {}: {e}", b2s_path.display())))?;
+    Ok(Some(image))
 }
 
 /// Write the captured qoi frame to `output_path` in the requested format,
@@ -353,19 +745,37 @@ fn convert_frame(
         return Ok(());
     }
 
-    let mut image = ImageReader::open(frame)?
+    write_image(decode_frame(frame)?, output_path, format, max_width)
+}
+
+fn decode_frame(frame: &Path) -> io::Result<DynamicImage> {
+    ImageReader::open(frame)?
         .with_guessed_format()?
         .decode()
-        .map_err(|e| io::Error::other(format!("Unable to decode {}: {e}", frame.display())))?;
+        .map_err(|e| io::Error::other(format!("Unable to decode {}: {e}", frame.display())))
+}
 
-    // Downscale (never upscale) so the width fits max_width, keeping aspect ratio.
-    if let Some(max_width) = max_width
-        && image.width() > max_width
-    {
-        let height =
-            ((image.height() as u64 * max_width as u64) / image.width() as u64).max(1) as u32;
-        image = image.resize(max_width, height, FilterType::Lanczos3);
+/// Downscale (never upscale) so the width fits max_width, keeping aspect ratio.
+fn downscale(image: DynamicImage, max_width: Option<u32>) -> DynamicImage {
+    match max_width {
+        Some(max_width) if image.width() > max_width => {
+            let height =
+                ((image.height() as u64 * max_width as u64) / image.width() as u64).max(1) as u32;
+            image.resize(max_width, height, FilterType::Lanczos3)
+        }
+        _ => image,
     }
+}
+
+/// Encode the image to `output_path` in the requested format, optionally
+/// downscaling first.
+fn write_image(
+    image: DynamicImage,
+    output_path: &Path,
+    format: CaptureFormat,
+    max_width: Option<u32>,
+) -> io::Result<()> {
+    let image = downscale(image, max_width);
 
     let write_err = |e| io::Error::other(format!("Unable to write {}: {e}", output_path.display()));
     match format {
@@ -376,6 +786,10 @@ fn convert_frame(
         CaptureFormat::Webp => image
             .save_with_format(output_path, ImageFormat::WebP)
             .map_err(write_err),
+        // only reached for images that did not come from a vpinball frame
+        CaptureFormat::Qoi => image
+            .save_with_format(output_path, ImageFormat::Qoi)
+            .map_err(write_err),
         // jpeg has no alpha channel, so drop it first; encode at JPEG_QUALITY.
         CaptureFormat::Jpg => {
             let rgb = image.to_rgb8();
@@ -384,11 +798,10 @@ fn convert_frame(
                 .encode_image(&rgb)
                 .map_err(write_err)
         }
-        CaptureFormat::Qoi => unreachable!("qoi handled above"),
     }
 }
 
-fn remove_capture_dir(capture_dir: &Path) {
+pub(crate) fn remove_capture_dir(capture_dir: &Path) {
     if capture_dir.exists() {
         // Best effort: leftover frames are harmless and a failure here should
         // not mask the capture result.
@@ -399,6 +812,7 @@ fn remove_capture_dir(capture_dir: &Path) {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use testdir::testdir;
 
     #[test]
     fn test_capture_format_from_str() {
@@ -427,6 +841,33 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_compare_image_paths() {
+        let vpx = Path::new("/tables/Foo/Foo.vpx");
+        assert_eq!(
+            baseline_image_path(vpx, CaptureFormat::Jpg),
+            PathBuf::from("/tables/Foo/media/table.baseline.jpg")
+        );
+        assert_eq!(
+            diff_image_path(vpx),
+            PathBuf::from("/tables/Foo/media/table.diff.png")
+        );
+    }
+
+    #[test]
+    fn test_existing_playfield_image() {
+        let dir = testdir!();
+        let vpx = dir.join("Foo.vpx");
+        assert!(existing_playfield_image(&vpx).is_none());
+        fs::create_dir_all(dir.join(MEDIA_DIR)).unwrap();
+        fs::write(dir.join(MEDIA_DIR).join("table.baseline.png"), b"").unwrap();
+        fs::write(dir.join(MEDIA_DIR).join("table.webp"), b"").unwrap();
+        assert_eq!(
+            existing_playfield_image(&vpx),
+            Some((CaptureFormat::Webp, dir.join(MEDIA_DIR).join("table.webp")))
+        );
+    }
+
     #[test]
     fn test_capture_image_path_no_parent() {
         let vpx = Path::new("Foo.vpx");
@@ -437,10 +878,145 @@ mod tests {
     }
 
     #[test]
-    fn test_is_playfield_frame() {
-        assert!(is_playfield_frame(Path::new("/c/Playfield_00001_tmp.qoi")));
-        assert!(is_playfield_frame(Path::new("/c/Playfield_00001.qoi")));
-        assert!(!is_playfield_frame(Path::new("/c/Backglass_00001.qoi")));
-        assert!(!is_playfield_frame(Path::new("/c/Playfield_00001.png")));
+    fn test_is_window_frame() {
+        let playfield = CaptureWindow::Playfield;
+        assert!(is_window_frame(
+            Path::new("/c/Playfield_00001_tmp.qoi"),
+            playfield
+        ));
+        assert!(is_window_frame(
+            Path::new("/c/Playfield_00001.qoi"),
+            playfield
+        ));
+        assert!(!is_window_frame(
+            Path::new("/c/Backglass_00001.qoi"),
+            playfield
+        ));
+        assert!(!is_window_frame(
+            Path::new("/c/Playfield_00001.png"),
+            playfield
+        ));
+        assert!(is_window_frame(
+            Path::new("/c/Backglass_00001.qoi"),
+            CaptureWindow::Backglass
+        ));
+        assert!(is_window_frame(
+            Path::new("/c/ScoreView_00001.qoi"),
+            CaptureWindow::Dmd
+        ));
+    }
+
+    #[test]
+    fn test_capture_media_path() {
+        let vpx = Path::new("/tables/Foo/Foo.vpx");
+        assert_eq!(
+            capture_media_path(vpx, CaptureWindow::Backglass, CaptureFormat::Png),
+            PathBuf::from("/tables/Foo/media/backglass.png")
+        );
+        assert_eq!(
+            capture_media_path(vpx, CaptureWindow::Dmd, CaptureFormat::Jpg),
+            PathBuf::from("/tables/Foo/media/dmd.jpg")
+        );
+        assert_eq!("Topper".parse::<CaptureWindow>(), Ok(CaptureWindow::Topper));
+    }
+
+    #[test]
+    fn test_animation_from_frames() {
+        let dir = testdir!();
+        let capture_dir = dir.join("Capture");
+        fs::create_dir_all(&capture_dir).unwrap();
+        for (index, shade) in [0u8, 128, 255].into_iter().enumerate() {
+            image::DynamicImage::ImageRgba8(image::RgbaImage::from_pixel(
+                8,
+                4,
+                image::Rgba([shade, shade, shade, 255]),
+            ))
+            .save_with_format(
+                capture_dir.join(format!("Playfield_{:05}.qoi", index + 1)),
+                ImageFormat::Qoi,
+            )
+            .unwrap();
+        }
+        let vpx_path = dir.join("Foo.vpx");
+        let animation = AnimationOptions {
+            format: AnimatedFormat::Gif,
+            loop_count: 0,
+        };
+        let output_path =
+            animated_media_path(&vpx_path, CaptureWindow::Playfield, animation.format);
+        assert_eq!(output_path, dir.join("media").join("table_animated.gif"));
+        fs::create_dir_all(output_path.parent().unwrap()).unwrap();
+        let options = CaptureOptions {
+            fps: 10,
+            max_width: Some(4),
+            animation: Some(animation),
+            ..CaptureOptions::default()
+        };
+
+        write_window_media(
+            &capture_dir,
+            &vpx_path,
+            CaptureWindow::Playfield,
+            &output_path,
+            &options,
+        )
+        .unwrap();
+
+        use image::AnimationDecoder;
+        let frames = image::codecs::gif::GifDecoder::new(io::BufReader::new(
+            File::open(&output_path).unwrap(),
+        ))
+        .unwrap()
+        .into_frames()
+        .collect_frames()
+        .unwrap();
+        assert_eq!(frames.len(), 3);
+        assert_eq!(frames[0].buffer().dimensions(), (4, 2));
+        assert_eq!(frames[0].delay().numer_denom_ms(), (100, 1));
+    }
+
+    #[test]
+    fn test_backglass_falls_back_to_directb2s() {
+        let dir = testdir!();
+        let vpx_path = dir.join("Foo.vpx");
+        let mut png = Vec::new();
+        DynamicImage::new_rgb8(4, 2)
+            .write_to(&mut io::Cursor::new(&mut png), ImageFormat::Png)
+            .unwrap();
+        let encoded = base64::engine::general_purpose::STANDARD.encode(&png);
+        let b2s = include_str!("../testdata/minimal.directb2s").replace("{IMAGE}", &encoded);
+        fs::write(vpx_path.with_extension("directb2s"), b2s).unwrap();
+        let output_path =
+            capture_media_path(&vpx_path, CaptureWindow::Backglass, CaptureFormat::Png);
+        fs::create_dir_all(output_path.parent().unwrap()).unwrap();
+        let options = CaptureOptions {
+            format: CaptureFormat::Png,
+            ..CaptureOptions::default()
+        };
+
+        // no capture folder, vpinball did not render the backglass
+        write_window_media(
+            &dir.join("Capture"),
+            &vpx_path,
+            CaptureWindow::Backglass,
+            &output_path,
+            &options,
+        )
+        .unwrap();
+        let image = image::open(&output_path).unwrap();
+        assert_eq!((image.width(), image.height()), (4, 2));
+
+        // no dmd image in the directb2s
+        let dmd_path = capture_media_path(&vpx_path, CaptureWindow::Dmd, CaptureFormat::Png);
+        assert!(
+            write_window_media(
+                &dir.join("Capture"),
+                &vpx_path,
+                CaptureWindow::Dmd,
+                &dmd_path,
+                &options,
+            )
+            .is_err()
+        );
     }
 }
@@ -0,0 +1,250 @@
+//! Json report of a batch `capture` run.
+//!
+//! The report is rewritten after every table, so after a crash or Ctrl-C it
+//! holds every table handled so far. That is what `--resume` (skip the tables
+//! already in the report) and `--retry-failed` (only the tables that failed
+//! or hung) work from.
+
+use crate::atomicwrite::atomic_write;
+use crate::capture::{CaptureFailure, CaptureOutcome};
+use crate::indexer::IsoSystemTime;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs::File;
+use std::io;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+const REPORT_FILE_NAME: &str = "capture_report.json";
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum CaptureStatus {
+    Captured,
+    /// vpinball hung, the images were salvaged after killing it.
+    CapturedAfterHang,
+    Skipped,
+    Failed,
+}
+
+impl CaptureStatus {
+    /// Failed or hung, the tables `--retry-failed` captures again.
+    pub fn needs_retry(&self) -> bool {
+        matches!(
+            self,
+            CaptureStatus::Failed | CaptureStatus::CapturedAfterHang
+        )
+    }
+}
+
+/// The outcome of capturing one table.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct CaptureReportEntry {
+    pub table: PathBuf,
+    pub status: CaptureStatus,
+    pub duration_secs: f64,
+    /// The images written, or that already existed when skipped.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub paths: Vec<PathBuf>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// The last lines of the vpinball output of a failed capture.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub log_tail: Vec<String>,
+    /// Difference with the previous playfield image in percent, `--compare` only.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub difference: Option<f64>,
+}
+
+impl CaptureReportEntry {
+    pub fn new(
+        table: &Path,
+        result: Result<&CaptureOutcome, &io::Error>,
+        duration: Duration,
+    ) -> Self {
+        let (status, paths, error, log_tail) = match result {
+            Ok(CaptureOutcome::Captured(paths)) => {
+                (CaptureStatus::Captured, paths.clone(), None, Vec::new())
+            }
+            Ok(CaptureOutcome::CapturedAfterHang(paths)) => (
+                CaptureStatus::CapturedAfterHang,
+                paths.clone(),
+                None,
+                Vec::new(),
+            ),
+            Ok(CaptureOutcome::Skipped(paths)) => {
+                (CaptureStatus::Skipped, paths.clone(), None, Vec::new())
+            }
+            Err(e) => match CaptureFailure::from_error(e) {
+                Some(failure) => (
+                    CaptureStatus::Failed,
+                    Vec::new(),
+                    Some(failure.message.clone()),
+                    failure.log_tail.clone(),
+                ),
+                None => (
+                    CaptureStatus::Failed,
+                    Vec::new(),
+                    Some(e.to_string()),
+                    Vec::new(),
+                ),
+            },
+        };
+        CaptureReportEntry {
+            table: table.to_path_buf(),
+            status,
+            duration_secs: duration.as_secs_f64(),
+            paths,
+            error,
+            log_tail,
+            difference: None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct CaptureReport {
+    pub started: IsoSystemTime,
+    pub updated: IsoSystemTime,
+    /// In capture order, one entry per table.
+    pub tables: Vec<CaptureReportEntry>,
+}
+
+impl CaptureReport {
+    pub fn new() -> Self {
+        let now = SystemTime::now();
+        CaptureReport {
+            started: now.into(),
+            updated: now.into(),
+            tables: Vec::new(),
+        }
+    }
+
+    pub fn read(report_path: &Path) -> io::Result<Self> {
+        let reader = BufReader::new(File::open(report_path)?);
+        serde_json::from_reader(reader).map_err(|e| {
+            io::Error::other(format!(
+                "Unable to read capture report {}: {e}",
+                report_path.display()
+            ))
+        })
+    }
+
+    pub fn write(&self, report_path: &Path) -> io::Result<()> {
+        if let Some(parent) = report_path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        atomic_write(report_path, |file| {
+            serde_json::to_writer_pretty(file, self).map_err(io::Error::other)
+        })
+    }
+
+    /// Add the entry, replacing an earlier one for the same table.
+    pub fn record(&mut self, entry: CaptureReportEntry) {
+        self.updated = SystemTime::now().into();
+        match self.tables.iter_mut().find(|e| e.table == entry.table) {
+            Some(existing) => *existing = entry,
+            None => self.tables.push(entry),
+        }
+    }
+
+    /// Tables with an entry, whatever the outcome.
+    pub fn handled(&self) -> HashSet<&Path> {
+        self.tables.iter().map(|e| e.table.as_path()).collect()
+    }
+
+    /// Tables that failed or hung.
+    pub fn needing_retry(&self) -> HashSet<&Path> {
+        self.tables
+            .iter()
+            .filter(|e| e.status.needs_retry())
+            .map(|e| e.table.as_path())
+            .collect()
+    }
+
+    pub fn count(&self, status: CaptureStatus) -> usize {
+        self.tables.iter().filter(|e| e.status == status).count()
+    }
+}
+
+/// `<config dir>/vpxtool/capture_report.json`, next to the config file.
+pub fn default_report_path() -> PathBuf {
+    dirs::config_dir()
+        .unwrap()
+        .join("vpxtool")
+        .join(REPORT_FILE_NAME)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use testdir::testdir;
+
+    #[test]
+    fn records_outcomes_and_round_trips() {
+        let dir = testdir!();
+        let report_path = dir.join("vpxtool").join(REPORT_FILE_NAME);
+        let mut report = CaptureReport::new();
+        report.record(CaptureReportEntry::new(
+            Path::new("/t/a.vpx"),
+            Ok(&CaptureOutcome::Captured(vec![PathBuf::from(
+                "/t/media/table.jpg",
+            )])),
+            Duration::from_millis(1500),
+        ));
+        report.record(CaptureReportEntry::new(
+            Path::new("/t/b.vpx"),
+            Ok(&CaptureOutcome::CapturedAfterHang(vec![])),
+            Duration::from_secs(60),
+        ));
+        let failure = CaptureFailure {
+            message: "vpinball crashed".to_string(),
+            log_tail: vec!["Script error".to_string()],
+        };
+        report.record(CaptureReportEntry::new(
+            Path::new("/t/c.vpx"),
+            Err(&io::Error::other(failure)),
+            Duration::from_secs(3),
+        ));
+        report.write(&report_path).unwrap();
+
+        let read = CaptureReport::read(&report_path).unwrap();
+        assert_eq!(read, report);
+        assert_eq!(read.tables[0].duration_secs, 1.5);
+        assert_eq!(read.tables[2].error.as_deref(), Some("vpinball crashed"));
+        assert_eq!(read.tables[2].log_tail, vec!["Script error"]);
+        assert_eq!(
+            read.handled(),
+            HashSet::from([
+                Path::new("/t/a.vpx"),
+                Path::new("/t/b.vpx"),
+                Path::new("/t/c.vpx")
+            ])
+        );
+        assert_eq!(
+            read.needing_retry(),
+            HashSet::from([Path::new("/t/b.vpx"), Path::new("/t/c.vpx")])
+        );
+    }
+
+    #[test]
+    fn retry_replaces_entry() {
+        let mut report = CaptureReport::new();
+        report.record(CaptureReportEntry::new(
+            Path::new("/t/a.vpx"),
+            Err(&io::Error::other("no frame")),
+            Duration::from_secs(3),
+        ));
+        report.record(CaptureReportEntry::new(
+            Path::new("/t/a.vpx"),
+            Ok(&CaptureOutcome::Captured(vec![])),
+            Duration::from_secs(30),
+        ));
+        assert_eq!(report.tables.len(), 1);
+        assert_eq!(report.count(CaptureStatus::Captured), 1);
+        assert_eq!(report.count(CaptureStatus::Failed), 0);
+        assert!(report.needing_retry().is_empty());
+    }
+}
@@ -1,20 +1,44 @@
-use crate::capture::{CaptureFormat, CaptureOptions, CaptureOutcome, capture_table};
+use crate::animated::AnimatedFormat;
+use crate::capture::{
+    AnimationOptions, CaptureFormat, CaptureOptions, CaptureOutcome, CaptureWindow, Comparison,
+    capture_and_compare, capture_table, display_paths,
+};
+use crate::capturereport;
+use crate::capturereport::{CaptureReport, CaptureReportEntry, CaptureStatus};
 use crate::config::{ResolvedConfig, SetupConfigResult};
-use crate::indexer::{DEFAULT_INDEX_FILE_NAME, IndexError, Progress};
-use crate::patcher::patch_vbs_file;
+use crate::doctor::Check;
+use crate::indexer::{
+    DEFAULT_INDEX_FILE_NAME, IndexError, IndexedTable, Progress, consider_sidecar_vbs,
+    find_vpx_files,
+};
+use crate::indexexport;
+use crate::indexexport::ExportFormat;
+use crate::indexquery::{IndexQuery, TableAsset};
+use crate::listing::{Cell, ColAlign, Column, ListFormat, print_aligned_table, print_list};
+use crate::patcher::{default_patch_rules_dir, load_patch_rules, patch_vbs_file};
+use crate::prune::{find_unused_assets, remove_assets};
+use crate::scriptfile::{read_script, restore_script_backup};
+use crate::scriptlint::{Severity, lint_script};
+use crate::scriptxref::{XrefCollection, XrefItem, xref_script};
+use crate::smoketest;
+use crate::smoketest::{ReportFormat, SmokeOutcome};
 use crate::{
     RemoveOnDrop, config, frontend, indexer, os_independent_file_name, path_exists, strip_cr_lf,
 };
 use base64::Engine;
+use chrono::{DateTime, Local, Utc};
 use clap::builder::Str;
 use clap::{Arg, ArgAction, ArgMatches, Command, arg};
 use colored::Colorize;
 use console::Emoji;
 use directb2s::read;
 use git_version::git_version;
-use indicatif::{ProgressBar, ProgressDrawTarget, ProgressStyle};
+use indicatif::{HumanBytes, ProgressBar, ProgressDrawTarget, ProgressStyle};
 use log::{LevelFilter, info};
 use pinmame_nvram::dips::get_all_dip_switches;
+use rayon::prelude::*;
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet};
 use std::error::Error;
 use std::ffi::OsStr;
 use std::fmt::Display;
@@ -23,13 +47,15 @@ use std::io;
 use std::io::{BufReader, BufWriter, Read, Write};
 use std::path::{Path, PathBuf};
 use std::process::{ExitCode, exit};
-use std::time::{Duration, SystemTime};
+use std::time::{Duration, Instant, SystemTime};
 use vpin::filesystem::RealFileSystem;
 use vpin::vpx;
 use vpin::vpx::expanded::ExpandOptions;
 use vpin::vpx::export::gltf_export::{GltfExportOptions, GltfFormat, export_gltf};
 use vpin::vpx::export::obj_export::{ExportUnits, ObjExportOptions, export_obj};
+use vpin::vpx::gameitem::GameItemEnum;
 use vpin::vpx::jsonmodel::{game_data_to_json, info_to_json};
+use vpin::vpx::sound::{OutputTarget, SoundData, write_sound};
 use vpin::vpx::{ExtractResult, VerifyResult, expanded, extractvbs, importvbs, verify};
 
 // see https://github.com/fusion-engineering/rust-git-version/issues/21
@@ -64,6 +90,7 @@ enum LockAction {
 }
 
 const CMD_LS: &str = "ls";
+const CMD_PRUNE: &str = "prune";
 
 const CMD_CONFIG: &str = "config";
 const CMD_CONFIG_SETUP: &str = "setup";
@@ -79,6 +106,9 @@ const CMD_SCRIPT_IMPORT: &str = "import";
 const CMD_SCRIPT_PATCH: &str = "patch";
 const CMD_SCRIPT_EDIT: &str = "edit";
 const CMD_SCRIPT_DIFF: &str = "diff";
+const CMD_SCRIPT_LINT: &str = "lint";
+const CMD_SCRIPT_UNPATCH: &str = "unpatch";
+const CMD_SCRIPT_XREF: &str = "xref";
 
 const CMD_INFO: &str = "info";
 const CMD_INFO_SHOW: &str = "show";
@@ -90,9 +120,14 @@ const CMD_INFO_DIFF: &str = "diff";
 const CMD_IMAGES: &str = "images";
 const CMD_IMAGES_WEBP: &str = "webp";
 const CMD_IMAGES_LIST: &str = "list";
+const CMD_IMAGES_EXTRACT: &str = "extract";
+const CMD_IMAGES_REPLACE: &str = "replace";
 
 const CMD_SOUNDS: &str = "sounds";
 const CMD_SOUNDS_LIST: &str = "list";
+const CMD_SOUNDS_EXTRACT: &str = "extract";
+const CMD_SOUNDS_REPLACE: &str = "replace";
+const CMD_SOUNDS_IMPORT: &str = "import";
 
 const CMD_COLLECTIONS: &str = "collections";
 const CMD_COLLECTIONS_LIST: &str = "list";
@@ -122,16 +157,49 @@ const CMD_EXPORT_OBJ: &str = "obj";
 const CMD_EXPORT_GLTF: &str = "gltf";
 const CMD_EXPORT_VPXZ: &str = "vpxz";
 
+const CMD_IMPORT_VPXZ: &str = "import-vpxz";
+
+const CMD_VPXZ: &str = "vpxz";
+const CMD_VPXZ_VERIFY: &str = "verify";
+
 const CMD_INDEX: &str = "index";
+const CMD_INDEX_QUERY: &str = "query";
+const CMD_INDEX_EXPORT: &str = "export";
+
+const CMD_DOCTOR: &str = "doctor";
 
 const CMD_CAPTURE: &str = "capture";
 
+const CMD_SMOKETEST: &str = "smoketest";
+
 const ARG_VERBOSE: &str = "VERBOSE";
 const ARG_MAX_DEPTH: &str = "MAX_DEPTH";
 const ARG_FORCE: &str = "FORCE";
 const ARG_FORMAT: &str = "FORMAT";
 const ARG_MAX_WIDTH: &str = "MAX_WIDTH_PX";
 const ARG_TIMEOUT: &str = "TIMEOUT_SECS";
+const ARG_WINDOW: &str = "WINDOW";
+const ARG_ANIMATED: &str = "ANIMATED";
+const ARG_FRAMES: &str = "FRAMES";
+const ARG_FPS: &str = "FPS";
+const ARG_LOOP_COUNT: &str = "LOOP_COUNT";
+const ARG_REPORT: &str = "REPORT";
+const ARG_RESUME: &str = "RESUME";
+const ARG_RETRY_FAILED: &str = "RETRY_FAILED";
+const ARG_COMPARE: &str = "COMPARE";
+const ARG_THRESHOLD: &str = "THRESHOLD";
+const ARG_EXECUTABLE: &str = "EXECUTABLE";
+const ARG_OUTPUT: &str = "OUTPUT";
+
+/// 3 seconds of attract mode.
+const ANIMATED_DEFAULT_FRAMES: u32 = 60;
+const ANIMATED_DEFAULT_FPS: u32 = 20;
+
+/// Mean pixel difference, in percent, above which `capture --compare` reports a table.
+const DEFAULT_COMPARE_THRESHOLD: f64 = 5.0;
+
+/// Textures above this are of little use on a phone screen.
+const VPXZ_DEFAULT_MAX_TEXTURE_SIZE: &str = "2048";
 
 pub(crate) struct ProgressBarProgress {
     pb: ProgressBar,
@@ -221,8 +289,9 @@ fn handle_command(matches: ArgMatches) -> io::Result<ExitCode> {
                 let path = sub_matches.get_one::<String>("VPXPATH").map(|s| s.as_str());
                 let path = path.unwrap_or("");
                 let expanded_path = path_exists(path)?;
+                let force = sub_matches.get_flag("FORCE");
                 crate::println!("importing info for {}", expanded_path.display())?;
-                info_import(&expanded_path)
+                info_import(&expanded_path, force)
             }
             Some((CMD_INFO_EDIT, sub_matches)) => {
                 let path = sub_matches.get_one::<String>("VPXPATH").map(|s| s.as_str());
@@ -340,7 +409,9 @@ fn handle_command(matches: ArgMatches) -> io::Result<ExitCode> {
             }
         }
         Some((CMD_INDEX, sub_matches)) => handle_index(sub_matches),
+        Some((CMD_DOCTOR, sub_matches)) => handle_doctor(sub_matches),
         Some((CMD_CAPTURE, sub_matches)) => handle_capture(sub_matches),
+        Some((CMD_SMOKETEST, sub_matches)) => handle_smoketest(sub_matches),
         Some((CMD_SCRIPT, sub_matches)) => match sub_matches.subcommand() {
             Some((CMD_SCRIPT_SHOW, sub_matches)) => {
                 let path = sub_matches
@@ -409,6 +480,24 @@ fn handle_command(matches: ArgMatches) -> io::Result<ExitCode> {
                 crate::println!("{}", diff)?;
                 Ok(ExitCode::SUCCESS)
             }
+            Some((CMD_SCRIPT_LINT, sub_matches)) => handle_script_lint(sub_matches),
+            Some((CMD_SCRIPT_XREF, sub_matches)) => handle_script_xref(sub_matches),
+            Some((CMD_SCRIPT_UNPATCH, sub_matches)) => {
+                let path = sub_matches
+                    .get_one::<String>("VPXPATH")
+                    .map(|s| s.as_str())
+                    .unwrap_or_default();
+
+                let expanded_path = path_exists(path)?;
+                let vbs_path = vpx::vbs_path_for(&expanded_path);
+                match restore_script_backup(&vbs_path) {
+                    Ok(()) => {
+                        crate::println!("RESTORED {}", vbs_path.display())?;
+                        Ok(ExitCode::SUCCESS)
+                    }
+                    Err(e) => fail(e.to_string()),
+                }
+            }
             Some((CMD_SCRIPT_PATCH, sub_matches)) => {
                 let path = sub_matches
                     .get_one::<String>("VPXPATH")
@@ -416,6 +505,10 @@ fn handle_command(matches: ArgMatches) -> io::Result<ExitCode> {
                     .unwrap_or_default();
 
                 let expanded_path = path_exists(path)?;
+                let rules = match load_patch_rules(&default_patch_rules_dir()) {
+                    Ok(rules) => rules,
+                    Err(e) => return fail(e.to_string()),
+                };
                 let vbs_path = match extractvbs(&expanded_path, None, false) {
                     Ok(ExtractResult::Existed(vbs_path)) => {
                         let warning =
@@ -430,7 +523,7 @@ fn handle_command(matches: ArgMatches) -> io::Result<ExitCode> {
                     Err(e) => return fail_with_error("Error extracting vbs", e),
                 };
 
-                let applied = patch_vbs_file(&vbs_path)?;
+                let applied = patch_vbs_file(&vbs_path, &rules)?;
                 if applied.is_empty() {
                     crate::println!("No patches applied")?;
                 } else {
@@ -729,10 +822,16 @@ fn handle_command(matches: ArgMatches) -> io::Result<ExitCode> {
                 Ok(ExitCode::SUCCESS)
             }
             Some((CMD_IMAGES_LIST, sub_matches)) => handle_images_list(sub_matches),
+            Some((CMD_IMAGES_EXTRACT, sub_matches)) => handle_images_extract(sub_matches),
+            Some((CMD_IMAGES_REPLACE, sub_matches)) => handle_images_replace(sub_matches),
             _ => unreachable!(),
         },
+        Some((CMD_PRUNE, sub_matches)) => handle_prune(sub_matches),
         Some((CMD_SOUNDS, sub_matches)) => match sub_matches.subcommand() {
             Some((CMD_SOUNDS_LIST, sub_matches)) => handle_sounds_list(sub_matches),
+            Some((CMD_SOUNDS_EXTRACT, sub_matches)) => handle_sounds_extract(sub_matches),
+            Some((CMD_SOUNDS_REPLACE, sub_matches)) => handle_sounds_replace(sub_matches),
+            Some((CMD_SOUNDS_IMPORT, sub_matches)) => handle_sounds_import(sub_matches),
             _ => unreachable!(),
         },
         Some((CMD_COLLECTIONS, sub_matches)) => match sub_matches.subcommand() {
@@ -801,37 +900,26 @@ fn handle_command(matches: ArgMatches) -> io::Result<ExitCode> {
             Some((CMD_EXPORT_VPXZ, sub_matches)) => handle_export_vpxz(sub_matches),
             _ => unreachable!(),
         },
+        Some((CMD_IMPORT_VPXZ, sub_matches)) => handle_import_vpxz(sub_matches),
+        Some((CMD_VPXZ, sub_matches)) => match sub_matches.subcommand() {
+            Some((CMD_VPXZ_VERIFY, sub_matches)) => handle_vpxz_verify(sub_matches),
+            _ => unreachable!(),
+        },
         _ => unreachable!(), // If all subcommands are defined above, anything else is unreachable!()
     }
 }
 
 fn handle_index(sub_matches: &ArgMatches) -> io::Result<ExitCode> {
+    match sub_matches.subcommand() {
+        Some((CMD_INDEX_QUERY, sub_matches)) => return handle_index_query(sub_matches),
+        Some((CMD_INDEX_EXPORT, sub_matches)) => return handle_index_export(sub_matches),
+        _ => {}
+    }
     let recursive = sub_matches.get_flag("RECURSIVE");
     let force = sub_matches.get_flag("FORCE");
     let max_depth_cli = sub_matches.get_one::<usize>(ARG_MAX_DEPTH).copied();
-    let tables_folders_path_arg = sub_matches
-        .get_one::<String>("VPXROOTPATH")
-        .map(|s| s.as_str());
-    let index_file_arg = sub_matches
-        .get_one::<String>("INDEX_FILE")
-        .map(|s| s.as_str());
     let config = config::load_config()?;
-
-    let tables_folder_path = match tables_folders_path_arg {
-        Some(path) => path_exists(path)?,
-        None => match &config {
-            Some((_, config)) => config.tables_folder.clone(),
-            None => {
-                crate::eprintln!("No VPXROOTPATH provided up and no vpxtool config file found")?;
-                exit(1);
-            }
-        },
-    };
-
-    let tables_index_path = match index_file_arg {
-        Some(path) => PathBuf::from(path),
-        None => tables_folder_path.join(DEFAULT_INDEX_FILE_NAME),
-    };
+    let (tables_folder_path, tables_index_path) = index_paths(sub_matches, &config)?;
 
     let global_pinmame_folder = config.as_ref().map(|(_, c)| c.global_pinmame_folder());
     let configured_pinmame_folder = config
@@ -883,6 +971,298 @@ fn handle_index(sub_matches: &ArgMatches) -> io::Result<ExitCode> {
     Ok(ExitCode::SUCCESS)
 }
 
+/// The tables folder and index file from the `VPXROOTPATH` and `INDEX_FILE`
+/// args, falling back to the config file.
+fn index_paths(
+    sub_matches: &ArgMatches,
+    config: &Option<(PathBuf, ResolvedConfig)>,
+) -> io::Result<(PathBuf, PathBuf)> {
+    let tables_folders_path_arg = sub_matches
+        .get_one::<String>("VPXROOTPATH")
+        .map(|s| s.as_str());
+    let index_file_arg = sub_matches
+        .get_one::<String>("INDEX_FILE")
+        .map(|s| s.as_str());
+
+    let tables_folder_path = match tables_folders_path_arg {
+        Some(path) => path_exists(path)?,
+        None => match config {
+            Some((_, config)) => config.tables_folder.clone(),
+            None => {
+                crate::eprintln!("No VPXROOTPATH provided up and no vpxtool config file found")?;
+                exit(1);
+            }
+        },
+    };
+
+    let tables_index_path = match index_file_arg {
+        Some(path) => PathBuf::from(path),
+        None => tables_folder_path.join(DEFAULT_INDEX_FILE_NAME),
+    };
+    Ok((tables_folder_path, tables_index_path))
+}
+
+/// The table filters shared by `index query` and `export vpxz --all`.
+fn index_query_args() -> [Arg; 5] {
+    [
+        Arg::new("MISSING_ROM")
+            .long("missing-rom")
+            .num_args(0)
+            .help("Only tables that require PinMAME but have no ROM"),
+        Arg::new("REQUIRES_PINMAME")
+            .long("requires-pinmame")
+            .num_args(0)
+            .help("Only tables that require PinMAME"),
+        Arg::new("HAS")
+            .long("has")
+            .num_args(1)
+            .action(ArgAction::Append)
+            .value_parser(TableAsset::VALUES)
+            .help("Only tables with this asset, can be repeated"),
+        Arg::new("ROM")
+            .long("rom")
+            .num_args(1)
+            .help("Only tables using this ROM (game name, case-insensitive)"),
+        Arg::new("MODIFIED_SINCE")
+            .long("modified-since")
+            .num_args(1)
+            .help("Only tables modified since this date (YYYY-MM-DD or RFC 3339)"),
+    ]
+}
+
+fn index_query(sub_matches: &ArgMatches) -> io::Result<IndexQuery> {
+    let modified_since = sub_matches
+        .get_one::<String>("MODIFIED_SINCE")
+        .map(|s| crate::indexquery::parse_since(s).map_err(io::Error::other))
+        .transpose()?;
+    Ok(IndexQuery {
+        missing_rom: sub_matches.get_flag("MISSING_ROM"),
+        requires_pinmame: sub_matches.get_flag("REQUIRES_PINMAME"),
+        has: sub_matches
+            .get_many::<String>("HAS")
+            .into_iter()
+            .flatten()
+            .map(|s| s.parse().map_err(io::Error::other))
+            .collect::<io::Result<_>>()?,
+        rom: sub_matches.get_one::<String>("ROM").cloned(),
+        modified_since,
+    })
+}
+
+fn handle_index_query(sub_matches: &ArgMatches) -> io::Result<ExitCode> {
+    let format = list_format(sub_matches)?;
+    let query = match index_query(sub_matches) {
+        Ok(query) => query,
+        Err(e) => return fail(e.to_string()),
+    };
+    let config = config::load_config()?;
+    let (tables_folder_path, tables_index_path) = index_paths(sub_matches, &config)?;
+    let Some(index) = indexer::read_index_json(&tables_index_path, Some(&tables_folder_path))?
+    else {
+        return fail(format!(
+            "No index found at {}, run `vpxtool index` first",
+            tables_index_path.display()
+        ));
+    };
+    let tables = query.filter(index.tables());
+
+    let optional_path = |path: Option<&PathBuf>| match path {
+        Some(path) => Cell::text(path.display().to_string()),
+        None => Cell::Empty,
+    };
+    let last_modified =
+        |table: &IndexedTable| -> DateTime<Utc> { SystemTime::from(table.last_modified).into() };
+    let (columns, rows): (Vec<Column>, Vec<Vec<Cell>>) = if format == ListFormat::Table {
+        let columns = vec![
+            Column::left("NAME", "table_name"),
+            Column::left("ROM", "game_name"),
+            Column::left("PINMAME", "requires_pinmame"),
+            Column::left("ROM_FILE", "rom"),
+            Column::left("B2S", "b2s"),
+            Column::left("WHEEL", "wheel"),
+            Column::left("ALTSOUND", "altsound"),
+            Column::left("ALTCOLOR", "altcolor"),
+            Column::left("PUP", "pup"),
+            Column::left("MODIFIED", "last_modified"),
+            Column::left("PATH", "path"),
+        ];
+        let rows = tables
+            .iter()
+            .map(|table| {
+                let modified: DateTime<Local> = last_modified(table).into();
+                vec![
+                    Cell::text(table.table_info.table_name.clone().unwrap_or_default()),
+                    Cell::text(table.game_name.clone().unwrap_or_default()),
+                    Cell::flag(table.requires_pinmame),
+                    Cell::flag(table.rom_path().is_some()),
+                    Cell::flag(table.b2s_path.is_some()),
+                    Cell::flag(table.wheel_path.is_some()),
+                    Cell::flag(table.altsound_path.is_some()),
+                    Cell::flag(table.altcolor_path.is_some()),
+                    Cell::flag(table.pup_pack_path.is_some()),
+                    Cell::text(modified.format("%Y-%m-%d %H:%M").to_string()),
+                    Cell::text(table.path.display().to_string()),
+                ]
+            })
+            .collect();
+        (columns, rows)
+    } else {
+        let columns = vec![
+            Column::left("PATH", "path"),
+            Column::left("NAME", "table_name"),
+            Column::left("ROM", "game_name"),
+            Column::left("PINMAME", "requires_pinmame"),
+            Column::left("ROM_PATH", "rom_path"),
+            Column::left("B2S_PATH", "b2s_path"),
+            Column::left("WHEEL_PATH", "wheel_path"),
+            Column::left("ALTSOUND_PATH", "altsound_path"),
+            Column::left("ALTCOLOR_PATH", "altcolor_path"),
+            Column::left("PUP_PACK_PATH", "pup_pack_path"),
+            Column::left("MODIFIED", "last_modified"),
+        ];
+        let rows = tables
+            .iter()
+            .map(|table| {
+                vec![
+                    Cell::text(table.path.display().to_string()),
+                    table
+                        .table_info
+                        .table_name
+                        .clone()
+                        .map(Cell::Text)
+                        .unwrap_or(Cell::Empty),
+                    table
+                        .game_name
+                        .clone()
+                        .map(Cell::Text)
+                        .unwrap_or(Cell::Empty),
+                    Cell::flag(table.requires_pinmame),
+                    optional_path(table.rom_path()),
+                    optional_path(table.b2s_path.as_ref()),
+                    optional_path(table.wheel_path.as_ref()),
+                    optional_path(table.altsound_path.as_ref()),
+                    optional_path(table.altcolor_path.as_ref()),
+                    optional_path(table.pup_pack_path.as_ref()),
+                    Cell::text(last_modified(table).to_rfc3339()),
+                ]
+            })
+            .collect();
+        (columns, rows)
+    };
+    print_list(format, &columns, &rows)?;
+    Ok(ExitCode::SUCCESS)
+}
+
+fn handle_index_export(sub_matches: &ArgMatches) -> io::Result<ExitCode> {
+    let format = sub_matches
+        .get_one::<String>(ARG_FORMAT)
+        .map(|s| s.parse::<ExportFormat>())
+        .transpose()
+        .map_err(io::Error::other)?
+        .expect("format is required");
+    let output = std::path::absolute(
+        sub_matches
+            .get_one::<String>(ARG_OUTPUT)
+            .map(PathBuf::from)
+            .unwrap_or_else(|| PathBuf::from(format.default_file_name())),
+    )?;
+    let base = match sub_matches.get_one::<String>("RELATIVE_TO") {
+        Some(base) => std::path::absolute(base)?,
+        None => output.parent().map(Path::to_path_buf).unwrap_or_default(),
+    };
+    let config = config::load_config()?;
+    let (tables_folder_path, tables_index_path) = index_paths(sub_matches, &config)?;
+    let Some(index) = indexer::read_index_json(&tables_index_path, Some(&tables_folder_path))?
+    else {
+        return fail(format!(
+            "No index found at {}, run `vpxtool index` first",
+            tables_index_path.display()
+        ));
+    };
+    let tables = IndexQuery::default().filter(index.tables());
+    let exported = indexexport::export(format, &tables, &base)?;
+    crate::atomicwrite::atomic_write(&output, |file| file.write_all(exported.as_bytes()))?;
+    crate::println!(
+        "Exported {} tables as {format} to {}",
+        tables.len(),
+        output.display()
+    )?;
+    Ok(ExitCode::SUCCESS)
+}
+
+fn handle_doctor(sub_matches: &ArgMatches) -> io::Result<ExitCode> {
+    let format = list_format(sub_matches)?;
+    let skip: Vec<Check> = sub_matches
+        .get_many::<String>("SKIP")
+        .into_iter()
+        .flatten()
+        .map(|s| s.parse().map_err(io::Error::other))
+        .collect::<io::Result<_>>()?;
+    let config = config::load_config()?;
+    let (tables_folder_path, tables_index_path) = index_paths(sub_matches, &config)?;
+    let Some(index) = indexer::read_index_json(&tables_index_path, Some(&tables_folder_path))?
+    else {
+        return fail(format!(
+            "No index found at {}, run `vpxtool index` first",
+            tables_index_path.display()
+        ));
+    };
+    let tables = IndexQuery::default().filter(index.tables());
+    let results = crate::doctor::check_tables(&tables, &skip);
+    let problem_count: usize = results.iter().map(|(_, problems)| problems.len()).sum();
+
+    if format == ListFormat::Table {
+        for (table, problems) in &results {
+            crate::println!("{} {}", NOK, table.path.display())?;
+            let width = problems
+                .iter()
+                .map(|p| p.check.to_string().len())
+                .max()
+                .unwrap_or(0);
+            for problem in problems {
+                crate::println!(
+                    "   {:<width$}  {}",
+                    problem.check.to_string().truecolor(255, 125, 0),
+                    problem.message
+                )?;
+            }
+        }
+        if problem_count == 0 {
+            crate::println!("{OK} No problems found in {} tables", tables.len())?;
+        } else {
+            crate::println!(
+                "{problem_count} problem(s) in {} of {} tables",
+                results.len(),
+                tables.len()
+            )?;
+        }
+    } else {
+        let columns = [
+            Column::left("PATH", "path"),
+            Column::left("CHECK", "check"),
+            Column::left("MESSAGE", "message"),
+        ];
+        let rows: Vec<Vec<Cell>> = results
+            .iter()
+            .flat_map(|(table, problems)| {
+                problems.iter().map(|problem| {
+                    vec![
+                        Cell::text(table.path.display().to_string()),
+                        Cell::text(problem.check.to_string()),
+                        Cell::text(&problem.message),
+                    ]
+                })
+            })
+            .collect();
+        print_list(format, &columns, &rows)?;
+    }
+    if problem_count == 0 {
+        Ok(ExitCode::SUCCESS)
+    } else {
+        Ok(ExitCode::FAILURE)
+    }
+}
+
 fn handle_capture(sub_matches: &ArgMatches) -> io::Result<ExitCode> {
     let force = sub_matches.get_flag(ARG_FORCE);
     let format = sub_matches
@@ -897,12 +1277,61 @@ fn handle_capture(sub_matches: &ArgMatches) -> io::Result<ExitCode> {
         .copied()
         .filter(|&s| s > 0)
         .map(Duration::from_secs);
+    let mut windows: Vec<CaptureWindow> = Vec::new();
+    for window in sub_matches
+        .get_many::<String>(ARG_WINDOW)
+        .into_iter()
+        .flatten()
+    {
+        let window = window.parse::<CaptureWindow>().map_err(io::Error::other)?;
+        if !windows.contains(&window) {
+            windows.push(window);
+        }
+    }
+    let animation = sub_matches
+        .get_one::<String>(ARG_ANIMATED)
+        .map(|s| s.parse::<AnimatedFormat>())
+        .transpose()
+        .map_err(io::Error::other)?
+        .map(|format| AnimationOptions {
+            format,
+            loop_count: sub_matches
+                .get_one::<u16>(ARG_LOOP_COUNT)
+                .copied()
+                .unwrap_or(0),
+        });
+    // a still only needs one frame, an animation a few seconds of attract mode
+    let (default_frames, default_fps) = if animation.is_some() {
+        (ANIMATED_DEFAULT_FRAMES, ANIMATED_DEFAULT_FPS)
+    } else {
+        (1, 1)
+    };
+    let frames = sub_matches
+        .get_one::<u32>(ARG_FRAMES)
+        .copied()
+        .unwrap_or(default_frames);
+    let fps = sub_matches
+        .get_one::<u32>(ARG_FPS)
+        .copied()
+        .unwrap_or(default_fps);
+    let compare = sub_matches.get_flag(ARG_COMPARE);
+    let threshold = sub_matches
+        .get_one::<f64>(ARG_THRESHOLD)
+        .copied()
+        .unwrap_or(DEFAULT_COMPARE_THRESHOLD);
+    // the comparison is done on the playfield image
+    if compare && !windows.contains(&CaptureWindow::Playfield) {
+        windows.insert(0, CaptureWindow::Playfield);
+    }
     let options = CaptureOptions {
         format,
         force,
+        frames,
+        fps,
         max_width,
         timeout,
-        ..CaptureOptions::default()
+        windows,
+        animation,
     };
 
     let (config_path, config) = config::load_or_setup_config()?;
@@ -923,23 +1352,30 @@ fn handle_capture(sub_matches: &ArgMatches) -> io::Result<ExitCode> {
     // Single table.
     if let Some(vpx_path_arg) = sub_matches.get_one::<String>("VPXPATH") {
         let vpx_path = path_exists(vpx_path_arg)?;
-        return match capture_table(&config, &vpx_path, &options) {
+        let result = capture_one(&config, &vpx_path, &options, compare);
+        if let Ok((_, Some(comparison))) = &result {
+            print_comparison(comparison, threshold)?;
+            if comparison.difference > threshold {
+                return Ok(ExitCode::FAILURE);
+            }
+        }
+        return match result.map(|(outcome, _)| outcome) {
             Ok(CaptureOutcome::Captured(_)) => Ok(ExitCode::SUCCESS),
-            Ok(CaptureOutcome::CapturedAfterHang(path)) => {
+            Ok(CaptureOutcome::CapturedAfterHang(paths)) => {
                 crate::eprintln!(
                     "{}",
                     format!(
                         "{WARN} vpinball hung and was killed; salvaged frame for {}",
-                        path.display()
+                        display_paths(&paths)
                     )
                     .yellow()
                 )?;
                 Ok(ExitCode::SUCCESS)
             }
-            Ok(CaptureOutcome::Skipped(path)) => {
+            Ok(CaptureOutcome::Skipped(paths)) => {
                 crate::println!(
                     "Skipping, {} already exists (use --force to regenerate)",
-                    path.display()
+                    display_paths(&paths)
                 )?;
                 Ok(ExitCode::SUCCESS)
             }
@@ -966,30 +1402,105 @@ fn handle_capture(sub_matches: &ArgMatches) -> io::Result<ExitCode> {
         }
     };
 
+    let report_path = sub_matches
+        .get_one::<String>(ARG_REPORT)
+        .map(PathBuf::from)
+        .unwrap_or_else(capturereport::default_report_path);
+    let resume = sub_matches.get_flag(ARG_RESUME);
+    let retry_failed = sub_matches.get_flag(ARG_RETRY_FAILED);
+    let mut report = if resume || retry_failed {
+        match CaptureReport::read(&report_path) {
+            Ok(report) => report,
+            Err(e) => {
+                crate::eprintln!(
+                    "{}",
+                    format!(
+                        "{NOK} Unable to read the previous capture report {}: {e}",
+                        report_path.display()
+                    )
+                    .red()
+                )?;
+                return Ok(ExitCode::FAILURE);
+            }
+        }
+    } else {
+        CaptureReport::new()
+    };
+    let tables: Vec<&IndexedTable> = if retry_failed {
+        let retry = report.needing_retry();
+        tables
+            .iter()
+            .filter(|t| retry.contains(t.path.as_path()))
+            .collect()
+    } else if resume {
+        let handled = report.handled();
+        tables
+            .iter()
+            .filter(|t| !handled.contains(t.path.as_path()))
+            .collect()
+    } else {
+        tables.iter().collect()
+    };
+    // the images of a hung table were salvaged, they have to be overwritten
+    let options = CaptureOptions {
+        force: options.force || retry_failed,
+        ..options
+    };
+
     crate::println!(
         "Capturing {} screenshots for {} tables in {}",
         format,
         tables.len(),
         config.tables_folder.display()
     )?;
+    crate::println!("Writing the capture report to {}", report_path.display())?;
 
     let mut captured = 0;
     let mut hung = 0;
     let mut skipped = 0;
     let mut failed = 0;
-    for table in &tables {
-        match capture_table(&config, &table.path, &options) {
+    let mut compared = 0;
+    let mut regressions: Vec<(&Path, f64)> = Vec::new();
+    for table in tables {
+        let started = Instant::now();
+        let result = capture_one(&config, &table.path, &options, compare);
+        let mut entry = CaptureReportEntry::new(
+            &table.path,
+            result.as_ref().map(|(outcome, _)| outcome),
+            started.elapsed(),
+        );
+        if let Ok((_, Some(comparison))) = &result {
+            print_comparison(comparison, threshold)?;
+            compared += 1;
+            if comparison.difference > threshold {
+                regressions.push((&table.path, comparison.difference));
+            }
+            entry.difference = Some(comparison.difference);
+        }
+        report.record(entry);
+        // written after every table so an interrupted run can be resumed
+        if let Err(e) = report.write(&report_path) {
+            crate::eprintln!(
+                "{}",
+                format!(
+                    "{WARN} Unable to write the capture report {}: {e}",
+                    report_path.display()
+                )
+                .yellow()
+            )?;
+        }
+        match result.map(|(outcome, _)| outcome) {
             Ok(CaptureOutcome::Captured(_)) => {
                 captured += 1;
             }
-            Ok(CaptureOutcome::CapturedAfterHang(path)) => {
+            Ok(CaptureOutcome::CapturedAfterHang(paths)) => {
                 hung += 1;
                 captured += 1;
                 crate::eprintln!(
                     "{}",
                     format!(
                         "{WARN} vpinball hung and was killed; salvaged frame for {}",
-                        path.display()
+                        display_paths(&paths)
                     )
                     .yellow()
                 )?;
@@ -1007,82 +1518,263 @@ fn handle_capture(sub_matches: &ArgMatches) -> io::Result<ExitCode> {
     crate::println!(
         "Done. {captured} captured ({hung} after a hang), {skipped} skipped, {failed} failed."
     )?;
-    if failed > 0 {
+    if resume || retry_failed {
+        crate::println!(
+            "Overall {} captured ({} after a hang), {} skipped, {} failed, see {}",
+            report.count(CaptureStatus::Captured) + report.count(CaptureStatus::CapturedAfterHang),
+            report.count(CaptureStatus::CapturedAfterHang),
+            report.count(CaptureStatus::Skipped),
+            report.count(CaptureStatus::Failed),
+            report_path.display()
+        )?;
+    }
+    if compare {
+        crate::println!(
+            "Compared {compared} tables with their previous capture, {} differ by more than {threshold}%{}",
+            regressions.len(),
+            if regressions.is_empty() { "." } else { ":" }
+        )?;
+        regressions.sort_by(|a, b| b.1.total_cmp(&a.1));
+        for (path, difference) in &regressions {
+            crate::println!("  {difference:>6.2}%  {}", path.display())?;
+        }
+    }
+    if failed > 0 || !regressions.is_empty() {
         Ok(ExitCode::FAILURE)
     } else {
         Ok(ExitCode::SUCCESS)
     }
 }
 
-fn build_command() -> Command {
-    // to allow for non-static strings in clap
-    // I had to enable the "string" module
-    // is this considered a bad idea?
-    Command::new("vpxtool")
-        .version(GIT_VERSION)
-        .author("Francis DB")
-        .about("Terminal based frontend and utilities for Visual Pinball")
-        .arg_required_else_help(true)
-        .before_help(format!("Vpxtool {GIT_VERSION}"))
-        .arg(
-            Arg::new(ARG_VERBOSE)
-                .short('v')
-                .long("verbose")
-                .action(ArgAction::SetTrue)
-                .help("Enable verbose logging")
-                .global(true),
-        )
-        .subcommand(
-            Command::new(CMD_INFO)
-                .subcommand_required(true)
-                .about("Vpx table info related commands")
-                .subcommand(
-                    Command::new(CMD_INFO_SHOW)
-                        .about("Show information for a vpx file")
-                        .arg(
-                            arg!(<VPXPATH> "The path to the vpx file")
-                                .required(true),
-                        ),
-                )
-                .subcommand(
-                    Command::new(CMD_INFO_EXTRACT)
-                        .about("Extract information from a vpx file")
-                        .arg(
-                            arg!(<VPXPATH> "The path to the vpx file")
-                                .required(true),
-                        ),
-                )
-                .subcommand(
-                    Command::new(CMD_INFO_IMPORT)
-                        .about("Import information into a vpx file")
-                        .arg(
-                            arg!(<VPXPATH> "The path to the vpx file")
-                                .required(true),
-                        ),
-                )
-                .subcommand(
-                    Command::new(CMD_INFO_EDIT)
-                        .about("Edit information for a vpx file")
-                        .long_about("Extracts the information from the vpx file into a json file, and opens it in the default editor.")
-                        .arg(
-                            arg!(<VPXPATH> "The path to the vpx file")
-                                .required(true),
-                        ),
-                )
-                .subcommand(
-                    Command::new(CMD_INFO_DIFF)
-                        .about("Prints out a diff between the info in the vpx and the sidecar json")
-                        .arg(
-                            arg!(<VPXPATH> "The path to the vpx file")
-                                .required(true),
-                        ),
-                ),
-        )
-        .subcommand(
-            Command::new(CMD_DIFF)
-                .about("Prints out a diff between the vbs in the vpx and the sidecar vbs")
-                .arg(arg!(<VPXPATH> "The path to the vpx file").required(true))
-        )
+/// Capture a table, with `compare` also comparing the playfield image with
+/// the previous one.
+fn capture_one(
+    config: &ResolvedConfig,
+    vpx_path: &Path,
+    options: &CaptureOptions,
+    compare: bool,
+) -> io::Result<(CaptureOutcome, Option<Comparison>)> {
+    if compare {
+        capture_and_compare(config, vpx_path, options)
+    } else {
+        capture_table(config, vpx_path, options).map(|outcome| (outcome, None))
+    }
+}
+
+fn print_comparison(comparison: &Comparison, threshold: f64) -> io::Result<()> {
+    let message = format!(
+        "{:.2}% different from {}, heatmap at {}",
+        comparison.difference,
+        comparison.baseline.display(),
+        comparison.heatmap.display()
+    );
+    if comparison.difference > threshold {
+        crate::println!("{}", format!("{WARN} {message}").yellow())
+    } else {
+        crate::println!("{OK} {message}")
+    }
+}
+
+fn handle_smoketest(sub_matches: &ArgMatches) -> io::Result<ExitCode> {
+    let timeout = sub_matches
+        .get_one::<u64>(ARG_TIMEOUT)
+        .copied()
+        .filter(|&s| s > 0)
+        .map(Duration::from_secs);
+    let format = sub_matches
+        .get_one::<String>(ARG_FORMAT)
+        .map(|s| s.parse::<ReportFormat>())
+        .transpose()
+        .map_err(io::Error::other)?
+        .unwrap_or_default();
+    let output = sub_matches
+        .get_one::<String>(ARG_OUTPUT)
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from(format!("smoketest.{}", format.extension())));
+
+    let (config_path, mut config) = config::load_or_setup_config()?;
+    crate::println!("Using vpxtool config file {}", config_path.display())?;
+    if let Some(executable) = sub_matches.get_one::<String>(ARG_EXECUTABLE) {
+        config.vpx_executable = PathBuf::from(executable);
+    }
+    if !config.vpx_executable.is_file() {
+        crate::eprintln!(
+            "{}",
+            format!(
+                "vpinball executable not found at {}",
+                config.vpx_executable.display()
+            )
+            .red()
+        )?;
+        return Ok(ExitCode::FAILURE);
+    }
+
+    let tables: Vec<PathBuf> = match sub_matches.get_one::<String>("VPXPATH") {
+        Some(vpx_path) => vec![path_exists(vpx_path)?],
+        None => {
+            let configured_pinmame_folder = config.configured_pinmame_folder();
+            match frontend::frontend_index(
+                &config,
+                true,
+                config.tables_scan_max_depth,
+                configured_pinmame_folder.as_deref(),
+                vec![],
+            ) {
+                Ok(tables) => tables.into_iter().map(|t| t.path).collect(),
+                Err(e) => {
+                    crate::eprintln!("{}", format!("Unable to index tables: {e:?}").red())?;
+                    return Ok(ExitCode::FAILURE);
+                }
+            }
+        }
+    };
+
+    crate::println!(
+        "Smoke testing {} tables with {}",
+        tables.len(),
+        config.vpx_executable.display()
+    )?;
+    let mut results = Vec::new();
+    let mut aborted = false;
+    for table in &tables {
+        match smoketest::smoketest_table(&config, table, timeout) {
+            Ok(result) => {
+                if result.outcome.is_ok() {
+                    crate::println!("{OK} {}", table.display())?;
+                } else {
+                    crate::eprintln!(
+                        "{}",
+                        format!("{NOK} {}: {}", table.display(), result.outcome).red()
+                    )?;
+                }
+                results.push(result);
+            }
+            // not a table problem, e.g. the executable can't be started
+            Err(e) => {
+                crate::eprintln!(
+                    "{}",
+                    format!(
+                        "{NOK} Unable to run {}: {e}",
+                        config.vpx_executable.display()
+                    )
+                    .red()
+                )?;
+                aborted = true;
+                break;
+            }
+        }
+    }
+
+    let report = match format {
+        ReportFormat::Junit => smoketest::junit_report(&results),
+        ReportFormat::Json => smoketest::json_report(&results)?,
+    };
+    crate::atomicwrite::atomic_write(&output, |file| file.write_all(report.as_bytes()))?;
+    // keep what was tested so far, a long run should not be lost
+    if aborted {
+        crate::eprintln!(
+            "Aborted after {} of {} tables. Partial report written to {}",
+            results.len(),
+            tables.len(),
+            output.display()
+        )?;
+        return Ok(ExitCode::FAILURE);
+    }
+
+    let failed = results.iter().filter(|r| !r.outcome.is_ok()).count();
+    let count = |f: fn(&SmokeOutcome) -> bool| results.iter().filter(|r| f(&r.outcome)).count();
+    crate::println!(
+        "Done. {} ok, {} crashed, {} hung, {} script errors. Report written to {}",
+        results.len() - failed,
+        count(|o| matches!(o, SmokeOutcome::Crashed { .. })),
+        count(|o| matches!(o, SmokeOutcome::Hung)),
+        count(|o| matches!(o, SmokeOutcome::ScriptError { .. })),
+        output.display()
+    )?;
+    if failed > 0 {
+        Ok(ExitCode::FAILURE)
+    } else {
+        Ok(ExitCode::SUCCESS)
+    }
+}
+
+fn build_command() -> Command {
+    // to allow for non-static strings in clap
+    // I had to enable the "string" module
+    // is this considered a bad idea?
+    Command::new("vpxtool")
+        .version(GIT_VERSION)
+        .author("Francis DB")
+        .about("Terminal based frontend and utilities for Visual Pinball")
+        .arg_required_else_help(true)
+        .before_help(format!("Vpxtool {GIT_VERSION}"))
+        .arg(
+            Arg::new(ARG_VERBOSE)
+                .short('v')
+                .long("verbose")
+                .action(ArgAction::SetTrue)
+                .help("Enable verbose logging")
+                .global(true),
+        )
+        .subcommand(
+            Command::new(CMD_INFO)
+                .subcommand_required(true)
+                .about("Vpx table info related commands")
+                .subcommand(
+                    Command::new(CMD_INFO_SHOW)
+                        .about("Show information for a vpx file")
+                        .arg(
+                            arg!(<VPXPATH> "The path to the vpx file")
+                                .required(true),
+                        ),
+                )
+                .subcommand(
+                    Command::new(CMD_INFO_EXTRACT)
+                        .about("Extract information from a vpx file")
+                        .arg(
+                            arg!(<VPXPATH> "The path to the vpx file")
+                                .required(true),
+                        ),
+                )
+                .subcommand(
+                    Command::new(CMD_INFO_IMPORT)
+                        .about("Import information into a vpx file")
+                        .long_about("Reads the <stem>.info.json sidecar (as written by `info extract` or `info edit`), validates it, shows the fields that will change and writes the table info and custom info tags back into the vpx file.")
+                        .arg(
+                            Arg::new("FORCE")
+                                .short('f')
+                                .long("force")
+                                .num_args(0)
+                                .help("Do not ask for confirmation before writing the changes"),
+                        )
+                        .arg(
+                            arg!(<VPXPATH> "The path to the vpx file")
+                                .required(true),
+                        ),
+                )
+                .subcommand(
+                    Command::new(CMD_INFO_EDIT)
+                        .about("Edit information for a vpx file")
+                        .long_about("Extracts the information from the vpx file into a json file, and opens it in the default editor.")
+                        .arg(
+                            arg!(<VPXPATH> "The path to the vpx file")
+                                .required(true),
+                        ),
+                )
+                .subcommand(
+                    Command::new(CMD_INFO_DIFF)
+                        .about("Prints out a diff between the info in the vpx and the sidecar json")
+                        .arg(
+                            arg!(<VPXPATH> "The path to the vpx file")
+                                .required(true),
+                        ),
+                ),
+        )
+        .subcommand(
+            Command::new(CMD_DIFF)
+                .about("Prints out a diff between the vbs in the vpx and the sidecar vbs")
+                .arg(arg!(<VPXPATH> "The path to the vpx file").required(true))
+        )
         .subcommand(
             Command::new(CMD_FRONTEND)
                 .about("Text based frontend for launching vpx files")
@@ -1132,16 +1824,115 @@ fn build_command() -> Command {
                 .arg(
                     arg!(<INDEX_FILE> "Where the index will be written. Defaults to VPXROOTPATH/vpxtool_index.json.")
                         .required(false)
+                )
+                .args_conflicts_with_subcommands(true)
+                .subcommand(
+                    Command::new(CMD_INDEX_QUERY)
+                        .about("Query the table index")
+                        .long_about(
+                            "List the tables in the index that match all given filters, sorted \
+                             by path. The index is not updated, run `index` first.\n\
+                             \n\
+                             The table view shows NAME, ROM (game name), PINMAME (Y/N), the \
+                             detected assets as Y/N flags, MODIFIED and PATH. The tsv, csv and \
+                             json formats emit the keys path, table_name, game_name, \
+                             requires_pinmame, rom_path, b2s_path, wheel_path, altsound_path, \
+                             altcolor_path, pup_pack_path and last_modified (RFC 3339), with \
+                             empty/null for assets that were not found.",
+                        )
+                        .args(index_query_args())
+                        .arg(list_format_arg())
+                        .arg(
+                            arg!(<VPXROOTPATH> "The path to the root directory of vpx files. Defaults to what is set up in the vpxtool config file.")
+                                .required(false)
+                        )
+                        .arg(
+                            arg!(<INDEX_FILE> "The index to read. Defaults to VPXROOTPATH/vpxtool_index.json.")
+                                .required(false)
+                        ),
+                )
+                .subcommand(
+                    Command::new(CMD_INDEX_EXPORT)
+                        .about("Export the table index as another frontend's game list")
+                        .long_about(
+                            "Write the tables in the index as an EmulationStation / ES-DE \
+                             gamelist.xml, a PinballY (PinballX style) game database or a csv \
+                             file. Table, wheel and playfield image paths are written relative \
+                             to the folder of the output file, or to --relative-to, and stay \
+                             absolute when outside of it. The index is not updated, run `index` \
+                             first.",
+                        )
+                        .arg(
+                            Arg::new(ARG_FORMAT)
+                                .long("format")
+                                .required(true)
+                                .value_parser(ExportFormat::VALUES)
+                                .help("gamelist (ES-DE), pinbally or csv"),
+                        )
+                        .arg(
+                            Arg::new(ARG_OUTPUT)
+                                .short('o')
+                                .long("output")
+                                .help("The file to write. Defaults to gamelist.xml, Visual Pinball X.xml or tables.csv in the current directory."),
+                        )
+                        .arg(
+                            Arg::new("RELATIVE_TO")
+                                .long("relative-to")
+                                .help("Folder the paths are made relative to, e.g. the ES-DE rom folder. Defaults to the folder of the output file."),
+                        )
+                        .arg(
+                            arg!(<VPXROOTPATH> "The path to the root directory of vpx files. Defaults to what is set up in the vpxtool config file.")
+                                .required(false)
+                        )
+                        .arg(
+                            arg!(<INDEX_FILE> "The index to read. Defaults to VPXROOTPATH/vpxtool_index.json.")
+                                .required(false)
+                        ),
+                ),
+        )
+        .subcommand(
+            Command::new(CMD_DOCTOR)
+                .about("Check all indexed tables for problems")
+                .long_about(
+                    "Check every table in the index and report all problems found. Exits \
+                     with a non-zero code if there are any. The index is not updated, run \
+                     `index` first.\n\
+                     \n\
+                     Checks: vpx-missing (stale index), verify-failed (see `verify`), \
+                     vbs-differs (sidecar script differs from the embedded one), missing-rom \
+                     (PinMAME table without rom), b2s-unreadable, ini-unreadable (table \
+                     .ini), missing-wheel and missing-capture (no media/table.* playfield \
+                     image, see `capture`).\n\
+                     \n\
+                     For scripting use `--format tsv|csv|json`, which emit one row per \
+                     problem with the keys path, check and message.",
+                )
+                .arg(
+                    Arg::new("SKIP")
+                        .long("skip")
+                        .num_args(1)
+                        .action(ArgAction::Append)
+                        .value_parser(Check::VALUES)
+                        .help("Do not run this check, can be repeated"),
+                )
+                .arg(list_format_arg())
+                .arg(
+                    arg!(<VPXROOTPATH> "The path to the root directory of vpx files. Defaults to what is set up in the vpxtool config file.")
+                        .required(false)
+                )
+                .arg(
+                    arg!(<INDEX_FILE> "The index to read. Defaults to VPXROOTPATH/vpxtool_index.json.")
+                        .required(false)
                 ),
         )
         .subcommand(
             Command::new(CMD_CAPTURE)
-                .about("Capture a playfield screenshot using vpinball")
+                .about("Capture playfield, backglass and DMD screenshots using vpinball")
                 .long_about(
-                    "Capture a playfield screenshot next to the table using vpinball's \
-                     attract capture mode. With a VPXPATH a single table is captured, \
+                    "Capture screenshots of the vpinball windows next to the table using \
+                     vpinball's attract capture mode. With a VPXPATH a single table is captured, \
                      otherwise every table in the configured tables folder is captured, \
-                     skipping tables that already have an image unless --force is given.",
+                     skipping tables that already have all images unless --force is given.",
                 )
                 .arg(
                     arg!([VPXPATH] "The path to a single vpx file. Defaults to capturing all tables in the configured tables folder.")
@@ -1173,6 +1964,116 @@ fn build_command() -> Command {
                         .value_parser(clap::value_parser!(u64))
                         .default_value("60")
                         .help("Kill vpinball and skip the table if a capture takes longer than this many seconds (0 disables the timeout). Prevents a hanging table from stalling a batch."),
+                )
+                .arg(
+                    Arg::new(ARG_WINDOW)
+                        .long("window")
+                        .value_parser(CaptureWindow::VALUES)
+                        .value_delimiter(',')
+                        .action(ArgAction::Append)
+                        .default_value("playfield")
+                        .help("Windows to capture, comma separated or repeated. Written as media/table.<ext> for the playfield and media/<window>.<ext> for the others. A backglass or DMD that vpinball did not render falls back to the image in the .directb2s."),
+                )
+                .arg(
+                    Arg::new(ARG_ANIMATED)
+                        .long("animated")
+                        .value_parser(["webp", "gif"])
+                        .num_args(0..=1)
+                        .default_missing_value("webp")
+                        .help("Encode all captured frames as an animated attract loop, written as media/<window>_animated.<ext>. webp (lossless) is the default, gif is limited to 256 colors but plays everywhere. Use --max-width to keep the files small."),
+                )
+                .arg(
+                    Arg::new(ARG_FRAMES)
+                        .long("frames")
+                        .value_parser(clap::value_parser!(u32).range(1..))
+                        .help(format!("Number of frames to capture. Defaults to 1, or {ANIMATED_DEFAULT_FRAMES} with --animated.")),
+                )
+                .arg(
+                    Arg::new(ARG_FPS)
+                        .long("fps")
+                        .value_parser(clap::value_parser!(u32).range(1..=100))
+                        .help(format!("Capture frame rate, also the frame rate of the animation. Defaults to {ANIMATED_DEFAULT_FPS} with --animated.")),
+                )
+                .arg(
+                    Arg::new(ARG_LOOP_COUNT)
+                        .long("loop-count")
+                        .value_parser(clap::value_parser!(u16))
+                        .requires(ARG_ANIMATED)
+                        .help("Number of times the animation plays, 0 (the default) loops forever."),
+                )
+                .arg(
+                    Arg::new(ARG_REPORT)
+                        .long("report")
+                        .conflicts_with("VPXPATH")
+                        .help("Where a batch capture writes its json report, listing each table's outcome, duration and the end of the vpinball output for failures. Defaults to capture_report.json in the vpxtool config folder."),
+                )
+                .arg(
+                    Arg::new(ARG_RESUME)
+                        .long("resume")
+                        .num_args(0)
+                        .conflicts_with_all(["VPXPATH", ARG_RETRY_FAILED])
+                        .help("Continue an interrupted batch capture, skipping the tables already in the report."),
+                )
+                .arg(
+                    Arg::new(ARG_RETRY_FAILED)
+                        .long("retry-failed")
+                        .num_args(0)
+                        .conflicts_with("VPXPATH")
+                        .help("Only capture the tables that failed or hung in the report, overwriting salvaged images."),
+                )
+                .arg(
+                    Arg::new(ARG_COMPARE)
+                        .long("compare")
+                        .num_args(0)
+                        .conflicts_with(ARG_ANIMATED)
+                        .help("Recapture and compare the playfield with the previous capture, to find rendering regressions after a vpinball upgrade. The previous image is kept as media/table.baseline.<ext> and a heatmap of the changed pixels is written to media/table.diff.png. Tables that differ more than --threshold are listed and fail the command."),
+                )
+                .arg(
+                    Arg::new(ARG_THRESHOLD)
+                        .long("threshold")
+                        .value_parser(clap::value_parser!(f64))
+                        .requires(ARG_COMPARE)
+                        .help(format!("Mean pixel difference in percent above which a table counts as changed, attract mode animation alone is usually a few percent. Defaults to {DEFAULT_COMPARE_THRESHOLD}.")),
+                ),
+        )
+        .subcommand(
+            Command::new(CMD_SMOKETEST)
+                .about("Run every table headless and report crashes, hangs and script errors")
+                .long_about(
+                    "Run each table for a moment in vpinball's non-interactive attract capture mode \
+                     and classify it as ok, crashed, hung or script error (from the vpinball log). \
+                     With a VPXPATH a single table is tested, otherwise every table in the \
+                     configured tables folder. Writes a JUnit XML or json report and fails when \
+                     any table did not pass, to check a vpinball upgrade against a table library.",
+                )
+                .arg(
+                    arg!([VPXPATH] "The path to a single vpx file. Defaults to all tables in the configured tables folder.")
+                        .required(false),
+                )
+                .arg(
+                    Arg::new(ARG_EXECUTABLE)
+                        .long("executable")
+                        .help("The vpinball executable to test, defaults to the configured one."),
+                )
+                .arg(
+                    Arg::new(ARG_TIMEOUT)
+                        .long("timeout")
+                        .value_parser(clap::value_parser!(u64))
+                        .default_value("60")
+                        .help("Seconds after which a table that is still running counts as hung and is killed (0 disables the timeout)."),
+                )
+                .arg(
+                    Arg::new(ARG_FORMAT)
+                        .long("format")
+                        .value_parser(ReportFormat::VALUES)
+                        .default_value("junit")
+                        .help("Report format"),
+                )
+                .arg(
+                    Arg::new(ARG_OUTPUT)
+                        .short('o')
+                        .long("output")
+                        .help("Where to write the report. Defaults to smoketest.xml or smoketest.json in the current directory."),
                 ),
         )
         .subcommand(
@@ -1218,15 +2119,59 @@ fn build_command() -> Command {
                                 .required(true),
                         ),
                 )
+                .subcommand(
+                    Command::new(CMD_SCRIPT_LINT)
+                        .about("Report script constructs that are known to break on standalone")
+                        .long_about("Scans the table script, or the sidecar vbs if present, for constructs that break on vpinball standalone: DTArray/STArray patterns fixed by `script patch`, Windows-only COM objects, FileSystemObject/WScript.Shell use, mixed line endings and ExecuteGlobal of missing files. When given a directory all vpx files below it are scanned. Exits with a failure if any error was found.")
+                        .arg(
+                            arg!(<PATH> "The path to a vpx file or a directory with vpx files")
+                                .required(true),
+                        )
+                        .arg(list_format_arg()),
+                )
+                .subcommand(
+                    Command::new(CMD_SCRIPT_XREF)
+                        .about("Cross-reference the table script with the table's gameitems and collections")
+                        .long_about("Compares the names used in the table script, or the sidecar vbs if present, with the gameitems and collections in the vpx. Reports references to items that do not exist (error), event subs like <name>_Hit for items that do not exist (warning), items with an enabled timer but no <name>_Timer sub (warning) and items the script never mentions (info). Names built at runtime, like Eval(\"Light\" & i), can not be followed so unreferenced items may still be used. Exits with a failure if any error was found.")
+                        .arg(
+                            arg!(<VPXPATH> "The path to the vpx file")
+                                .required(true),
+                        )
+                        .arg(list_format_arg()),
+                )
                 .subcommand(
                     Command::new(CMD_SCRIPT_PATCH)
                         .about("Patch the table vpx script for typical standalone issues")
+                        .long_about("Extracts the script next to the vpx and applies the built-in fixes (DTArray/STArray) followed by the user-defined rules in the patches folder next to the vpxtool config file (<config dir>/vpxtool/patches/*.toml). Applied rules are reported by id. The script's encoding is kept and the unpatched script is saved next to it as <table>.vbs.orig, see `script unpatch`.")
+                        .arg(
+                            arg!(<VPXPATH> "The path to the vpx file")
+                                .required(true),
+                        ),
+                )
+                .subcommand(
+                    Command::new(CMD_SCRIPT_UNPATCH)
+                        .about("Restore the table vbs script from the backup made by `script patch`")
                         .arg(
                             arg!(<VPXPATH> "The path to the vpx file")
                                 .required(true),
                         ),
                 ),
         )
+        .subcommand(
+            Command::new(CMD_PRUNE)
+                .about("Remove images, materials and sounds that are not used by the table")
+                .long_about("Finds the images, materials and sounds that are not referenced by any gameitem, the table settings or a string in the script (the embedded one and the sidecar vbs), removes them and compacts the vpx. Scripts that build names at runtime like PlaySound \"fx_rubber\" & n keep every asset starting with that string, other runtime-built names can not be seen so check the list with --dry-run first.")
+                .arg(
+                    Arg::new("DRY_RUN")
+                        .long("dry-run")
+                        .num_args(0)
+                        .help("Only list the unused assets, do not change the vpx"),
+                )
+                .arg(
+                    arg!(<VPXPATH> "The path to the vpx file")
+                        .required(true),
+                ),
+        )
         .subcommand(
             Command::new(CMD_LS)
                 .about("Show the vpx file contents")
@@ -1358,11 +2303,55 @@ fn build_command() -> Command {
                         .long_about(
                             "List the images stored in a vpx file as aligned columns: \
                              NAME, FORMAT, WIDTH, HEIGHT, SIZE (bytes), LINKED (Y/N for \
-                             screenshot-style image links), PATH (original import path). \
-                             PATH is last so awk-style column extraction works on the \
-                             other fields even when paths contain spaces.",
+                             screenshot-style image links), PATH (original import path).\n\
+                             \n\
+                             For scripting use `--format tsv|csv|json`, which emit raw \
+                             values under the keys name, format, width, height, size, \
+                             linked (true/false) and path.",
+                        )
+                        .arg(arg!(<VPXPATH> "The path to the vpx file").required(true))
+                        .arg(list_format_arg()),
+                )
+                .subcommand(
+                    Command::new(CMD_IMAGES_EXTRACT)
+                        .about("Extract a single image from a vpx file")
+                        .long_about(
+                            "Extract a single image from a vpx file. Image names are matched \
+                             case-insensitively, see `images list`. Images are written as \
+                             stored, except for bitmaps which vpinball keeps as raw pixels \
+                             and are written as .bmp.",
+                        )
+                        .arg(arg!(<VPXPATH> "The path to the vpx file").required(true))
+                        .arg(arg!(<NAME> "The name of the image").required(true))
+                        .arg(
+                            Arg::new("OUTPUT")
+                                .short('o')
+                                .long("output")
+                                .num_args(1)
+                                .help("File to write to. Defaults to <NAME>.<ext> in the current directory."),
+                        )
+                        .arg(
+                            Arg::new("FORCE")
+                                .short('f')
+                                .long("force")
+                                .num_args(0)
+                                .help("Do not ask for confirmation before overwriting an existing file"),
+                        ),
+                )
+                .subcommand(
+                    Command::new(CMD_IMAGES_REPLACE)
+                        .about("Replace the data of a single image in a vpx file")
+                        .long_about(
+                            "Replace the data of a single image in a vpx file with the given \
+                             file. The file must be an image format vpinball supports (png, \
+                             jpg, webp, bmp, gif, tga, hdr or exr) and its extension must match \
+                             its contents. Width and height are taken from the new file, the \
+                             image name and original import path are kept (only the extension \
+                             follows the new format). The vpx file is compacted afterwards.",
                         )
-                        .arg(arg!(<VPXPATH> "The path to the vpx file").required(true)),
+                        .arg(arg!(<VPXPATH> "The path to the vpx file").required(true))
+                        .arg(arg!(<NAME> "The name of the image").required(true))
+                        .arg(arg!(<FILE> "The new image file").required(true)),
                 ),
         )
         .subcommand(
@@ -1378,11 +2367,85 @@ fn build_command() -> Command {
                              (raw integers from the vpx file, not the signed-percent values \
                              vpinball shows in its GUI), FREQ (sample rate, Hz), CHAN \
                              (channel count), LENGTH (seconds, WAV only, blank otherwise), \
-                             SIZE (bytes), PATH (original import path). PATH is last so \
-                             awk-style column extraction works on the other fields even \
-                             when paths contain spaces.",
+                             SIZE (bytes), PATH (original import path).\n\
+                             \n\
+                             For scripting use `--format tsv|csv|json`, which emit raw \
+                             values under the keys name, format, output, pan, fade, volume, \
+                             sample_rate, channels, length_secs (unrounded, empty/null for \
+                             non-WAV), size and path.",
+                        )
+                        .arg(arg!(<VPXPATH> "The path to the vpx file").required(true))
+                        .arg(list_format_arg()),
+                )
+                .subcommand(
+                    Command::new(CMD_SOUNDS_EXTRACT)
+                        .about("Extract one or all sounds from a vpx file")
+                        .long_about(
+                            "Extract one or all sounds from a vpx file as playable files. \
+                             Sound names are matched case-insensitively, see `sounds list`. \
+                             Ogg and mp3 sounds are written as stored, wav sounds get a \
+                             freshly built RIFF header as vpinball only keeps the samples.\n\
+                             \n\
+                             With a NAME, OUTPUT is the file to write (default <NAME>.<ext> \
+                             in the current directory). Without, every sound is written to \
+                             the OUTPUT directory (default <table>_sounds in the current \
+                             directory).",
+                        )
+                        .arg(arg!(<VPXPATH> "The path to the vpx file").required(true))
+                        .arg(arg!([NAME] "The name of the sound, all sounds if omitted"))
+                        .arg(
+                            Arg::new("OUTPUT")
+                                .short('o')
+                                .long("output")
+                                .num_args(1)
+                                .help("File (single sound) or directory (all sounds) to write to"),
+                        )
+                        .arg(
+                            Arg::new("FORCE")
+                                .short('f')
+                                .long("force")
+                                .num_args(0)
+                                .help("Do not ask for confirmation before overwriting existing files"),
+                        ),
+                )
+                .subcommand(
+                    Command::new(CMD_SOUNDS_REPLACE)
+                        .about("Replace the audio of a single sound in a vpx file")
+                        .long_about(
+                            "Replace the audio of a single sound in a vpx file with the given \
+                             wav (PCM or float), ogg or mp3 file. The extension must match the \
+                             contents. Name, output target, pan, fade and volume are kept, as \
+                             is the original import path (only the extension follows the new \
+                             format). The vpx file is compacted afterwards.",
                         )
-                        .arg(arg!(<VPXPATH> "The path to the vpx file").required(true)),
+                        .arg(arg!(<VPXPATH> "The path to the vpx file").required(true))
+                        .arg(arg!(<NAME> "The name of the sound").required(true))
+                        .arg(arg!(<FILE> "The new sound file").required(true)),
+                )
+                .subcommand(
+                    Command::new(CMD_SOUNDS_IMPORT)
+                        .about("Add a new sound to a vpx file")
+                        .long_about(
+                            "Add a wav (PCM or float), ogg or mp3 file as a new sound, with \
+                             the same defaults the vpinball editor uses: centered, no fade, \
+                             full volume. Fails if a sound with that name already exists, use \
+                             `sounds replace` for that. The vpx file is compacted afterwards.",
+                        )
+                        .arg(arg!(<VPXPATH> "The path to the vpx file").required(true))
+                        .arg(arg!(<FILE> "The sound file to import").required(true))
+                        .arg(
+                            Arg::new("NAME")
+                                .short('n')
+                                .long("name")
+                                .num_args(1)
+                                .help("Name of the new sound. Defaults to the file name without extension."),
+                        )
+                        .arg(
+                            Arg::new("BACKGLASS")
+                                .long("backglass")
+                                .num_args(0)
+                                .help("Play the sound on the backglass output instead of the table"),
+                        ),
                 ),
         )
         .subcommand(
@@ -1395,9 +2458,14 @@ fn build_command() -> Command {
                         .long_about(
                             "List the collections stored in a vpx file as aligned columns: \
                              NAME, ITEMS (number of element names in the collection), \
-                             FIRE_EVENTS, STOP_SINGLES, GROUP_ELEMENTS (all Y/N flags).",
+                             FIRE_EVENTS, STOP_SINGLES, GROUP_ELEMENTS (all Y/N flags).\n\
+                             \n\
+                             For scripting use `--format tsv|csv|json`, which emit raw \
+                             values under the keys name, items, fire_events, \
+                             stop_single_events and group_elements (true/false).",
                         )
-                        .arg(arg!(<VPXPATH> "The path to the vpx file").required(true)),
+                        .arg(arg!(<VPXPATH> "The path to the vpx file").required(true))
+                        .arg(list_format_arg()),
                 ),
         )
         .subcommand(
@@ -1412,9 +2480,14 @@ fn build_command() -> Command {
                              NAME, BASE_COLOR (RGB hex), METAL (Y/N), ROUGHNESS (0..1), \
                              OPACITY (0..1), EDGE (0..1). Supports both the 10.8+ MATR \
                              format and the pre-10.8 MATE format; columns are the fields \
-                             that exist in both.",
+                             that exist in both.\n\
+                             \n\
+                             For scripting use `--format tsv|csv|json`, which emit raw \
+                             (unrounded) values under the keys name, base_color, metal \
+                             (true/false), roughness, opacity and edge.",
                         )
-                        .arg(arg!(<VPXPATH> "The path to the vpx file").required(true)),
+                        .arg(arg!(<VPXPATH> "The path to the vpx file").required(true))
+                        .arg(list_format_arg()),
                 ),
         )
         .subcommand(
@@ -1434,11 +2507,17 @@ fn build_command() -> Command {
                              '--'-joined to match the format vpinball's editor uses; this \
                              makes `grep -F -- '--MyTexture'` a reliable way to find every \
                              item that references a given texture. Empty cells mean either \
-                             the field is not set or the variant has no such field. For \
-                             type counts: `vpxtool gameitems list table.vpx | awk 'NR>1 \
-                             {print $2}' | sort | uniq -c`.",
+                             the field is not set or the variant has no such field.\n\
+                             \n\
+                             For scripting use `--format tsv|csv|json`, which emit raw \
+                             values under the keys name, type, visible, locked, layer, \
+                             part_group, physics_material, images and materials. visible \
+                             and locked are true/false (empty/null when the variant has no \
+                             such concept); images and materials are '--'-joined in \
+                             tsv/csv and arrays in json.",
                         )
-                        .arg(arg!(<VPXPATH> "The path to the vpx file").required(true)),
+                        .arg(arg!(<VPXPATH> "The path to the vpx file").required(true))
+                        .arg(list_format_arg()),
                 ),
         )
         .subcommand(
@@ -1526,6 +2605,46 @@ fn build_command() -> Command {
                 .long_about("Extracts the PinMAME ROM name from a vpx file by searching for specific patterns in the table script. If the table is not PinMAME based, no output is produced.")
                 .arg(arg!(<VPXPATH> "The path to the vpx file").required(true)),
         )
+        .subcommand(
+            Command::new(CMD_IMPORT_VPXZ)
+                .about("Import a .vpxz archive into the tables folder")
+                .long_about("Unpacks a .vpxz archive (see `export vpxz`) into <tables folder>/<archive name>/ and indexes the new table. The archive must contain exactly one .vpx and is refused when an entry would end up outside of the table folder. With --move-roms the bundled pinmame/roms/*.zip go to the PinMAME roms folder instead, roms already there are kept.")
+                .arg(arg!(<VPXZPATH> "The path to the vpxz file").required(true))
+                .arg(
+                    Arg::new("TABLES_FOLDER")
+                        .long("tables-folder")
+                        .num_args(1)
+                        .help("Folder to import into. Defaults to the tables folder from the vpxtool config file."),
+                )
+                .arg(
+                    Arg::new("MOVE_ROMS")
+                        .long("move-roms")
+                        .num_args(0)
+                        .help("Move bundled roms to the PinMAME roms folder: the VPinballX.ini PinMAMEPath when absolute, otherwise the global pinmame folder"),
+                )
+                .arg(
+                    Arg::new("FORCE")
+                        .short('f')
+                        .long("force")
+                        .num_args(0)
+                        .help("Import into an existing table folder, overwriting files"),
+                ),
+        )
+        .subcommand(
+            Command::new(CMD_VPXZ)
+                .subcommand_required(true)
+                .about("Work with .vpxz archives")
+                .subcommand(
+                    Command::new(CMD_VPXZ_VERIFY)
+                        .about("Check .vpxz archives for mobile compatibility")
+                        .long_about("Checks that each archive follows the rules `export vpxz` enforces and the mobile importer expects: exactly one .vpx, no nested .vpxz, only the .directb2s matching the vpx and, when the script needs PinMAME, the rom as pinmame/roms/<rom>.zip next to the vpx. The embedded vpx is verified like the `verify` command does.")
+                        .arg(
+                            arg!(<VPXZPATH> "The path(s) to the vpxz file(s)")
+                                .required(true)
+                                .num_args(1..),
+                        ),
+                ),
+        )
         .subcommand(
             Command::new(CMD_EXPORT)
                 .subcommand_required(true)
@@ -1559,14 +2678,28 @@ fn build_command() -> Command {
                 .subcommand(
                     Command::new(CMD_EXPORT_VPXZ)
                         .about("Export the table as a .vpxz archive for the Visual Pinball mobile app")
-                        .long_about("Bundles the vpx and its sidecar files (.vbs, .ini, .directb2s, .png/.jpg) into a single .vpxz archive (a renamed zip). When the table is PinMAME-based, also bundles the matching rom zip from the configured pinmame folder unless --no-rom is set.")
-                        .arg(arg!(<VPXPATH> "The path to the vpx file").required(true))
+                        .long_about("Bundles the vpx and its sidecar files (.vbs, .ini, .directb2s, .png/.jpg) into a single .vpxz archive (a renamed zip). When the table is PinMAME-based, also bundles the matching rom zip from the configured pinmame folder unless --no-rom is set.\n\nWith --all every table in the configured tables folder, optionally narrowed down with the same filters as `index query`, is exported in parallel into the -o folder as <stem>.vpxz. Archives newer than all their files are skipped unless --force is set. A manifest.json listing each archive's size, whether the rom is bundled and the excluded files is written next to them.")
+                        .arg(
+                            arg!(<VPXPATH> "The path to the vpx file")
+                                .required(false)
+                                .required_unless_present("ALL")
+                                .conflicts_with("ALL"),
+                        )
+                        .arg(
+                            Arg::new("ALL")
+                                .long("all")
+                                .num_args(0)
+                                .requires("OUTPUT")
+                                .conflicts_with("DRY_RUN")
+                                .help("Export all indexed tables into the -o folder, with a manifest.json"),
+                        )
+                        .args(index_query_args().map(|arg| arg.requires("ALL")))
                         .arg(
                             Arg::new("OUTPUT")
                                 .short('o')
                                 .long("output")
                                 .num_args(1)
-                                .help("Output .vpxz path. Defaults to <stem>.vpxz one folder up from the vpx, so re-runs don't recursively pick up the previous output."),
+                                .help("Output .vpxz path, or the output folder with --all. Defaults to <stem>.vpxz one folder up from the vpx, so re-runs don't recursively pick up the previous output."),
                         )
                         .arg(
                             Arg::new("NO_ROM")
@@ -1574,12 +2707,47 @@ fn build_command() -> Command {
                                 .num_args(0)
                                 .help("Do not bundle the matching PinMAME rom zip"),
                         )
+                        .arg(
+                            Arg::new("OPTIMIZE")
+                                .long("optimize")
+                                .num_args(0)
+                                .help("Write a size-optimized copy of the vpx for mobile devices: lossless textures are converted to webp and textures larger than --max-texture-size are downscaled. The vpx on disk is not changed."),
+                        )
+                        .arg(
+                            Arg::new("MAX_TEXTURE_SIZE")
+                                .long("max-texture-size")
+                                .num_args(1)
+                                .value_parser(clap::value_parser!(u32).range(1..))
+                                .default_value(VPXZ_DEFAULT_MAX_TEXTURE_SIZE)
+                                .requires("OPTIMIZE")
+                                .help("With --optimize, downscale textures with a side larger than this many pixels"),
+                        )
+                        .arg(
+                            Arg::new("DROP_PUP")
+                                .long("drop-pup")
+                                .num_args(0)
+                                .requires("OPTIMIZE")
+                                .help("With --optimize, leave out PUP videos (pupvideos/ folders)"),
+                        )
+                        .arg(
+                            Arg::new("DROP_ALTSOUND")
+                                .long("drop-altsound")
+                                .num_args(0)
+                                .requires("OPTIMIZE")
+                                .help("With --optimize, leave out altsound packs (altsound/ folders)"),
+                        )
+                        .arg(
+                            Arg::new("DRY_RUN")
+                                .long("dry-run")
+                                .num_args(0)
+                                .help("Print every file with its size before and after, without writing the archive"),
+                        )
                         .arg(
                             Arg::new("FORCE")
                                 .short('f')
                                 .long("force")
                                 .num_args(0)
-                                .help("Overwrite the output file if it already exists"),
+                                .help("Overwrite the output file if it already exists, with --all also archives that are up to date"),
                         ),
                 )
                 .subcommand(
@@ -1760,6 +2928,9 @@ fn handle_export_gltf(sub_matches: &ArgMatches) -> io::Result<ExitCode> {
 }
 
 fn handle_export_vpxz(sub_matches: &ArgMatches) -> io::Result<ExitCode> {
+    if sub_matches.get_flag("ALL") {
+        return handle_export_vpxz_all(sub_matches);
+    }
     let path = sub_matches
         .get_one::<String>("VPXPATH")
         .map(|s| s.as_str())
@@ -1767,14 +2938,16 @@ fn handle_export_vpxz(sub_matches: &ArgMatches) -> io::Result<ExitCode> {
     let expanded_path = path_exists(path)?;
     let bundle_rom = !sub_matches.get_flag("NO_ROM");
     let force = sub_matches.get_flag("FORCE");
+    let dry_run = sub_matches.get_flag("DRY_RUN");
     let output = sub_matches.get_one::<String>("OUTPUT").map(PathBuf::from);
+    let optimize = vpxz_optimize_options(sub_matches);
 
     let output_path = match output {
         Some(p) => p,
         None => crate::vpxz::default_output_path(&expanded_path)?,
     };
 
-    if output_path.exists() {
+    if output_path.exists() && !dry_run {
         if output_path.is_dir() {
             return fail(format!(
                 "Output path exists and is a directory: {}",
@@ -1830,9 +3003,41 @@ fn handle_export_vpxz(sub_matches: &ArgMatches) -> io::Result<ExitCode> {
             exclude_globs: &exclude_globs,
             rom_zip: rom_zip.as_deref(),
             progress: Some(&progress),
+            optimize,
+            dry_run,
         },
     )?;
 
+    if dry_run {
+        for file in &report.included {
+            if file.size == file.original_size {
+                crate::println!("  {} {}", file.path, HumanBytes(file.size))?;
+            } else {
+                crate::println!(
+                    "  {} {} -> {}",
+                    file.path,
+                    HumanBytes(file.original_size),
+                    HumanBytes(file.size)
+                )?;
+            }
+        }
+    }
+    if !report.optimized_images.is_empty() {
+        crate::println!("Optimized {} images:", report.optimized_images.len())?;
+        for image in &report.optimized_images {
+            let change = &image.change;
+            crate::println!(
+                "  {} {} {}x{} -> {} {}x{}",
+                image.name,
+                change.old_extension,
+                change.old_size.0,
+                change.old_size.1,
+                change.new_extension,
+                change.new_size.0,
+                change.new_size.1
+            )?;
+        }
+    }
     if !report.excluded.is_empty() {
         crate::println!("Excluded {} files:", report.excluded.len())?;
         for (path, reason) in &report.excluded {
@@ -1851,8 +3056,18 @@ fn handle_export_vpxz(sub_matches: &ArgMatches) -> io::Result<ExitCode> {
             format!("Note: rom '{rom_name}' not found; not bundled.").truecolor(255, 125, 0)
         )?;
     }
+    let sizes = if report.size() == report.original_size() {
+        HumanBytes(report.size()).to_string()
+    } else {
+        format!(
+            "{} -> {}",
+            HumanBytes(report.original_size()),
+            HumanBytes(report.size())
+        )
+    };
     crate::println!(
-        "Wrote {} ({} included, {} excluded)",
+        "{} {} ({} included, {} excluded, {sizes} uncompressed)",
+        if dry_run { "Would write" } else { "Wrote" },
         report.output.display(),
         report.included.len(),
         report.excluded.len()
@@ -1860,6 +3075,314 @@ fn handle_export_vpxz(sub_matches: &ArgMatches) -> io::Result<ExitCode> {
     Ok(ExitCode::SUCCESS)
 }
 
+fn vpxz_optimize_options(sub_matches: &ArgMatches) -> Option<crate::vpxz::VpxzOptimizeOptions> {
+    sub_matches
+        .get_flag("OPTIMIZE")
+        .then(|| crate::vpxz::VpxzOptimizeOptions {
+            max_texture_size: sub_matches.get_one::<u32>("MAX_TEXTURE_SIZE").copied(),
+            drop_pup_videos: sub_matches.get_flag("DROP_PUP"),
+            drop_altsound: sub_matches.get_flag("DROP_ALTSOUND"),
+        })
+}
+
+/// `export vpxz --all`: one archive per indexed table in the output folder,
+/// plus a manifest.
+fn handle_export_vpxz_all(sub_matches: &ArgMatches) -> io::Result<ExitCode> {
+    let output_dir = sub_matches
+        .get_one::<String>("OUTPUT")
+        .map(PathBuf::from)
+        .unwrap_or_default();
+    let bundle_rom = !sub_matches.get_flag("NO_ROM");
+    let force = sub_matches.get_flag("FORCE");
+    let optimize = vpxz_optimize_options(sub_matches);
+    let query = match index_query(sub_matches) {
+        Ok(query) => query,
+        Err(e) => return fail(e.to_string()),
+    };
+    if output_dir.is_file() {
+        return fail(format!(
+            "Output path exists and is not a directory: {}",
+            output_dir.display()
+        ));
+    }
+    let Some((_, config)) = config::load_config()? else {
+        return fail("No config file found");
+    };
+
+    let configured_pinmame_folder = config.configured_pinmame_folder();
+    let tables = match frontend::frontend_index(
+        &config,
+        true,
+        config.tables_scan_max_depth,
+        configured_pinmame_folder.as_deref(),
+        vec![],
+    ) {
+        Ok(tables) => query.filter(tables),
+        Err(e) => {
+            crate::eprintln!("{}", format!("Unable to index tables: {e:?}").red())?;
+            return Ok(ExitCode::FAILURE);
+        }
+    };
+    if tables.is_empty() {
+        crate::println!("No tables to export")?;
+        return Ok(ExitCode::SUCCESS);
+    }
+    std::fs::create_dir_all(&output_dir)?;
+    let manifest_path = output_dir.join(crate::vpxz::MANIFEST_FILE_NAME);
+    let previous = if manifest_path.exists() {
+        match crate::vpxz::VpxzManifest::read(&manifest_path) {
+            Ok(manifest) => Some(manifest),
+            Err(e) => {
+                crate::eprintln!("{}", format!("{WARN} {e}, ignoring it").yellow())?;
+                None
+            }
+        }
+    } else {
+        None
+    };
+
+    // Two tables with the same file name would overwrite each other's archive.
+    let mut archive_owners: HashMap<String, &Path> = HashMap::new();
+    let archive_paths: Vec<(PathBuf, Option<io::Error>)> = tables
+        .iter()
+        .map(|table| {
+            let archive_name = format!(
+                "{}.vpxz",
+                table.path.file_stem().unwrap_or_default().to_string_lossy()
+            );
+            let collision = match archive_owners.entry(archive_name.to_lowercase()) {
+                Entry::Occupied(owner) => Some(io::Error::new(
+                    io::ErrorKind::AlreadyExists,
+                    format!(
+                        "{archive_name} is already used for {}",
+                        owner.get().display()
+                    ),
+                )),
+                Entry::Vacant(slot) => {
+                    slot.insert(&table.path);
+                    None
+                }
+            };
+            (output_dir.join(archive_name), collision)
+        })
+        .collect();
+
+    let pb = ProgressBar::new(tables.len() as u64);
+    pb.set_style(
+        ProgressStyle::with_template("{spinner:.green} [{bar:.cyan/blue}] {pos}/{human_len} {msg}")
+            .unwrap(),
+    );
+    pb.set_message("exporting");
+    let export_table =
+        |table: &IndexedTable, archive_path: &Path| -> io::Result<crate::vpxz::VpxzManifestEntry> {
+            let rom_zip = table
+                .rom_path()
+                .filter(|p| bundle_rom && p.is_file())
+                .cloned();
+            let rom_name = table
+                .game_name
+                .as_deref()
+                .filter(|_| table.requires_pinmame);
+            let options = crate::vpxz::VpxzExportOptions {
+                exclude_globs: &config.vpxz_excludes,
+                rom_zip: rom_zip.as_deref(),
+                progress: None,
+                optimize,
+                dry_run: false,
+            };
+            let exported = previous
+                .as_ref()
+                .and_then(|manifest| manifest.exported(&table.path));
+            if !force
+                && let Some(entry) = exported
+                && crate::vpxz::archive_up_to_date(&table.path, archive_path, &options, exported)?
+            {
+                return Ok(crate::vpxz::VpxzManifestEntry {
+                    status: crate::vpxz::ManifestStatus::UpToDate,
+                    size: Some(std::fs::metadata(archive_path)?.len()),
+                    ..entry.clone()
+                });
+            }
+            match crate::vpxz::export_vpxz(&table.path, archive_path, &options) {
+                Ok(report) => crate::vpxz::VpxzManifestEntry::new(
+                    &table.path,
+                    crate::vpxz::ManifestStatus::Exported,
+                    &report,
+                    rom_name,
+                    &options,
+                ),
+                Err(e) => {
+                    // a partial archive would look up to date next time
+                    let _ = std::fs::remove_file(archive_path);
+                    Err(e)
+                }
+            }
+        };
+    let entries: Vec<crate::vpxz::VpxzManifestEntry> = tables
+        .par_iter()
+        .zip(archive_paths.par_iter())
+        .map(|(table, (archive_path, collision))| {
+            let entry = match collision {
+                Some(e) => crate::vpxz::VpxzManifestEntry::failed(&table.path, archive_path, e),
+                None => export_table(table, archive_path).unwrap_or_else(|e| {
+                    crate::vpxz::VpxzManifestEntry::failed(&table.path, archive_path, &e)
+                }),
+            };
+            pb.inc(1);
+            entry
+        })
+        .collect();
+    pb.finish_and_clear();
+
+    let count = |status| entries.iter().filter(|e| e.status == status).count();
+    for entry in &entries {
+        if let Some(error) = &entry.error {
+            crate::eprintln!(
+                "{}",
+                format!("{NOK} {}: {error}", entry.table.display()).red()
+            )?;
+        }
+    }
+    let failed = count(crate::vpxz::ManifestStatus::Failed);
+    let exported = count(crate::vpxz::ManifestStatus::Exported);
+    let up_to_date = count(crate::vpxz::ManifestStatus::UpToDate);
+    // tables left out by the query keep their entries
+    crate::vpxz::VpxzManifest::merge(previous, entries).write(&manifest_path)?;
+    crate::println!(
+        "Exported {exported}, {up_to_date} up to date, {failed} failed. Wrote {}",
+        manifest_path.display()
+    )?;
+    Ok(if failed == 0 {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    })
+}
+
+fn handle_vpxz_verify(sub_matches: &ArgMatches) -> io::Result<ExitCode> {
+    let mut failed = false;
+    for path in sub_matches
+        .get_many::<String>("VPXZPATH")
+        .unwrap_or_default()
+    {
+        let expanded_path = path_exists(path)?;
+        let report = match crate::vpxz::verify_vpxz(&expanded_path) {
+            Ok(report) => report,
+            Err(e) => {
+                failed = true;
+                crate::eprintln!(
+                    "{}",
+                    format!("{NOK} {}: {e}", expanded_path.display()).red()
+                )?;
+                continue;
+            }
+        };
+        if report.is_ok() {
+            crate::println!("{OK} {}", expanded_path.display())?;
+        } else {
+            failed = true;
+            crate::eprintln!("{}", format!("{NOK} {}", expanded_path.display()).red())?;
+            for problem in &report.problems {
+                crate::eprintln!("  {problem}")?;
+            }
+        }
+    }
+    Ok(if failed {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    })
+}
+
+fn handle_import_vpxz(sub_matches: &ArgMatches) -> io::Result<ExitCode> {
+    let path = sub_matches
+        .get_one::<String>("VPXZPATH")
+        .map(|s| s.as_str())
+        .unwrap_or_default();
+    let expanded_path = path_exists(path)?;
+    let force = sub_matches.get_flag("FORCE");
+    let move_roms = sub_matches.get_flag("MOVE_ROMS");
+
+    let loaded_config = config::load_config()?;
+    let config = loaded_config.as_ref().map(|c| &c.1);
+    let tables_folder = match sub_matches.get_one::<String>("TABLES_FOLDER") {
+        Some(folder) => path_exists(folder)?,
+        None => match config {
+            Some(config) => config.tables_folder.clone(),
+            None => return fail("No --tables-folder provided and no vpxtool config file found"),
+        },
+    };
+    let roms_folder = if move_roms {
+        let Some(config) = config else {
+            return fail("--move-roms needs a vpxtool config file to find the PinMAME folder");
+        };
+        let pinmame_folder = config
+            .configured_pinmame_folder()
+            .filter(|folder| folder.is_absolute())
+            .unwrap_or_else(|| config.global_pinmame_folder());
+        Some(pinmame_folder.join("roms"))
+    } else {
+        None
+    };
+
+    let report = match crate::vpxz::import_vpxz(
+        &expanded_path,
+        &tables_folder,
+        &crate::vpxz::VpxzImportOptions {
+            roms_folder: roms_folder.as_deref(),
+            force,
+        },
+    ) {
+        Ok(report) => report,
+        Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+            return fail(format!("{e}, use --force to import anyway"));
+        }
+        Err(e) => return fail(e.to_string()),
+    };
+    for rom in &report.moved_roms {
+        crate::println!("Moved rom to {}", rom.display())?;
+    }
+    for rom in &report.existing_roms {
+        crate::println!("Kept existing rom {}", rom.display())?;
+    }
+    crate::println!(
+        "Imported {} files into {}",
+        report.extracted,
+        report.table_dir.display()
+    )?;
+
+    if let Some(config) = config
+        && tables_folder == config.tables_folder
+    {
+        let configured_pinmame_folder = config.configured_pinmame_folder();
+        if let Err(e) = frontend::frontend_index(
+            config,
+            true,
+            config.tables_scan_max_depth,
+            configured_pinmame_folder.as_deref(),
+            vec![report.vpx_path.clone()],
+        ) {
+            crate::eprintln!(
+                "{}",
+                format!(
+                    "{WARN} Unable to index {}: {e:?}",
+                    report.vpx_path.display()
+                )
+                .yellow()
+            )?;
+        } else {
+            crate::println!("Indexed {}", report.vpx_path.display())?;
+        }
+    } else {
+        crate::println!(
+            "Run `vpxtool index {}` to index the new table",
+            tables_folder.display()
+        )?;
+    }
+    crate::println!("{OK} {}", report.vpx_path.display())?;
+    Ok(ExitCode::SUCCESS)
+}
+
 /// Resolve `path` to an nvram file. Accepts:
 /// * a `.nv` file directly,
 /// * a rom `.zip` (looks for `../nvram/<stem>.nv` next to it), or
@@ -1877,28 +3400,31 @@ enum NvramResolveError {
 }
 
 impl NvramResolveError {
-    fn fail(self) -> io::Result<ExitCode> {
+    fn message(&self) -> String {
         match self {
             NvramResolveError::NotPinmame(p) => {
-                fail(format!("Table {} is not PinMAME-based", p.display()))
+                format!("Table {} is not PinMAME-based", p.display())
             }
-            NvramResolveError::NoNvramFor(p) => fail(format!(
+            NvramResolveError::NoNvramFor(p) => format!(
                 "No nvram file found for {} - try launching the table once",
                 p.display()
-            )),
-            NvramResolveError::NoNvramNextToZip(p) => fail(format!(
-                "No nvram file found next to rom zip {}",
-                p.display()
-            )),
+            ),
+            NvramResolveError::NoNvramNextToZip(p) => {
+                format!("No nvram file found next to rom zip {}", p.display())
+            }
             NvramResolveError::InvalidZipStem(p) => {
-                fail(format!("rom zip has no usable file stem: {}", p.display()))
+                format!("rom zip has no usable file stem: {}", p.display())
             }
-            NvramResolveError::UnsupportedExtension(p) => fail(format!(
+            NvramResolveError::UnsupportedExtension(p) => format!(
                 "Unsupported file type: {} (expected .vpx, .nv, or rom .zip)",
                 p.display()
-            )),
+            ),
         }
     }
+
+    fn fail(self) -> io::Result<ExitCode> {
+        fail(self.message())
+    }
 }
 
 fn resolve_nvram_path(expanded_path: &Path) -> io::Result<Result<PathBuf, NvramResolveError>> {
@@ -1998,40 +3524,53 @@ fn handle_scores_show(sub_matches: &ArgMatches) -> io::Result<ExitCode> {
         .unwrap_or("table");
     let expanded_path = path_exists(path)?;
 
-    // Resolve the input into a flat list of sections, trying PinMAME first
-    // and falling back to VPReg.ini for .vpx tables that are not PinMAME.
-    let sections: Vec<crate::scores::Section> = match resolve_nvram_path(&expanded_path)? {
+    match find_score_sections(&expanded_path)? {
+        Ok(sections) => render_sections(&sections, format),
+        Err(msg) => fail(msg),
+    }
+}
+
+/// Resolve the high scores of a `.vpx`, `.nv` or rom `.zip` into a flat list
+/// of sections, trying PinMAME first and falling back to VPReg.ini, GLF and
+/// EM-style `.txt` files for `.vpx` tables that are not PinMAME. The inner
+/// `Err` explains why no scores were found.
+pub(crate) fn find_score_sections(
+    expanded_path: &Path,
+) -> io::Result<Result<Vec<crate::scores::Section>, String>> {
+    let sections = match resolve_nvram_path(expanded_path)? {
         Ok(nvram_path) => match pinmame_nvram::resolve::resolve(&nvram_path) {
             Ok(Some(r)) => crate::scores::extract_sections(&r),
             Ok(None) => {
-                return fail(format!("No pinmame-nvram map for {}", nvram_path.display()));
+                return Ok(Err(format!(
+                    "No pinmame-nvram map for {}",
+                    nvram_path.display()
+                )));
             }
             Err(e) => {
-                return fail(format!(
+                return Ok(Err(format!(
                     "Failed to resolve nvram {}: {e}",
                     nvram_path.display()
-                ));
+                )));
             }
         },
-        Err(prior) => match try_non_pinmame_fallback(&expanded_path, &prior)? {
+        Err(prior) => match try_non_pinmame_fallback(expanded_path, &prior)? {
             Some(sections) => sections,
             None => match &prior {
                 // For a rom-less .vpx we probed VPReg, GLF, and EM .txt
                 // before giving up - the original "not PinMAME-based" wording
                 // would suggest we never tried. Surface a holistic message.
                 NvramResolveError::NotPinmame(p) => {
-                    return fail(format!(
+                    return Ok(Err(format!(
                         "Could not find any high scores for {}: tried PinMAME \
                          nvram, VPReg.ini, GLF, and EM-style .txt files",
                         p.display()
-                    ));
+                    )));
                 }
-                _ => return prior.fail(),
+                _ => return Ok(Err(prior.message())),
             },
         },
     };
-
-    render_sections(&sections, format)
+    Ok(Ok(sections))
 }
 
 /// If `expanded_path` is a `.vpx` that PinMAME resolution couldn't handle,
@@ -2198,35 +3737,38 @@ fn render_sections(sections: &[crate::scores::Section], format: &str) -> io::Res
             crate::print!("{}", rendered)?;
         }
         _ => {
-            // Human table view: drop the trailing UNITS column after using
-            // it to format the SCORE column (e.g. seconds -> mm:ss).
-            let mut rows: Vec<Vec<String>> = sections
-                .iter()
-                .flat_map(|s| s.rows.iter().cloned())
-                .collect();
-            #[cfg(not(windows))]
-            if let Some(sys) = readable_system_locale() {
-                crate::scores::pretty_score_column(&mut rows, &sys);
-            } else {
-                crate::scores::pretty_score_column(&mut rows, &num_format::Locale::en);
-            }
-            #[cfg(windows)]
-            crate::scores::pretty_score_column(&mut rows, &num_format::Locale::en);
             let visible_headers = ["LABEL", "INITIALS", "SCORE"];
             let aligns = [ColAlign::Left, ColAlign::Left, ColAlign::Right];
-            let visible_rows: Vec<Vec<String>> = rows
-                .into_iter()
-                .map(|mut r| {
-                    r.truncate(3);
-                    r
-                })
-                .collect();
-            print_aligned_table(&visible_headers, &aligns, &visible_rows)?;
+            print_aligned_table(&visible_headers, &aligns, &pretty_score_rows(sections))?;
         }
     }
     Ok(ExitCode::SUCCESS)
 }
 
+/// Rows for the human table view: the SCORE column formatted for the user's
+/// locale and units (e.g. seconds -> mm:ss), the trailing UNITS column
+/// dropped.
+pub(crate) fn pretty_score_rows(sections: &[crate::scores::Section]) -> Vec<Vec<String>> {
+    let mut rows: Vec<Vec<String>> = sections
+        .iter()
+        .flat_map(|s| s.rows.iter().cloned())
+        .collect();
+    #[cfg(not(windows))]
+    if let Some(sys) = readable_system_locale() {
+        crate::scores::pretty_score_column(&mut rows, &sys);
+    } else {
+        crate::scores::pretty_score_column(&mut rows, &num_format::Locale::en);
+    }
+    #[cfg(windows)]
+    crate::scores::pretty_score_column(&mut rows, &num_format::Locale::en);
+    rows.into_iter()
+        .map(|mut r| {
+            r.truncate(3);
+            r
+        })
+        .collect()
+}
+
 /// Read the user's `LC_ALL` / `LANG` / `LC_NUMERIC` to pick a thousands
 /// separator, but **fall back to `Locale::en` (comma) when the system locale
 /// has no separator at all** - the POSIX `C` / `POSIX` locales specify
@@ -2246,55 +3788,162 @@ fn readable_system_locale() -> Option<num_format::SystemLocale> {
     Some(sys)
 }
 
-/// Right- vs left-aligned column. The last column is printed without trailing
-/// padding regardless, so paths with spaces don't break awk-style splitting
-/// on the preceding fields.
-#[derive(Clone, Copy)]
-enum ColAlign {
-    Left,
-    Right,
-}
-
-fn print_aligned_table(
-    headers: &[&str],
-    aligns: &[ColAlign],
-    rows: &[Vec<String>],
-) -> io::Result<()> {
-    assert_eq!(headers.len(), aligns.len());
-    let mut widths: Vec<usize> = headers.iter().map(|h| h.len()).collect();
-    for row in rows {
-        assert_eq!(row.len(), headers.len());
-        for (i, cell) in row.iter().enumerate() {
-            if cell.len() > widths[i] {
-                widths[i] = cell.len();
-            }
-        }
-    }
-    let mut buf = String::new();
-    let mut emit = |cells: &[&str]| -> io::Result<()> {
-        buf.clear();
-        let last = cells.len() - 1;
-        for (i, cell) in cells.iter().enumerate() {
-            if i > 0 {
-                buf.push_str("  ");
-            }
-            if i == last {
-                buf.push_str(cell);
-            } else {
-                match aligns[i] {
-                    ColAlign::Left => buf.push_str(&format!("{:<w$}", cell, w = widths[i])),
-                    ColAlign::Right => buf.push_str(&format!("{:>w$}", cell, w = widths[i])),
-                }
+fn list_format(sub_matches: &ArgMatches) -> io::Result<ListFormat> {
+    sub_matches
+        .get_one::<String>(ARG_FORMAT)
+        .map(|s| s.parse::<ListFormat>())
+        .transpose()
+        .map_err(io::Error::other)
+        .map(Option::unwrap_or_default)
+}
+
+fn list_format_arg() -> Arg {
+    Arg::new(ARG_FORMAT)
+        .long("format")
+        .value_parser(ListFormat::VALUES)
+        .default_value("table")
+        .help("Output format: 'table' (aligned columns, default), or 'tsv', 'csv', 'json' with raw values keyed by stable snake_case column keys")
+}
+
+fn handle_script_lint(sub_matches: &ArgMatches) -> io::Result<ExitCode> {
+    let path = sub_matches
+        .get_one::<String>("PATH")
+        .map(|s| s.as_str())
+        .unwrap_or_default();
+    let format = list_format(sub_matches)?;
+    let expanded_path = path_exists(path)?;
+    let (root, vpx_paths) = if expanded_path.is_dir() {
+        let mut vpx_paths: Vec<PathBuf> = find_vpx_files(true, None, &expanded_path)?
+            .into_iter()
+            .map(|p| p.path)
+            .collect();
+        vpx_paths.sort();
+        (expanded_path.clone(), vpx_paths)
+    } else {
+        let parent = expanded_path
+            .parent()
+            .unwrap_or(Path::new(""))
+            .to_path_buf();
+        (parent, vec![expanded_path.clone()])
+    };
+    // ExecuteGlobal'd files are looked up next to the table and in the
+    // scripts folder next to the vpinball executable
+    let vpinball_scripts_dir = config::load_config()?
+        .and_then(|(_, config)| config.vpx_executable.parent().map(|p| p.join("scripts")));
+
+    let mut failed = false;
+    let mut rows = Vec::new();
+    for vpx_path in &vpx_paths {
+        let table = vpx_path
+            .strip_prefix(&root)
+            .unwrap_or(vpx_path)
+            .display()
+            .to_string();
+        let code = vpx::open(vpx_path)
+            .and_then(|mut vpx_file| vpx_file.read_gamedata())
+            .and_then(|game_data| consider_sidecar_vbs(vpx_path, game_data));
+        let code = match code {
+            Ok(code) => code,
+            Err(e) => {
+                let warning = format!("Error reading script from {table}: {e}").red();
+                crate::eprintln!("{}", warning)?;
+                failed = true;
+                continue;
             }
+        };
+        let mut script_dirs = vec![vpx_path.parent().unwrap_or(Path::new("")).to_path_buf()];
+        script_dirs.extend(vpinball_scripts_dir.clone());
+        for finding in lint_script(&code, &script_dirs) {
+            failed |= finding.severity == Severity::Error;
+            rows.push(vec![
+                Cell::text(&table),
+                Cell::Int(finding.line as i64),
+                Cell::Int(finding.column as i64),
+                Cell::text(finding.severity.to_string()),
+                Cell::text(finding.rule),
+                Cell::text(finding.message),
+            ]);
         }
-        crate::println!("{}", buf)
-    };
-    emit(headers)?;
-    for row in rows {
-        let refs: Vec<&str> = row.iter().map(String::as_str).collect();
-        emit(&refs)?;
     }
-    Ok(())
+
+    let columns = [
+        Column::left("TABLE", "table"),
+        Column::right("LINE", "line"),
+        Column::right("COL", "column"),
+        Column::left("SEVERITY", "severity"),
+        Column::left("RULE", "rule"),
+        Column::left("MESSAGE", "message"),
+    ];
+    print_list(format, &columns, &rows)?;
+    if failed {
+        Ok(ExitCode::FAILURE)
+    } else {
+        Ok(ExitCode::SUCCESS)
+    }
+}
+
+fn handle_script_xref(sub_matches: &ArgMatches) -> io::Result<ExitCode> {
+    let path = sub_matches
+        .get_one::<String>("VPXPATH")
+        .map(|s| s.as_str())
+        .unwrap_or_default();
+    let format = list_format(sub_matches)?;
+    let expanded_path = path_exists(path)?;
+    let mut vpx_file = vpx::open(&expanded_path)?;
+    let game_data = vpx_file.read_gamedata()?;
+    let table_name = game_data.name.clone();
+    let code = consider_sidecar_vbs(&expanded_path, game_data)?;
+    let items: Vec<XrefItem> = vpx_file
+        .read_gameitems()?
+        .iter()
+        // part groups only exist in the editor
+        .filter(|item| !matches!(item, GameItemEnum::PartGroup(_)))
+        .map(XrefItem::from_gameitem)
+        .collect();
+    let collections: Vec<XrefCollection> = vpx_file
+        .read_collections()?
+        .into_iter()
+        .map(|c| XrefCollection {
+            name: c.name,
+            items: c.items,
+        })
+        .collect();
+
+    let findings = xref_script(&code, &table_name, &items, &collections);
+    let rows: Vec<Vec<Cell>> = findings
+        .iter()
+        .map(|finding| {
+            vec![
+                Cell::text(&finding.name),
+                finding
+                    .line
+                    .map(|line| Cell::Int(line as i64))
+                    .unwrap_or(Cell::Empty),
+                finding
+                    .column
+                    .map(|column| Cell::Int(column as i64))
+                    .unwrap_or(Cell::Empty),
+                Cell::text(finding.severity.to_string()),
+                Cell::text(finding.rule),
+                Cell::text(&finding.message),
+            ]
+        })
+        .collect();
+
+    let columns = [
+        Column::left("NAME", "name"),
+        Column::right("LINE", "line"),
+        Column::right("COL", "column"),
+        Column::left("SEVERITY", "severity"),
+        Column::left("RULE", "rule"),
+        Column::left("MESSAGE", "message"),
+    ];
+    print_list(format, &columns, &rows)?;
+    if findings.iter().any(|f| f.severity == Severity::Error) {
+        Ok(ExitCode::FAILURE)
+    } else {
+        Ok(ExitCode::SUCCESS)
+    }
 }
 
 fn handle_images_list(sub_matches: &ArgMatches) -> io::Result<ExitCode> {
@@ -2302,11 +3951,12 @@ fn handle_images_list(sub_matches: &ArgMatches) -> io::Result<ExitCode> {
         .get_one::<String>("VPXPATH")
         .map(|s| s.as_str())
         .unwrap_or_default();
+    let format = list_format(sub_matches)?;
     let expanded_path = path_exists(path)?;
     let mut vpx_file = vpx::open(&expanded_path)?;
     let images = vpx_file.read_images()?;
 
-    let rows: Vec<Vec<String>> = images
+    let rows: Vec<Vec<Cell>> = images
         .iter()
         .map(|image| {
             let size_bytes = if let Some(jpeg) = &image.jpeg {
@@ -2317,30 +3967,123 @@ fn handle_images_list(sub_matches: &ArgMatches) -> io::Result<ExitCode> {
                 0
             };
             vec![
-                image.name.clone(),
-                image.ext(),
-                image.width.to_string(),
-                image.height.to_string(),
-                size_bytes.to_string(),
-                if image.is_link() { "Y" } else { "N" }.to_string(),
-                image.path.clone(),
+                Cell::text(&image.name),
+                Cell::text(image.ext()),
+                Cell::int(image.width),
+                Cell::int(image.height),
+                Cell::Int(size_bytes as i64),
+                Cell::flag(image.is_link()),
+                Cell::text(&image.path),
             ]
         })
         .collect();
 
-    let headers = [
-        "NAME", "FORMAT", "WIDTH", "HEIGHT", "SIZE", "LINKED", "PATH",
-    ];
-    let aligns = [
-        ColAlign::Left,
-        ColAlign::Left,
-        ColAlign::Right,
-        ColAlign::Right,
-        ColAlign::Right,
-        ColAlign::Left,
-        ColAlign::Left,
+    let columns = [
+        Column::left("NAME", "name"),
+        Column::left("FORMAT", "format"),
+        Column::right("WIDTH", "width"),
+        Column::right("HEIGHT", "height"),
+        Column::right("SIZE", "size"),
+        Column::left("LINKED", "linked"),
+        Column::left("PATH", "path"),
     ];
-    print_aligned_table(&headers, &aligns, &rows)?;
+    print_list(format, &columns, &rows)?;
+    Ok(ExitCode::SUCCESS)
+}
+
+fn handle_images_extract(sub_matches: &ArgMatches) -> io::Result<ExitCode> {
+    let path = sub_matches
+        .get_one::<String>("VPXPATH")
+        .map(|s| s.as_str())
+        .unwrap_or_default();
+    let name = sub_matches
+        .get_one::<String>("NAME")
+        .map(|s| s.as_str())
+        .unwrap_or_default();
+    let force = sub_matches.get_flag("FORCE");
+    let expanded_path = path_exists(path)?;
+    let mut vpx_file = vpx::open(&expanded_path)?;
+    let images = vpx_file.read_images()?;
+    let Some(index) = crate::images::find_image(&images, name) else {
+        return fail(format!(
+            "Image \"{name}\" not found in {}, use `images list` to see the available images",
+            expanded_path.display()
+        ));
+    };
+    let image = &images[index];
+    let output_path = sub_matches
+        .get_one::<String>("OUTPUT")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from(crate::images::image_file_name(image)));
+    if output_path.exists() && !force {
+        let confirmed = confirm(
+            format!("File \"{}\" already exists", output_path.display()),
+            "Do you want to overwrite it?".to_string(),
+        )?;
+        if !confirmed {
+            crate::println!("Aborted")?;
+            return Ok(ExitCode::FAILURE);
+        }
+    }
+    let bytes = crate::images::image_file_bytes(image)?;
+    std::fs::write(&output_path, &bytes)?;
+    crate::println!(
+        "Extracted image \"{}\" ({}x{} {}) to {}",
+        image.name,
+        image.width,
+        image.height,
+        crate::images::image_extension(image),
+        output_path.display()
+    )?;
+    Ok(ExitCode::SUCCESS)
+}
+
+fn handle_images_replace(sub_matches: &ArgMatches) -> io::Result<ExitCode> {
+    let path = sub_matches
+        .get_one::<String>("VPXPATH")
+        .map(|s| s.as_str())
+        .unwrap_or_default();
+    let name = sub_matches
+        .get_one::<String>("NAME")
+        .map(|s| s.as_str())
+        .unwrap_or_default();
+    let file = sub_matches
+        .get_one::<String>("FILE")
+        .map(|s| s.as_str())
+        .unwrap_or_default();
+    let expanded_path = path_exists(path)?;
+    let expanded_file = path_exists(file)?;
+    let mut vpx = vpx::read(&expanded_path)?;
+    let Some(index) = crate::images::find_image(&vpx.images, name) else {
+        return fail(format!(
+            "Image \"{name}\" not found in {}, use `images list` to see the available images",
+            expanded_path.display()
+        ));
+    };
+    let data = std::fs::read(&expanded_file)?;
+    let file_name = expanded_file
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let image = &mut vpx.images[index];
+    let replacement = match crate::images::replace_image_data(image, &file_name, data) {
+        Ok(replacement) => replacement,
+        Err(e) => return fail(e.to_string()),
+    };
+    crate::println!(
+        "Replaced image \"{}\": {}x{} {} -> {}x{} {}",
+        image.name,
+        replacement.old_size.0,
+        replacement.old_size.1,
+        replacement.old_extension,
+        replacement.new_size.0,
+        replacement.new_size.1,
+        replacement.new_extension
+    )?;
+    let bytes = vpx::to_bytes(&vpx)?;
+    crate::atomicwrite::atomic_write(&expanded_path, |file| file.write_all(&bytes))?;
+    crate::println!("Compacting vpx file")?;
+    vpx::compact(&expanded_path)?;
     Ok(ExitCode::SUCCESS)
 }
 
@@ -2349,11 +4092,12 @@ fn handle_sounds_list(sub_matches: &ArgMatches) -> io::Result<ExitCode> {
         .get_one::<String>("VPXPATH")
         .map(|s| s.as_str())
         .unwrap_or_default();
+    let format = list_format(sub_matches)?;
     let expanded_path = path_exists(path)?;
     let mut vpx_file = vpx::open(&expanded_path)?;
     let sounds = vpx_file.read_sounds()?;
 
-    let rows: Vec<Vec<String>> = sounds
+    let rows: Vec<Vec<Cell>> = sounds
         .iter()
         .map(|sound| {
             let ext = sound
@@ -2370,53 +4114,296 @@ fn handle_sounds_list(sub_matches: &ArgMatches) -> io::Result<ExitCode> {
                 ext
             };
             let output = match sound.output_target {
-                vpin::vpx::sound::OutputTarget::Table => "table",
-                vpin::vpx::sound::OutputTarget::Backglass => "backglass",
+                OutputTarget::Table => "table",
+                OutputTarget::Backglass => "backglass",
             };
             // For WAV (PCM) we can derive the playback duration from the raw
             // sample bytes; for non-WAV containers the data is compressed and
             // we'd have to parse the container, so leave it blank.
             let length = if is_wav && sound.wave_form.avg_bytes_per_sec > 0 {
-                format!(
-                    "{:.2}",
-                    sound.data.len() as f64 / sound.wave_form.avg_bytes_per_sec as f64
-                )
+                Cell::Float {
+                    value: sound.data.len() as f64 / sound.wave_form.avg_bytes_per_sec as f64,
+                    precision: 2,
+                }
             } else {
-                String::new()
+                Cell::Empty
             };
             vec![
-                sound.name.clone(),
-                format,
-                output.to_string(),
-                sound.balance.to_string(),
-                sound.fade.to_string(),
-                sound.volume.to_string(),
-                sound.wave_form.samples_per_sec.to_string(),
-                sound.wave_form.channels.to_string(),
+                Cell::text(&sound.name),
+                Cell::text(format),
+                Cell::text(output),
+                Cell::int(sound.balance),
+                Cell::int(sound.fade),
+                Cell::int(sound.volume),
+                Cell::int(sound.wave_form.samples_per_sec),
+                Cell::int(sound.wave_form.channels),
                 length,
-                sound.data.len().to_string(),
-                sound.path.clone(),
+                Cell::Int(sound.data.len() as i64),
+                Cell::text(&sound.path),
             ]
         })
         .collect();
 
-    let headers = [
-        "NAME", "FORMAT", "OUTPUT", "PAN", "FADE", "VOL", "FREQ", "CHAN", "LENGTH", "SIZE", "PATH",
+    let columns = [
+        Column::left("NAME", "name"),
+        Column::left("FORMAT", "format"),
+        Column::left("OUTPUT", "output"),
+        Column::right("PAN", "pan"),
+        Column::right("FADE", "fade"),
+        Column::right("VOL", "volume"),
+        Column::right("FREQ", "sample_rate"),
+        Column::right("CHAN", "channels"),
+        Column::right("LENGTH", "length_secs"),
+        Column::right("SIZE", "size"),
+        Column::left("PATH", "path"),
     ];
-    let aligns = [
-        ColAlign::Left,  // NAME
-        ColAlign::Left,  // FORMAT
-        ColAlign::Left,  // OUTPUT
-        ColAlign::Right, // PAN
-        ColAlign::Right, // FADE
-        ColAlign::Right, // VOL
-        ColAlign::Right, // FREQ
-        ColAlign::Right, // CHAN
-        ColAlign::Right, // LENGTH
-        ColAlign::Right, // SIZE
-        ColAlign::Left,  // PATH
-    ];
-    print_aligned_table(&headers, &aligns, &rows)?;
+    print_list(format, &columns, &rows)?;
+    Ok(ExitCode::SUCCESS)
+}
+
+fn handle_sounds_extract(sub_matches: &ArgMatches) -> io::Result<ExitCode> {
+    let path = sub_matches
+        .get_one::<String>("VPXPATH")
+        .map(|s| s.as_str())
+        .unwrap_or_default();
+    let name = sub_matches.get_one::<String>("NAME").map(|s| s.as_str());
+    let output = sub_matches.get_one::<String>("OUTPUT").map(PathBuf::from);
+    let force = sub_matches.get_flag("FORCE");
+    let expanded_path = path_exists(path)?;
+    let mut vpx_file = vpx::open(&expanded_path)?;
+    let sounds = vpx_file.read_sounds()?;
+
+    let targets: Vec<(PathBuf, &SoundData)> = match name {
+        Some(name) => {
+            let Some(index) = crate::sounds::find_sound(&sounds, name) else {
+                return fail(format!(
+                    "Sound \"{name}\" not found in {}, use `sounds list` to see the available sounds",
+                    expanded_path.display()
+                ));
+            };
+            let sound = &sounds[index];
+            let output_path =
+                output.unwrap_or_else(|| PathBuf::from(crate::sounds::sound_file_name(sound)));
+            vec![(output_path, sound)]
+        }
+        None => {
+            let output_dir = output.unwrap_or_else(|| {
+                let stem = expanded_path
+                    .file_stem()
+                    .map(|s| s.to_string_lossy().to_string())
+                    .unwrap_or_default();
+                PathBuf::from(format!("{stem}_sounds"))
+            });
+            // vpinball does not enforce unique names, dedup like `extract` does
+            let mut seen = HashSet::new();
+            let mut dedup_counter = 0;
+            sounds
+                .iter()
+                .map(|sound| {
+                    let mut file_name = crate::sounds::sound_file_name(sound);
+                    if !seen.insert(file_name.to_lowercase()) {
+                        dedup_counter += 1;
+                        file_name = format!(
+                            "{}_dedup{dedup_counter}.{}",
+                            crate::safe_file_name(&sound.name),
+                            crate::sounds::sound_extension(sound)
+                        );
+                        seen.insert(file_name.to_lowercase());
+                    }
+                    (output_dir.join(file_name), sound)
+                })
+                .collect()
+        }
+    };
+    if targets.is_empty() {
+        crate::println!("No sounds in {}", expanded_path.display())?;
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    let existing = targets.iter().filter(|(path, _)| path.exists()).count();
+    if existing > 0 && !force {
+        let msg = match &targets[..] {
+            [(path, _)] => format!("File \"{}\" already exists", path.display()),
+            _ => format!("{existing} of the sound files already exist"),
+        };
+        let confirmed = confirm(msg, "Do you want to overwrite them?".to_string())?;
+        if !confirmed {
+            crate::println!("Aborted")?;
+            return Ok(ExitCode::FAILURE);
+        }
+    }
+    for (output_path, sound) in &targets {
+        if let Some(parent) = output_path.parent()
+            && !parent.as_os_str().is_empty()
+        {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(output_path, write_sound(sound))?;
+        crate::println!(
+            "Extracted sound \"{}\" to {}",
+            sound.name,
+            output_path.display()
+        )?;
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+fn handle_sounds_replace(sub_matches: &ArgMatches) -> io::Result<ExitCode> {
+    let path = sub_matches
+        .get_one::<String>("VPXPATH")
+        .map(|s| s.as_str())
+        .unwrap_or_default();
+    let name = sub_matches
+        .get_one::<String>("NAME")
+        .map(|s| s.as_str())
+        .unwrap_or_default();
+    let file = sub_matches
+        .get_one::<String>("FILE")
+        .map(|s| s.as_str())
+        .unwrap_or_default();
+    let expanded_path = path_exists(path)?;
+    let expanded_file = path_exists(file)?;
+    let mut vpx = vpx::read(&expanded_path)?;
+    let Some(index) = crate::sounds::find_sound(&vpx.sounds, name) else {
+        return fail(format!(
+            "Sound \"{name}\" not found in {}, use `sounds list` to see the available sounds",
+            expanded_path.display()
+        ));
+    };
+    let data = std::fs::read(&expanded_file)?;
+    let file_name = expanded_file
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let sound = &mut vpx.sounds[index];
+    let replacement = match crate::sounds::replace_sound_data(sound, &file_name, data) {
+        Ok(replacement) => replacement,
+        Err(e) => return fail(e.to_string()),
+    };
+    crate::println!(
+        "Replaced sound \"{}\": {} ({} bytes) -> {} ({} bytes)",
+        sound.name,
+        replacement.old_extension,
+        replacement.old_size,
+        replacement.new_extension,
+        replacement.new_size
+    )?;
+    let bytes = vpx::to_bytes(&vpx)?;
+    crate::atomicwrite::atomic_write(&expanded_path, |file| file.write_all(&bytes))?;
+    crate::println!("Compacting vpx file")?;
+    vpx::compact(&expanded_path)?;
+    Ok(ExitCode::SUCCESS)
+}
+
+fn handle_sounds_import(sub_matches: &ArgMatches) -> io::Result<ExitCode> {
+    let path = sub_matches
+        .get_one::<String>("VPXPATH")
+        .map(|s| s.as_str())
+        .unwrap_or_default();
+    let file = sub_matches
+        .get_one::<String>("FILE")
+        .map(|s| s.as_str())
+        .unwrap_or_default();
+    let backglass = sub_matches.get_flag("BACKGLASS");
+    let expanded_path = path_exists(path)?;
+    let expanded_file = path_exists(file)?;
+    let name = match sub_matches.get_one::<String>("NAME") {
+        Some(name) => name.clone(),
+        None => expanded_file
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_default(),
+    };
+    let mut vpx = vpx::read(&expanded_path)?;
+    if let Some(index) = crate::sounds::find_sound(&vpx.sounds, &name) {
+        return fail(format!(
+            "Sound \"{}\" already exists in {}, use `sounds replace` to replace it",
+            vpx.sounds[index].name,
+            expanded_path.display()
+        ));
+    }
+    let data = std::fs::read(&expanded_file)?;
+    let import_path = std::path::absolute(&expanded_file)?;
+    let mut sound = match crate::sounds::new_sound(&name, &import_path.to_string_lossy(), data) {
+        Ok(sound) => sound,
+        Err(e) => return fail(e.to_string()),
+    };
+    if backglass {
+        sound.output_target = OutputTarget::Backglass;
+    }
+    crate::println!(
+        "Imported sound \"{}\" ({}, {} bytes)",
+        sound.name,
+        crate::sounds::sound_extension(&sound),
+        sound.data.len()
+    )?;
+    vpx.sounds.push(sound);
+    vpx.gamedata.sounds_size += 1;
+    let bytes = vpx::to_bytes(&vpx)?;
+    crate::atomicwrite::atomic_write(&expanded_path, |file| file.write_all(&bytes))?;
+    crate::println!("Compacting vpx file")?;
+    vpx::compact(&expanded_path)?;
+    Ok(ExitCode::SUCCESS)
+}
+
+fn handle_prune(sub_matches: &ArgMatches) -> io::Result<ExitCode> {
+    let path = sub_matches
+        .get_one::<String>("VPXPATH")
+        .map(|s| s.as_str())
+        .unwrap_or_default();
+    let dry_run = sub_matches.get_flag("DRY_RUN");
+    let expanded_path = path_exists(path)?;
+    let mut vpx = vpx::read(&expanded_path)?;
+    let vbs_path = vpx::vbs_path_for(&expanded_path);
+    let sidecar = if vbs_path.exists() {
+        Some(read_script(&vbs_path)?.0)
+    } else {
+        None
+    };
+    let scripts: Vec<&str> = std::iter::once(vpx.gamedata.code.string.as_str())
+        .chain(sidecar.as_deref())
+        .collect();
+    let unused = find_unused_assets(&vpx, &scripts);
+    if unused.is_empty() {
+        crate::println!("No unused assets found")?;
+        return Ok(ExitCode::SUCCESS);
+    }
+    for asset in &unused {
+        if asset.size > 0 {
+            crate::println!(
+                "  {} {} ({})",
+                asset.kind,
+                asset.name,
+                HumanBytes(asset.size)
+            )?;
+        } else {
+            crate::println!("  {} {}", asset.kind, asset.name)?;
+        }
+    }
+    let asset_bytes: u64 = unused.iter().map(|a| a.size).sum();
+    if dry_run {
+        crate::println!(
+            "Would remove {} unused assets ({})",
+            unused.len(),
+            HumanBytes(asset_bytes)
+        )?;
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    let size_before = std::fs::metadata(&expanded_path)?.len();
+    remove_assets(&mut vpx, &unused);
+    let bytes = vpx::to_bytes(&vpx)?;
+    crate::atomicwrite::atomic_write(&expanded_path, |file| file.write_all(&bytes))?;
+    crate::println!("Compacting vpx file")?;
+    vpx::compact(&expanded_path)?;
+    let size_after = std::fs::metadata(&expanded_path)?.len();
+    crate::println!(
+        "Removed {} unused assets, {} -> {} (saved {})",
+        unused.len(),
+        HumanBytes(size_before),
+        HumanBytes(size_after),
+        HumanBytes(size_before.saturating_sub(size_after))
+    )?;
     Ok(ExitCode::SUCCESS)
 }
 
@@ -2425,38 +4412,32 @@ fn handle_collections_list(sub_matches: &ArgMatches) -> io::Result<ExitCode> {
         .get_one::<String>("VPXPATH")
         .map(|s| s.as_str())
         .unwrap_or_default();
+    let format = list_format(sub_matches)?;
     let expanded_path = path_exists(path)?;
     let mut vpx_file = vpx::open(&expanded_path)?;
     let collections = vpx_file.read_collections()?;
 
-    let rows: Vec<Vec<String>> = collections
+    let rows: Vec<Vec<Cell>> = collections
         .iter()
         .map(|c| {
             vec![
-                c.name.clone(),
-                c.items.len().to_string(),
-                if c.fire_events { "Y" } else { "N" }.to_string(),
-                if c.stop_single_events { "Y" } else { "N" }.to_string(),
-                if c.group_elements { "Y" } else { "N" }.to_string(),
+                Cell::text(&c.name),
+                Cell::Int(c.items.len() as i64),
+                Cell::flag(c.fire_events),
+                Cell::flag(c.stop_single_events),
+                Cell::flag(c.group_elements),
             ]
         })
         .collect();
 
-    let headers = [
-        "NAME",
-        "ITEMS",
-        "FIRE_EVENTS",
-        "STOP_SINGLES",
-        "GROUP_ELEMENTS",
+    let columns = [
+        Column::left("NAME", "name"),
+        Column::right("ITEMS", "items"),
+        Column::left("FIRE_EVENTS", "fire_events"),
+        Column::left("STOP_SINGLES", "stop_single_events"),
+        Column::left("GROUP_ELEMENTS", "group_elements"),
     ];
-    let aligns = [
-        ColAlign::Left,
-        ColAlign::Right,
-        ColAlign::Left,
-        ColAlign::Left,
-        ColAlign::Left,
-    ];
-    print_aligned_table(&headers, &aligns, &rows)?;
+    print_list(format, &columns, &rows)?;
     Ok(ExitCode::SUCCESS)
 }
 
@@ -2465,32 +4446,34 @@ fn handle_materials_list(sub_matches: &ArgMatches) -> io::Result<ExitCode> {
         .get_one::<String>("VPXPATH")
         .map(|s| s.as_str())
         .unwrap_or_default();
+    let format = list_format(sub_matches)?;
     let expanded_path = path_exists(path)?;
     let mut vpx_file = vpx::open(&expanded_path)?;
     let gamedata = vpx_file.read_gamedata()?;
 
-    // Build a uniform Vec<Vec<String>> from whichever material format is
+    // Go through the shortest f32 representation so the raw formats print
+    // 0.5 rather than the widened 0.5000000298023224.
+    let ratio = |value: f32| Cell::Float {
+        value: value.to_string().parse().unwrap_or(value as f64),
+        precision: 3,
+    };
+    // Build a uniform Vec<Vec<Cell>> from whichever material format is
     // present. 10.8+ uses the MATR storage (`gamedata.materials`); older
     // files use the legacy MATE storage (`gamedata.materials_old`).
-    let rows: Vec<Vec<String>> = if let Some(materials) = gamedata.materials.as_ref() {
+    let rows: Vec<Vec<Cell>> = if let Some(materials) = gamedata.materials.as_ref() {
         materials
             .iter()
             .map(|m| {
                 vec![
-                    m.name.clone(),
-                    format!(
+                    Cell::text(&m.name),
+                    Cell::text(format!(
                         "#{:02X}{:02X}{:02X}",
                         m.base_color.r, m.base_color.g, m.base_color.b
-                    ),
-                    if m.type_ == vpin::vpx::material::MaterialType::Metal {
-                        "Y"
-                    } else {
-                        "N"
-                    }
-                    .to_string(),
-                    format!("{:.3}", m.roughness),
-                    format!("{:.3}", m.opacity),
-                    format!("{:.3}", m.edge),
+                    )),
+                    Cell::flag(m.type_ == vpin::vpx::material::MaterialType::Metal),
+                    ratio(m.roughness),
+                    ratio(m.opacity),
+                    ratio(m.edge),
                 ]
             })
             .collect()
@@ -2500,37 +4483,29 @@ fn handle_materials_list(sub_matches: &ArgMatches) -> io::Result<ExitCode> {
             .iter()
             .map(|m| {
                 vec![
-                    m.name.clone(),
-                    format!(
+                    Cell::text(&m.name),
+                    Cell::text(format!(
                         "#{:02X}{:02X}{:02X}",
                         m.base_color.r, m.base_color.g, m.base_color.b
-                    ),
-                    if m.is_metal { "Y" } else { "N" }.to_string(),
-                    format!("{:.3}", m.roughness),
-                    format!("{:.3}", m.opacity),
-                    format!("{:.3}", m.edge),
+                    )),
+                    Cell::flag(m.is_metal),
+                    ratio(m.roughness),
+                    ratio(m.opacity),
+                    ratio(m.edge),
                 ]
             })
             .collect()
     };
 
-    let headers = [
-        "NAME",
-        "BASE_COLOR",
-        "METAL",
-        "ROUGHNESS",
-        "OPACITY",
-        "EDGE",
-    ];
-    let aligns = [
-        ColAlign::Left,
-        ColAlign::Left,
-        ColAlign::Left,
-        ColAlign::Right,
-        ColAlign::Right,
-        ColAlign::Right,
+    let columns = [
+        Column::left("NAME", "name"),
+        Column::left("BASE_COLOR", "base_color"),
+        Column::left("METAL", "metal"),
+        Column::right("ROUGHNESS", "roughness"),
+        Column::right("OPACITY", "opacity"),
+        Column::right("EDGE", "edge"),
     ];
-    print_aligned_table(&headers, &aligns, &rows)?;
+    print_list(format, &columns, &rows)?;
     Ok(ExitCode::SUCCESS)
 }
 
@@ -2539,69 +4514,49 @@ fn handle_gameitems_list(sub_matches: &ArgMatches) -> io::Result<ExitCode> {
         .get_one::<String>("VPXPATH")
         .map(|s| s.as_str())
         .unwrap_or_default();
+    let format = list_format(sub_matches)?;
     let expanded_path = path_exists(path)?;
     let mut vpx_file = vpx::open(&expanded_path)?;
     let gameitems = vpx_file.read_gameitems()?;
 
-    let rows: Vec<Vec<String>> = gameitems
+    let rows: Vec<Vec<Cell>> = gameitems
         .iter()
         .map(|item| {
-            let visible = match item.is_visible() {
-                Some(true) => "Y",
-                Some(false) => "N",
-                None => "-",
-            };
-            let locked = match item.is_locked() {
-                Some(true) => "Y",
-                Some(false) => "N",
-                None => "-",
-            };
             // Prefer the editor layer name; fall back to the numeric layer if
             // unnamed, empty if neither is set.
             let layer = match item.editor_layer_name() {
-                Some(name) if !name.is_empty() => name.clone(),
+                Some(name) if !name.is_empty() => Cell::text(name),
                 _ => item
                     .editor_layer()
-                    .map(|n| n.to_string())
-                    .unwrap_or_default(),
+                    .map(|n| Cell::text(n.to_string()))
+                    .unwrap_or(Cell::Empty),
             };
             vec![
-                item.name().to_string(),
-                item.type_name(),
-                visible.to_string(),
-                locked.to_string(),
+                Cell::text(item.name()),
+                Cell::text(item.type_name()),
+                Cell::Flag(item.is_visible()),
+                Cell::Flag(item.is_locked()),
                 layer,
-                item.part_group_name().unwrap_or("").to_string(),
-                item.physics_material().unwrap_or("").to_string(),
-                item.images().join("--"),
-                item.materials().join("--"),
+                Cell::non_empty(item.part_group_name().unwrap_or("")),
+                Cell::non_empty(item.physics_material().unwrap_or("")),
+                Cell::List(item.images().into_iter().map(String::from).collect()),
+                Cell::List(item.materials().into_iter().map(String::from).collect()),
             ]
         })
         .collect();
 
-    let headers = [
-        "NAME",
-        "TYPE",
-        "VISIBLE",
-        "LOCKED",
-        "LAYER",
-        "PART_GROUP",
-        "PHYSICS_MATERIAL",
-        "IMAGES",
-        "MATERIALS",
+    let columns = [
+        Column::left("NAME", "name"),
+        Column::left("TYPE", "type"),
+        Column::left("VISIBLE", "visible"),
+        Column::left("LOCKED", "locked"),
+        Column::left("LAYER", "layer"),
+        Column::left("PART_GROUP", "part_group"),
+        Column::left("PHYSICS_MATERIAL", "physics_material"),
+        Column::left("IMAGES", "images"),
+        Column::left("MATERIALS", "materials"),
     ];
-    let aligns = [
-        ColAlign::Left,
-        ColAlign::Left,
-        ColAlign::Left,
-        ColAlign::Left,
-        ColAlign::Left,
-        ColAlign::Left,
-        ColAlign::Left,
-        ColAlign::Left,
-        ColAlign::Left,
-    ];
-    print_aligned_table(&headers, &aligns, &rows)?;
+    print_list(format, &columns, &rows)?;
     Ok(ExitCode::SUCCESS)
 }
 
@@ -2985,31 +4940,70 @@ fn open_configured_editor(file_to_edit: &Path, editor: &String) -> io::Result<()
     }
 }
 
-fn info_import(_vpx_file_path: &Path) -> io::Result<ExitCode> {
-    // let info_file_path = vpx_file_path.with_extension("info.json");
-    // if !info_file_path.exists() {
-    //     let warning = format!("File \"{}\" does not exist", info_file_path.display());
-    //     return Err(io::Error::new(io::ErrorKind::NotFound, warning));
-    // }
-    // let mut info_file = File::open(&info_file_path)?;
-    // let json = serde_json::from_reader(&mut info_file).map_err(|e| {
-    //     io::Error::new(
-    //         io::ErrorKind::Other,
-    //         format!(
-    //             "Failed to parse/read json {}: {}",
-    //             info_file_path.display(),
-    //             e
-    //         ),
-    //     )
-    // })?;
-
-    // let (table_info, custom_info_tags) = json_to_info(json, None)?;
-    // let mut vpx_file = vpx::open(vpx_file_path)?;
-    // vpx_file.write_custominfotags(&custom_info_tags)?;
-    // vpx_file.write_tableinfo(&table_info)?;
-    // println!("Imported table info from {}", info_file_path.display())?;
-    // Ok(ExitCode::SUCCESS)
-    fail("Not yet implemented")
+fn info_import(vpx_file_path: &Path, force: bool) -> io::Result<ExitCode> {
+    let info_file_path = vpx_file_path.with_extension("info.json");
+    if !info_file_path.exists() {
+        return fail(format!(
+            "File \"{}\" does not exist, use `info extract` to create it",
+            info_file_path.display()
+        ));
+    }
+    let info_file = File::open(&info_file_path)?;
+    let json: serde_json::Value = match serde_json::from_reader(BufReader::new(info_file)) {
+        Ok(json) => json,
+        Err(e) => return fail(format!("Failed to parse {}: {e}", info_file_path.display())),
+    };
+
+    let mut vpx = vpx::read(vpx_file_path)?;
+    let (table_info, custom_info_tags) =
+        match crate::info::parse_info_json(json, vpx.info.screenshot.clone()) {
+            Ok(parsed) => parsed,
+            Err(problems) => {
+                crate::eprintln!("{} {}", "error:".red(), info_file_path.display())?;
+                for problem in problems {
+                    crate::eprintln!("  {problem}")?;
+                }
+                return Ok(ExitCode::FAILURE);
+            }
+        };
+
+    let changes = crate::info::diff_info(
+        &vpx.info,
+        &vpx.custominfotags,
+        &table_info,
+        &custom_info_tags,
+    );
+    if changes.is_empty() {
+        crate::println!("No changes to import")?;
+        return Ok(ExitCode::SUCCESS);
+    }
+    for change in &changes {
+        crate::println!("{}", change.field.bold())?;
+        let old = change.old.as_deref().unwrap_or("[not set]");
+        let new = change.new.as_deref().unwrap_or("[not set]");
+        crate::println!("  {}", format!("- {old}").red())?;
+        crate::println!("  {}", format!("+ {new}").green())?;
+    }
+    if !force {
+        let confirmed = confirm(
+            format!("{} field(s) will change", changes.len()),
+            format!(
+                "Do you want to write them to \"{}\"?",
+                vpx_file_path.display()
+            ),
+        )?;
+        if !confirmed {
+            crate::println!("Aborted")?;
+            return Ok(ExitCode::FAILURE);
+        }
+    }
+
+    vpx.info = table_info;
+    vpx.custominfotags = custom_info_tags;
+    let bytes = vpx::to_bytes(&vpx)?;
+    crate::atomicwrite::atomic_write(vpx_file_path, |file| file.write_all(&bytes))?;
+    crate::println!("Imported table info from {}", info_file_path.display())?;
+    Ok(ExitCode::SUCCESS)
 }
 
 pub fn ls(vpx_file_path: &Path) -> io::Result<()> {
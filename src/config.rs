@@ -20,9 +20,18 @@ const CONFIGURATION_FILE_NAME: &str = "vpxtool.cfg";
 pub struct LaunchTemplate {
     pub name: String,
     pub executable: PathBuf,
+    /// May contain placeholders, see [`crate::placeholders`]. When `{table}`
+    /// is used, `-play <table>` is no longer appended.
     pub arguments: Option<Vec<String>>,
+    /// Values may contain placeholders.
     pub env: Option<HashMap<String, String>>,
     pub vpinball_config: Option<PathBuf>,
+    /// Command (program and arguments) run before vpinball, a failure aborts
+    /// the launch. May contain placeholders.
+    pub pre_launch: Option<Vec<String>>,
+    /// Command run after vpinball exited, whatever the exit status. May
+    /// contain placeholders.
+    pub post_launch: Option<Vec<String>>,
 }
 
 #[derive(Deserialize, Serialize)]
@@ -231,6 +240,8 @@ fn generate_default_launch_templates(vpx_executable: &Path) -> Vec<LaunchTemplat
         arguments: None,
         env: Some(default_env),
         vpinball_config: None,
+        pre_launch: None,
+        post_launch: None,
     }]
 }
 
@@ -524,6 +535,8 @@ mod tests {
                         ("SDL_RENDER_DRIVER".to_string(), "".to_string()),
                     ])),
                     vpinball_config: None,
+                    pre_launch: None,
+                    post_launch: None,
                 },),
 
                 vpx_config: default_vpinball_ini_file(&PathBuf::from("/home/me/vpinball")),
@@ -568,6 +581,8 @@ mod tests {
                     arguments: None,
                     env: None,
                     vpinball_config: Some(PathBuf::from("/tmp/test/VPinballX_BGFX.ini")),
+                    pre_launch: None,
+                    post_launch: None,
                 },
                 LaunchTemplate {
                     name: "Launch GL".to_string(),
@@ -575,12 +590,48 @@ mod tests {
                     arguments: None,
                     env: None,
                     vpinball_config: None,
+                    pre_launch: None,
+                    post_launch: None,
                 },
             ]
         );
         Ok(())
     }
 
+    #[test]
+    fn test_read_launch_template_with_hooks() -> io::Result<()> {
+        let temp_dir = testdir!();
+        let config_file = temp_dir.join(CONFIGURATION_FILE_NAME);
+        let mut file = File::create(&config_file)?;
+        file.write_all(
+            b"vpx_executable = \"/tmp/test/vpinball\"\n\
+              \n\
+              [[launch_templates]]\n\
+              name = \"Launch with DOF\"\n\
+              executable = \"/tmp/test/vpinball\"\n\
+              arguments = [\"-Play\", \"{table}\"]\n\
+              pre_launch = [\"/usr/bin/dof-start\", \"{rom}\"]\n\
+              post_launch = [\"/usr/bin/cp\", \"nvram/{rom}.nv\", \"{table_dir}/backup\"]\n",
+        )?;
+
+        let config = read_config(&config_file)?;
+
+        let template = &config.launch_templates[0];
+        assert_eq!(
+            template.pre_launch,
+            Some(vec!["/usr/bin/dof-start".to_string(), "{rom}".to_string()])
+        );
+        assert_eq!(
+            template.post_launch,
+            Some(vec![
+                "/usr/bin/cp".to_string(),
+                "nvram/{rom}.nv".to_string(),
+                "{table_dir}/backup".to_string()
+            ])
+        );
+        Ok(())
+    }
+
     #[test]
     fn test_read_config_with_tables_scan_max_depth() -> io::Result<()> {
         let temp_dir = testdir!();
@@ -620,6 +671,8 @@ mod tests {
                         ("SDL_RENDER_DRIVER".to_string(), "".to_string()),
                     ])),
                     vpinball_config: None,
+                    pre_launch: None,
+                    post_launch: None,
                 },),
                 vpx_config: default_vpinball_ini_file(&PathBuf::from("/tmp/test/vpinball")),
                 tables_folder: PathBuf::from("/tmp/test/tables"),
@@ -657,6 +710,8 @@ mod tests {
                         ("SDL_RENDER_DRIVER".to_string(), "".to_string()),
                     ])),
                     vpinball_config: None,
+                    pre_launch: None,
+                    post_launch: None,
                 }),
                 vpx_config: default_vpinball_ini_file(&PathBuf::from("/tmp/test/vpinball")),
                 tables_folder: expected_tables_dir.clone(),
@@ -700,6 +755,8 @@ mod tests {
                         ("SDL_RENDER_DRIVER".to_string(), "".to_string()),
                     ])),
                     vpinball_config: None,
+                    pre_launch: None,
+                    post_launch: None,
                 })
             }
         );
@@ -0,0 +1,322 @@
+//! `vpxtool doctor`: run every health check we have over the indexed tables
+//! and report all problems found, so a cab image can be checked unattended.
+//!
+//! The checks only read, nothing is fixed. Each problem carries a stable
+//! [`Check`] name so reports can be filtered and diffed between runs.
+
+use crate::capture::{CaptureFormat, capture_image_path};
+use crate::indexer::IndexedTable;
+use crate::scriptfile::read_script;
+use crate::vpinball_config::VPinballConfig;
+use rayon::prelude::*;
+use std::fmt::{Display, Formatter};
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+use std::str::FromStr;
+use vpin::vpx;
+use vpin::vpx::{VerifyResult, ini_path_for, vbs_path_for};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Check {
+    /// The indexed vpx file no longer exists, the index is stale.
+    VpxMissing,
+    /// The vpx file fails `vpxtool verify` (MAC mismatch or unreadable).
+    VerifyFailed,
+    /// The sidecar `.vbs`, which vpinball prefers, differs from the embedded
+    /// script.
+    VbsDiffers,
+    /// The table needs PinMAME but no ROM was found.
+    MissingRom,
+    /// The `.directb2s` backglass can not be parsed.
+    B2sUnreadable,
+    /// The table `.ini` can not be parsed.
+    IniUnreadable,
+    /// No wheel image was found for the table.
+    MissingWheel,
+    /// No playfield capture (`media/table.*`) exists for the table.
+    MissingCapture,
+}
+
+impl Check {
+    pub const VALUES: [&'static str; 8] = [
+        "vpx-missing",
+        "verify-failed",
+        "vbs-differs",
+        "missing-rom",
+        "b2s-unreadable",
+        "ini-unreadable",
+        "missing-wheel",
+        "missing-capture",
+    ];
+}
+
+impl Display for Check {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            Check::VpxMissing => "vpx-missing",
+            Check::VerifyFailed => "verify-failed",
+            Check::VbsDiffers => "vbs-differs",
+            Check::MissingRom => "missing-rom",
+            Check::B2sUnreadable => "b2s-unreadable",
+            Check::IniUnreadable => "ini-unreadable",
+            Check::MissingWheel => "missing-wheel",
+            Check::MissingCapture => "missing-capture",
+        };
+        f.write_str(s)
+    }
+}
+
+impl FromStr for Check {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "vpx-missing" => Ok(Check::VpxMissing),
+            "verify-failed" => Ok(Check::VerifyFailed),
+            "vbs-differs" => Ok(Check::VbsDiffers),
+            "missing-rom" => Ok(Check::MissingRom),
+            "b2s-unreadable" => Ok(Check::B2sUnreadable),
+            "ini-unreadable" => Ok(Check::IniUnreadable),
+            "missing-wheel" => Ok(Check::MissingWheel),
+            "missing-capture" => Ok(Check::MissingCapture),
+            other => Err(format!(
+                "Unknown check '{other}', expected one of {}",
+                Self::VALUES.join(", ")
+            )),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Problem {
+    pub check: Check,
+    pub message: String,
+}
+
+impl Problem {
+    fn new(check: Check, message: impl Into<String>) -> Self {
+        Problem {
+            check,
+            message: message.into(),
+        }
+    }
+}
+
+/// Run all checks not in `skip` for every table, in parallel. Returns the
+/// tables that have problems, in the order given.
+pub fn check_tables<'a>(
+    tables: &'a [IndexedTable],
+    skip: &[Check],
+) -> Vec<(&'a IndexedTable, Vec<Problem>)> {
+    tables
+        .par_iter()
+        .map(|table| (table, check_table(table, skip)))
+        .filter(|(_, problems)| !problems.is_empty())
+        .collect()
+}
+
+/// Run all checks not in `skip` for a single table.
+pub fn check_table(table: &IndexedTable, skip: &[Check]) -> Vec<Problem> {
+    let enabled = |check: Check| !skip.contains(&check);
+    let mut problems = Vec::new();
+    if !table.path.exists() {
+        if enabled(Check::VpxMissing) {
+            problems.push(Problem::new(
+                Check::VpxMissing,
+                "file no longer exists, re-run `vpxtool index`",
+            ));
+        }
+        return problems;
+    }
+
+    if enabled(Check::VerifyFailed)
+        && let VerifyResult::Failed(_, message) = vpx::verify(&table.path)
+    {
+        problems.push(Problem::new(Check::VerifyFailed, message));
+    }
+    if enabled(Check::VbsDiffers)
+        && let Some(problem) = check_sidecar_vbs(&table.path)
+    {
+        problems.push(problem);
+    }
+    if enabled(Check::MissingRom) && table.requires_pinmame && table.rom_path().is_none() {
+        let message = match &table.game_name {
+            Some(game_name) => format!("no rom found for {game_name}"),
+            None => "table requires PinMAME but the rom name could not be found in the script"
+                .to_string(),
+        };
+        problems.push(Problem::new(Check::MissingRom, message));
+    }
+    if enabled(Check::B2sUnreadable)
+        && let Some(b2s_path) = &table.b2s_path
+        && let Err(e) = read_b2s(b2s_path)
+    {
+        problems.push(Problem::new(
+            Check::B2sUnreadable,
+            format!("{}: {e}", b2s_path.display()),
+        ));
+    }
+    let ini_path = ini_path_for(&table.path);
+    if enabled(Check::IniUnreadable)
+        && ini_path.exists()
+        && let Err(e) = VPinballConfig::read(&ini_path)
+    {
+        problems.push(Problem::new(
+            Check::IniUnreadable,
+            format!("{}: {e}", ini_path.display()),
+        ));
+    }
+    if enabled(Check::MissingWheel) && table.wheel_path.is_none() {
+        problems.push(Problem::new(Check::MissingWheel, "no wheel image found"));
+    }
+    if enabled(Check::MissingCapture)
+        && !CaptureFormat::ALL
+            .iter()
+            .any(|format| capture_image_path(&table.path, *format).exists())
+    {
+        problems.push(Problem::new(
+            Check::MissingCapture,
+            "no playfield capture found, run `vpxtool capture`",
+        ));
+    }
+    problems
+}
+
+fn read_b2s(b2s_path: &Path) -> Result<(), String> {
+    let file = File::open(b2s_path).map_err(|e| e.to_string())?;
+    directb2s::read(BufReader::new(file))
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+/// vpinball loads a `.vbs` next to the table instead of the embedded script,
+/// a leftover from patching silently changes the table.
+fn check_sidecar_vbs(vpx_path: &Path) -> Option<Problem> {
+    let vbs_path = vbs_path_for(vpx_path);
+    if !vbs_path.exists() {
+        return None;
+    }
+    let sidecar = match read_script(&vbs_path) {
+        Ok((text, _)) => text,
+        Err(e) => {
+            return Some(Problem::new(
+                Check::VbsDiffers,
+                format!("{}: {e}", vbs_path.display()),
+            ));
+        }
+    };
+    // an unreadable vpx is reported by verify
+    let embedded = vpx::open(vpx_path)
+        .and_then(|mut vpx_file| vpx_file.read_gamedata())
+        .ok()?
+        .code
+        .string;
+    // line endings change when scripts pass through git or an editor
+    if sidecar.replace("\r\n", "\n") != embedded.replace("\r\n", "\n") {
+        Some(Problem::new(
+            Check::VbsDiffers,
+            format!(
+                "{} differs from the embedded script, see `vpxtool script diff`",
+                vbs_path.display()
+            ),
+        ))
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::indexer::tests::indexed_table;
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+    use std::path::PathBuf;
+    use testdir::testdir;
+    use vpin::vpx::{ExtractResult, extractvbs};
+
+    fn checks(problems: &[Problem]) -> Vec<Check> {
+        problems.iter().map(|p| p.check).collect()
+    }
+
+    fn blank_table(dir: &Path) -> PathBuf {
+        let vpx_path = dir.join("blank.vpx");
+        std::fs::copy("testdata/completely_blank_table_10_7_4.vpx", &vpx_path).unwrap();
+        vpx_path
+    }
+
+    #[test]
+    fn reports_missing_vpx_only() {
+        let dir = testdir!();
+        let table = indexed_table(dir.join("gone.vpx"), json!({"requires_pinmame": true}));
+        let problems = check_table(&table, &[]);
+        assert_eq!(checks(&problems), vec![Check::VpxMissing]);
+    }
+
+    #[test]
+    fn healthy_table_has_no_problems() {
+        let dir = testdir!();
+        let vpx_path = blank_table(&dir);
+        let capture = capture_image_path(&vpx_path, CaptureFormat::Png);
+        std::fs::create_dir_all(capture.parent().unwrap()).unwrap();
+        std::fs::write(&capture, b"png").unwrap();
+        let ExtractResult::Extracted(_) = extractvbs(&vpx_path, None, false).unwrap() else {
+            panic!("vbs should not exist yet");
+        };
+        let table = indexed_table(&vpx_path, json!({"wheel_path": dir.join("wheel.png")}));
+        assert_eq!(check_table(&table, &[]), vec![]);
+    }
+
+    #[test]
+    fn reports_every_problem() {
+        let dir = testdir!();
+        let vpx_path = blank_table(&dir);
+        std::fs::write(vbs_path_for(&vpx_path), "' patched\n").unwrap();
+        std::fs::write(ini_path_for(&vpx_path), "[Player\nBroken").unwrap();
+        let b2s_path = dir.join("blank.directb2s");
+        std::fs::write(&b2s_path, "not xml").unwrap();
+        let table = indexed_table(
+            &vpx_path,
+            json!({"requires_pinmame": true, "game_name": "tz_94h", "b2s_path": b2s_path}),
+        );
+        let problems = check_table(&table, &[]);
+        assert_eq!(
+            checks(&problems),
+            vec![
+                Check::VbsDiffers,
+                Check::MissingRom,
+                Check::B2sUnreadable,
+                Check::IniUnreadable,
+                Check::MissingWheel,
+                Check::MissingCapture,
+            ]
+        );
+        assert_eq!(problems[1].message, "no rom found for tz_94h");
+
+        let skipped = check_table(&table, &[Check::MissingWheel, Check::MissingCapture]);
+        assert_eq!(skipped.len(), 4);
+    }
+
+    #[test]
+    fn decodes_sidecar_vbs_like_script_patch() {
+        let dir = testdir!();
+        let vpx_path = blank_table(&dir);
+        let code = vpx::read(&vpx_path).unwrap().gamedata.code.string;
+        let bytes =
+            crate::scriptfile::encode_script(&code, crate::scriptfile::ScriptEncoding::Utf16Le)
+                .unwrap();
+        std::fs::write(vbs_path_for(&vpx_path), bytes).unwrap();
+        assert_eq!(check_sidecar_vbs(&vpx_path), None);
+    }
+
+    #[test]
+    fn reports_corrupt_vpx() {
+        let dir = testdir!();
+        let vpx_path = dir.join("corrupt.vpx");
+        std::fs::write(&vpx_path, b"not a vpx").unwrap();
+        let table = indexed_table(&vpx_path, json!({}));
+        let problems = check_table(&table, &[Check::MissingWheel, Check::MissingCapture]);
+        assert_eq!(checks(&problems), vec![Check::VerifyFailed]);
+    }
+}
@@ -1,17 +1,27 @@
 use crate::backglass::find_hole;
-use crate::capture::{CaptureOptions, CaptureOutcome, capture_table};
+use crate::capture::{CaptureOptions, CaptureOutcome, capture_table, display_paths};
 use crate::cli::{
-    DiffColor, ProgressBarProgress, confirm, info_diff, info_edit, info_gather, open_editor,
-    run_diff, script_diff,
+    DiffColor, ProgressBarProgress, confirm, find_score_sections, info_diff, info_edit,
+    info_gather, open_editor, pretty_score_rows, run_diff, script_diff,
 };
 use crate::colorful_theme_patched::ColorfulThemePatched;
 use crate::config::{LaunchTemplate, ResolvedConfig};
+use crate::history::PlayRecord;
 use crate::indexer::{IndexError, IndexedTable, Progress};
+use crate::listing::{ColAlign, print_aligned_table};
 use crate::patcher::LineEndingsResult::{NoChanges, Unified};
-use crate::patcher::{patch_vbs_file, unify_line_endings_vbs_file};
+use crate::patcher::{
+    default_patch_rules_dir, load_patch_rules, patch_vbs_file, unify_line_endings_vbs_file,
+};
+use crate::placeholders::LaunchContext;
+use crate::tableview::{ListFilter, SortOrder};
 use crate::vpinball_config::{VPinballConfig, WindowInfo, WindowType};
-use crate::{describe_exit, indexer, strip_cr_lf, was_killed_by_signal};
+use crate::{
+    describe_exit, history, indexer, placeholders, scores, strip_cr_lf, tableview,
+    was_killed_by_signal,
+};
 use base64::Engine;
+use chrono::{DateTime, Local};
 use colored::Colorize;
 use console::{Emoji, Term};
 use dialoguer::theme::ColorfulTheme;
@@ -28,6 +38,7 @@ use std::{
     io::Write,
     path::{Path, PathBuf},
     process::{ExitStatus, exit},
+    time::{Duration, Instant, SystemTime},
 };
 use vpin::vpx::{ExtractResult, extractvbs, ini_path_for, vbs_path_for};
 
@@ -36,8 +47,18 @@ const CRASH: Emoji = Emoji("💥", "[crash]");
 
 const SEARCH: &str = "> Search";
 const RECENT: &str = "> Recent";
+const RECENTLY_PLAYED: &str = "> Recently played";
+const MOST_PLAYED: &str = "> Most played";
+const NEVER_PLAYED: &str = "> Never played";
 const SEARCH_INDEX: usize = 0;
 const RECENT_INDEX: usize = 1;
+const RECENTLY_PLAYED_INDEX: usize = 2;
+const MOST_PLAYED_INDEX: usize = 3;
+const NEVER_PLAYED_INDEX: usize = 4;
+const SORT_INDEX: usize = 5;
+const FILTER_INDEX: usize = 6;
+/// Number of view entries above the table list in the main menu.
+const TABLES_OFFSET: usize = 7;
 
 #[derive(PartialEq, Eq, Clone)]
 enum TableOption {
@@ -52,6 +73,7 @@ enum TableOption {
     UnifyLineEndings,
     ShowVBSDiff,
     CreateVBSPatch,
+    ScoresShow,
     NVRAMDipSwitches,
     NVRAMShow,
     NVRAMClear,
@@ -83,6 +105,7 @@ impl TableOption {
             TableOption::UnifyLineEndings,
             TableOption::ShowVBSDiff,
             TableOption::CreateVBSPatch,
+            TableOption::ScoresShow,
             TableOption::NVRAMDipSwitches,
             TableOption::NVRAMShow,
             TableOption::NVRAMClear,
@@ -111,6 +134,7 @@ impl TableOption {
             TableOption::ShowVBSDiff => "VBScript > Diff".to_string(),
             TableOption::CreateVBSPatch => "VBScript > Create patch file".to_string(),
             TableOption::NVRAMDipSwitches => "NVRAM > DIP Switches".to_string(),
+            TableOption::ScoresShow => "Scores > Show".to_string(),
             TableOption::NVRAMShow => "NVRAM > Show".to_string(),
             TableOption::NVRAMClear => "NVRAM > Clear".to_string(),
             TableOption::B2SAutoPositionDMD => "Backglass > Auto-position DMD".to_string(),
@@ -162,13 +186,35 @@ pub fn frontend(
     mut vpx_files_with_tableinfo: Vec<IndexedTable>,
 ) {
     let mut main_selection_opt = None;
+    let mut sort_order = SortOrder::default();
+    let mut filters: Vec<ListFilter> = Vec::new();
     loop {
-        let tables: Vec<String> = vpx_files_with_tableinfo
-            .iter()
-            .map(display_table_line_full)
-            .collect();
+        let visible: Vec<IndexedTable> =
+            tableview::table_view(&vpx_files_with_tableinfo, sort_order, &filters)
+                .into_iter()
+                .cloned()
+                .collect();
+        let tables: Vec<String> = visible.iter().map(display_table_line_full).collect();
+        let filter_label = if filters.is_empty() {
+            "> Filter: none".to_string()
+        } else {
+            format!(
+                "> Filter: {} active, {} of {} tables",
+                filters.len(),
+                visible.len(),
+                vpx_files_with_tableinfo.len()
+            )
+        };
 
-        let mut selections = vec![SEARCH.bold().to_string(), RECENT.bold().to_string()];
+        let mut selections = vec![
+            SEARCH.bold().to_string(),
+            RECENT.bold().to_string(),
+            RECENTLY_PLAYED.bold().to_string(),
+            MOST_PLAYED.bold().to_string(),
+            NEVER_PLAYED.bold().to_string(),
+            format!("> Sort: {sort_order}").bold().to_string(),
+            filter_label.bold().to_string(),
+        ];
         selections.extend(tables.clone());
 
         if let Err(e) = Term::stderr().clear_screen() {
@@ -198,11 +244,8 @@ pub fn frontend(
 
                         if let Some(selected_index) = selected {
                             // return to the main list with the table selected
-                            main_selection_opt = Some(selected_index + 2);
-                            let info = vpx_files_with_tableinfo
-                                .get(selected_index)
-                                .unwrap()
-                                .clone();
+                            main_selection_opt = Some(selected_index + TABLES_OFFSET);
+                            let info = visible.get(selected_index).unwrap().clone();
                             let info_str = display_table_line_full(&info);
                             table_menu(
                                 config,
@@ -217,41 +260,72 @@ pub fn frontend(
                         // take the last 50 most recently modified tables
                         let mut recent: Vec<IndexedTable> = vpx_files_with_tableinfo.clone();
                         recent.sort_by_key(|indexed| indexed.last_modified);
-                        let last_modified = recent.iter().rev().take(50).collect::<Vec<_>>();
-                        let last_modified_str: Vec<String> = last_modified
+                        let last_modified: Vec<IndexedTable> =
+                            recent.into_iter().rev().take(50).collect();
+                        let last_modified_str: Vec<String> =
+                            last_modified.iter().map(display_table_line_full).collect();
+                        tables_submenu(
+                            config,
+                            configured_pinmame_folder,
+                            &mut vpx_files_with_tableinfo,
+                            &last_modified,
+                            &last_modified_str,
+                        );
+                    }
+                    RECENTLY_PLAYED_INDEX | MOST_PLAYED_INDEX | NEVER_PLAYED_INDEX => {
+                        let stats = history::stats_by_table(&read_play_history());
+                        let played = match selection {
+                            RECENTLY_PLAYED_INDEX => {
+                                history::recently_played(&vpx_files_with_tableinfo, &stats)
+                            }
+                            MOST_PLAYED_INDEX => {
+                                history::most_played(&vpx_files_with_tableinfo, &stats)
+                            }
+                            _ => history::never_played(&vpx_files_with_tableinfo, &stats),
+                        };
+                        let played: Vec<IndexedTable> = played.into_iter().cloned().collect();
+                        if played.is_empty() {
+                            prompt("No tables in this view yet.");
+                            continue;
+                        }
+                        let played_str: Vec<String> = played
                             .iter()
-                            .map(|indexed| display_table_line_full(indexed))
+                            .map(|indexed| match stats.get(&indexed.path) {
+                                Some(table_stats) => format!(
+                                    "{} {}",
+                                    display_table_line_full(indexed),
+                                    format!(
+                                        "({}x, {})",
+                                        table_stats.launches,
+                                        history::format_play_time(table_stats.total)
+                                    )
+                                    .dimmed()
+                                ),
+                                None => display_table_line_full(indexed),
+                            })
                             .collect();
-
-                        let mut recent_selection: Option<usize> = None;
-                        loop {
-                            let selected = Select::with_theme(&ColorfulTheme::default())
-                                .with_prompt("Select a table")
-                                .items(&last_modified_str)
-                                .default(recent_selection.unwrap_or(0))
-                                .interact_opt()
-                                .unwrap();
-
-                            if let Some(selected_index) = selected {
-                                recent_selection = Some(selected_index);
-                                let info = last_modified.get(selected_index).unwrap();
-                                let info_str = display_table_line_full(info);
-                                table_menu(
-                                    config,
-                                    configured_pinmame_folder,
-                                    &mut vpx_files_with_tableinfo,
-                                    info,
-                                    &info_str,
-                                );
-                            } else {
-                                break;
-                            }
+                        tables_submenu(
+                            config,
+                            configured_pinmame_folder,
+                            &mut vpx_files_with_tableinfo,
+                            &played,
+                            &played_str,
+                        );
+                    }
+                    SORT_INDEX => {
+                        if let Some(selected) = choose_sort_order(sort_order) {
+                            sort_order = selected;
+                        }
+                    }
+                    FILTER_INDEX => {
+                        if let Some(selected) = choose_filters(&filters) {
+                            filters = selected;
                         }
                     }
                     _ => {
-                        let index = selection - 2;
+                        let index = selection - TABLES_OFFSET;
 
-                        let info = vpx_files_with_tableinfo.get(index).unwrap().clone();
+                        let info = visible.get(index).unwrap().clone();
                         let info_str = display_table_line_full(&info);
                         table_menu(
                             config,
@@ -268,6 +342,74 @@ pub fn frontend(
     }
 }
 
+fn choose_sort_order(current: SortOrder) -> Option<SortOrder> {
+    let default = SortOrder::ALL
+        .iter()
+        .position(|order| *order == current)
+        .unwrap_or(0);
+    let items: Vec<String> = SortOrder::ALL
+        .iter()
+        .map(|order| format!("Sort by {order}"))
+        .collect();
+    Select::with_theme(&ColorfulTheme::default())
+        .with_prompt("Sort tables")
+        .default(default)
+        .items(&items)
+        .interact_opt()
+        .unwrap()
+        .map(|index| SortOrder::ALL[index])
+}
+
+/// Toggle the list filters, returns `None` when cancelled.
+fn choose_filters(current: &[ListFilter]) -> Option<Vec<ListFilter>> {
+    let items: Vec<String> = ListFilter::ALL.iter().map(|f| f.to_string()).collect();
+    let checked: Vec<bool> = ListFilter::ALL
+        .iter()
+        .map(|f| current.contains(f))
+        .collect();
+    MultiSelect::with_theme(&ColorfulTheme::default())
+        .with_prompt("Only show tables that match all of (space to toggle)")
+        .items(&items)
+        .defaults(&checked)
+        .interact_opt()
+        .unwrap()
+        .map(|indexes| indexes.into_iter().map(|i| ListFilter::ALL[i]).collect())
+}
+
+/// A list of tables, each opening the table menu, until the user backs out.
+fn tables_submenu(
+    config: &ResolvedConfig,
+    configured_pinmame_folder: Option<&Path>,
+    vpx_files_with_tableinfo: &mut Vec<IndexedTable>,
+    tables: &[IndexedTable],
+    tables_str: &[String],
+) {
+    let mut selection: Option<usize> = None;
+    loop {
+        let selected = Select::with_theme(&ColorfulTheme::default())
+            .with_prompt("Select a table")
+            .items(tables_str)
+            .default(selection.unwrap_or(0))
+            .interact_opt()
+            .unwrap();
+
+        if let Some(selected_index) = selected {
+            selection = Some(selected_index);
+            let info = tables.get(selected_index).unwrap();
+            let info_str = display_table_line_full(info);
+            table_menu(
+                config,
+                configured_pinmame_folder,
+                vpx_files_with_tableinfo,
+                info,
+                &info_str,
+            );
+        } else {
+            break;
+        }
+    }
+}
+
 fn table_menu(
     config: &ResolvedConfig,
     configured_pinmame_folder: Option<&Path>,
@@ -276,13 +418,22 @@ fn table_menu(
     info_str: &str,
 ) {
     let selected_path = &info.path;
+    // resolving scores reads the vpx script, only do it once
+    let score_summary = score_summary(selected_path);
     let mut exit = false;
     let mut option = None;
     while !exit {
-        option = choose_table_option(config, info_str, option);
+        let mut menu_prompt = info_str.to_string();
+        for summary in [play_summary(selected_path), score_summary.clone()]
+            .into_iter()
+            .flatten()
+        {
+            menu_prompt.push_str(&format!("\n  {}", summary.dimmed()));
+        }
+        option = choose_table_option(config, &menu_prompt, option);
         match option {
             Some(TableOption::Launch { ref template }) => {
-                launch(selected_path, template);
+                launch(info, template);
                 exit = true;
             }
             Some(TableOption::ForceReload) => {
@@ -340,6 +491,13 @@ fn table_menu(
                 }
             },
             Some(TableOption::PatchVBS) => {
+                let rules = match load_patch_rules(&default_patch_rules_dir()) {
+                    Ok(rules) => rules,
+                    Err(err) => {
+                        prompt(&err.to_string().truecolor(255, 125, 0).to_string());
+                        return;
+                    }
+                };
                 let vbs_path = match extractvbs(selected_path, None, false) {
                     Ok(ExtractResult::Existed(path)) => path,
                     Ok(ExtractResult::Extracted(path)) => path,
@@ -349,7 +507,7 @@ fn table_menu(
                         return;
                     }
                 };
-                match patch_vbs_file(&vbs_path) {
+                match patch_vbs_file(&vbs_path, &rules) {
                     Ok(applied) => {
                         if applied.is_empty() {
                             prompt("No patches applied.");
@@ -451,6 +609,9 @@ fn table_menu(
             Some(TableOption::NVRAMDipSwitches) => {
                 nvram_dip_switches(info);
             }
+            Some(TableOption::ScoresShow) => {
+                scores_show(info);
+            }
             Some(TableOption::NVRAMShow) => {
                 nvram_show(info);
             }
@@ -506,16 +667,17 @@ fn table_menu(
                     ..CaptureOptions::default()
                 };
                 match capture_table(config, selected_path, &options) {
-                    Ok(CaptureOutcome::Captured(path)) => {
-                        prompt(&format!("Captured screenshot to {}", path.display()))
+                    Ok(CaptureOutcome::Captured(paths)) => {
+                        prompt(&format!("Captured screenshot to {}", display_paths(&paths)))
                     }
-                    Ok(CaptureOutcome::CapturedAfterHang(path)) => prompt(&format!(
+                    Ok(CaptureOutcome::CapturedAfterHang(paths)) => prompt(&format!(
                         "vpinball hung and was killed, but the screenshot was salvaged to {}",
-                        path.display()
+                        display_paths(&paths)
+                    )),
+                    Ok(CaptureOutcome::Skipped(paths)) => prompt(&format!(
+                        "Screenshot already exists at {}",
+                        display_paths(&paths)
                     )),
-                    Ok(CaptureOutcome::Skipped(path)) => {
-                        prompt(&format!("Screenshot already exists at {}", path.display()))
-                    }
                     Err(err) => prompt_error(&format!("Unable to capture screenshot: {err}")),
                 }
             }
@@ -546,6 +708,43 @@ fn nvram_dip_switches(info: &IndexedTable) {
     }
 }
 
+/// The top score for the table menu header, e.g.
+/// `Grand Champion: ABC 52,000,000`. Quietly `None` when no scores are found.
+fn score_summary(selected_path: &Path) -> Option<String> {
+    let sections = find_score_sections(selected_path).ok()?.ok()?;
+    let rows = pretty_score_rows(&sections);
+    let top = rows.first()?;
+    let (label, initials, score) = (
+        &top[scores::COL_LABEL],
+        &top[scores::COL_INITIALS],
+        &top[scores::COL_SCORE],
+    );
+    if initials.is_empty() {
+        Some(format!("{label}: {score}"))
+    } else {
+        Some(format!("{label}: {initials} {score}"))
+    }
+}
+
+fn scores_show(info: &IndexedTable) {
+    match find_score_sections(&info.path) {
+        Ok(Ok(sections)) => {
+            let rows = pretty_score_rows(&sections);
+            let result = print_aligned_table(
+                &["LABEL", "INITIALS", "SCORE"],
+                &[ColAlign::Left, ColAlign::Left, ColAlign::Right],
+                &rows,
+            );
+            match result {
+                Ok(()) => prompt("High scores"),
+                Err(e) => prompt_error(&format!("Unable to show scores: {e}")),
+            }
+        }
+        Ok(Err(msg)) => prompt(&msg),
+        Err(e) => prompt_error(&format!("Unable to read scores: {e}")),
+    }
+}
+
 fn nvram_show(info: &IndexedTable) {
     if info.requires_pinmame {
         if let Some(nvram_path) = nvram_for_rom(info) {
@@ -817,6 +1016,8 @@ fn export_vpxz(
             exclude_globs: &config.vpxz_excludes,
             rom_zip: rom_zip.as_deref(),
             progress: Some(&progress),
+            optimize: None,
+            dry_run: false,
         },
     )?;
 
@@ -913,7 +1114,8 @@ fn choose_table_option(
     selection_opt.and_then(|index| all_options.get(index).cloned())
 }
 
-fn launch(selected_path: &PathBuf, launch_template: &LaunchTemplate) {
+fn launch(info: &IndexedTable, launch_template: &LaunchTemplate) {
+    let selected_path = &info.path;
     println!("{} {}", LAUNCH, selected_path.display());
 
     let vpinball_executable = &launch_template.executable;
@@ -925,7 +1127,42 @@ fn launch(selected_path: &PathBuf, launch_template: &LaunchTemplate) {
         ));
     }
 
-    match launch_table(selected_path, launch_template) {
+    let context = LaunchContext::for_table(info);
+    let (cmd, env) = match launch_command(selected_path, launch_template, &context) {
+        Ok(cmd_and_env) => cmd_and_env,
+        Err(msg) => {
+            prompt_error(&format!(
+                "Unable to launch table, template \"{}\": {msg}",
+                launch_template.name
+            ));
+            return;
+        }
+    };
+    if let Some(pre_launch) = &launch_template.pre_launch
+        && let Err(msg) = run_hook("pre_launch", pre_launch, &context, &env)
+    {
+        prompt_error(&format!("{CRASH} {msg}, not launching the table"));
+        return;
+    }
+
+    let started = SystemTime::now();
+    let timer = Instant::now();
+    let result = launch_table(cmd);
+    if let Ok(status) = &result {
+        record_play(
+            selected_path,
+            launch_template,
+            started,
+            timer.elapsed(),
+            *status,
+        );
+    }
+    if let Some(post_launch) = &launch_template.post_launch
+        && let Err(msg) = run_hook("post_launch", post_launch, &context, &env)
+    {
+        prompt_error(&format!("{CRASH} {msg}"));
+    }
+    match result {
         Ok(status) if status.success() => {
             Term::stderr().clear_screen().ok();
         }
@@ -954,31 +1191,122 @@ fn launch(selected_path: &PathBuf, launch_template: &LaunchTemplate) {
     }
 }
 
+/// Run a `pre_launch`/`post_launch` hook with the template environment.
+fn run_hook(
+    kind: &str,
+    command: &[String],
+    context: &LaunchContext,
+    env: &[(String, String)],
+) -> Result<(), String> {
+    let command = context
+        .substitute_all(command)
+        .map_err(|msg| format!("Invalid {kind} hook: {msg}"))?;
+    let Some((program, args)) = command.split_first() else {
+        return Err(format!("Invalid {kind} hook: the command is empty"));
+    };
+    println!("Running {kind} hook: {}", command.join(" "));
+    let status = std::process::Command::new(program)
+        .args(args)
+        .envs(env.iter().map(|(k, v)| (k, v)))
+        .status()
+        .map_err(|e| format!("Unable to run {kind} hook {program}: {e}"))?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(format!("The {kind} hook {}", describe_exit(status)))
+    }
+}
+
+fn read_play_history() -> Vec<PlayRecord> {
+    let history_path = history::default_history_path();
+    history::read_records(&history_path).unwrap_or_else(|e| {
+        eprintln!(
+            "Failed to read play history {}: {e}",
+            history_path.display()
+        );
+        Vec::new()
+    })
+}
+
+/// A failure to record is not worth interrupting the session for.
+fn record_play(
+    selected_path: &Path,
+    launch_template: &LaunchTemplate,
+    started: SystemTime,
+    duration: Duration,
+    status: ExitStatus,
+) {
+    let record = PlayRecord::new(
+        selected_path,
+        &launch_template.name,
+        started,
+        duration,
+        status,
+    );
+    let history_path = history::default_history_path();
+    if let Err(e) = history::append_record(&history_path, &record) {
+        eprintln!("Failed to record play in {}: {e}", history_path.display());
+    }
+}
+
+/// e.g. `Played 3 times, 1h 05m in total, last on 2024-06-01 21:14`
+fn play_summary(selected_path: &Path) -> Option<String> {
+    let stats = history::stats_by_table(&read_play_history());
+    let table_stats = stats.get(selected_path)?;
+    let times = if table_stats.launches == 1 {
+        "once".to_string()
+    } else {
+        format!("{} times", table_stats.launches)
+    };
+    let mut summary = format!(
+        "Played {times}, {} in total",
+        history::format_play_time(table_stats.total)
+    );
+    if let Some(last_played) = table_stats.last_played {
+        let last_played: DateTime<Local> = last_played.into();
+        summary.push_str(&format!(
+            ", last on {}",
+            last_played.format("%Y-%m-%d %H:%M")
+        ));
+    }
+    Some(summary)
+}
+
 fn report_and_exit(msg: String) -> ! {
     eprintln!("{CRASH} {msg}");
     exit(1);
 }
 
-fn launch_table(
-    selected_path: &PathBuf,
+/// The vpinball command for a template, with the placeholders substituted,
+/// and the substituted template environment, which the hooks share.
+fn launch_command(
+    selected_path: &Path,
     launch_template: &LaunchTemplate,
-) -> io::Result<ExitStatus> {
+    context: &LaunchContext,
+) -> Result<(std::process::Command, Vec<(String, String)>), String> {
     let mut cmd = std::process::Command::new(&launch_template.executable);
-    if let Some(env) = &launch_template.env {
-        for (key, value) in env.iter() {
-            cmd.env(key, value);
+    let mut env = Vec::new();
+    if let Some(template_env) = &launch_template.env {
+        for (key, value) in template_env.iter() {
+            env.push((key.clone(), context.substitute(value)?));
         }
     }
+    cmd.envs(env.iter().map(|(k, v)| (k, v)));
     if let Some(vpinball_config) = &launch_template.vpinball_config {
         cmd.arg("-Ini");
         cmd.arg(vpinball_config);
     }
-    if let Some(args) = &launch_template.arguments {
-        cmd.args(args);
+    let arguments = launch_template.arguments.as_deref().unwrap_or_default();
+    cmd.args(context.substitute_all(arguments)?);
+    // the template positions the table itself
+    if !placeholders::mentions_table(arguments) {
+        cmd.arg("-play");
+        cmd.arg(selected_path);
     }
-    cmd.arg("-play");
-    cmd.arg(selected_path);
+    Ok((cmd, env))
+}
 
+fn launch_table(mut cmd: std::process::Command) -> io::Result<ExitStatus> {
     println!("Spawning command: {cmd:?}");
 
     let mut child = cmd.spawn()?;
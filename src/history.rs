@@ -0,0 +1,273 @@
+//! Play history for the frontend: every launch is appended as one json line
+//! to a per-user file, so "what actually gets played" survives re-indexing
+//! and moving tables between folders does not lose more than their history.
+//!
+//! JSON lines keep recording a launch a cheap append, and a line that was
+//! cut short (power loss on a cab is not unusual) only loses that launch.
+
+use crate::indexer::{IndexedTable, IsoSystemTime};
+use log::warn;
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitStatus;
+use std::time::{Duration, SystemTime};
+
+const HISTORY_FILE_NAME: &str = "play_history.jsonl";
+
+/// A single table launch.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct PlayRecord {
+    pub table: PathBuf,
+    /// Name of the launch template used.
+    pub template: String,
+    pub started: IsoSystemTime,
+    pub duration_secs: u64,
+    /// `None` when vpinball was killed by a signal.
+    pub exit_code: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signal: Option<i32>,
+}
+
+impl PlayRecord {
+    pub fn new(
+        table: &Path,
+        template: &str,
+        started: SystemTime,
+        duration: Duration,
+        status: ExitStatus,
+    ) -> Self {
+        #[cfg(unix)]
+        let signal = {
+            use std::os::unix::process::ExitStatusExt;
+            status.signal()
+        };
+        #[cfg(not(unix))]
+        let signal = None;
+        PlayRecord {
+            table: table.to_path_buf(),
+            template: template.to_string(),
+            started: started.into(),
+            duration_secs: duration.as_secs(),
+            exit_code: status.code(),
+            signal,
+        }
+    }
+}
+
+/// Aggregated history of one table.
+#[derive(Debug, Default, PartialEq, Clone)]
+pub struct PlayStats {
+    pub launches: u32,
+    pub total: Duration,
+    pub last_played: Option<SystemTime>,
+}
+
+/// `<config dir>/vpxtool/play_history.jsonl`, next to the config file.
+pub fn default_history_path() -> PathBuf {
+    dirs::config_dir()
+        .unwrap()
+        .join("vpxtool")
+        .join(HISTORY_FILE_NAME)
+}
+
+pub fn append_record(history_path: &Path, record: &PlayRecord) -> io::Result<()> {
+    if let Some(parent) = history_path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let mut line = serde_json::to_string(record)?;
+    line.push('\n');
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(history_path)?;
+    // a single write so concurrent appends don't interleave
+    file.write_all(line.as_bytes())
+}
+
+/// All records in the history file, oldest first. A missing file is an empty
+/// history, unreadable lines are skipped.
+pub fn read_records(history_path: &Path) -> io::Result<Vec<PlayRecord>> {
+    if !history_path.exists() {
+        return Ok(Vec::new());
+    }
+    let reader = BufReader::new(File::open(history_path)?);
+    let mut records = Vec::new();
+    for (index, line) in reader.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str(&line) {
+            Ok(record) => records.push(record),
+            Err(e) => warn!(
+                "Skipping line {} of {}: {e}",
+                index + 1,
+                history_path.display()
+            ),
+        }
+    }
+    Ok(records)
+}
+
+pub fn stats_by_table(records: &[PlayRecord]) -> HashMap<PathBuf, PlayStats> {
+    let mut stats: HashMap<PathBuf, PlayStats> = HashMap::new();
+    for record in records {
+        let entry = stats.entry(record.table.clone()).or_default();
+        entry.launches += 1;
+        entry.total += Duration::from_secs(record.duration_secs);
+        let started: SystemTime = record.started.into();
+        if entry.last_played.is_none_or(|last| started > last) {
+            entry.last_played = Some(started);
+        }
+    }
+    stats
+}
+
+/// Played tables, most recently played first.
+pub fn recently_played<'a>(
+    tables: &'a [IndexedTable],
+    stats: &HashMap<PathBuf, PlayStats>,
+) -> Vec<&'a IndexedTable> {
+    let mut played: Vec<(&IndexedTable, SystemTime)> = tables
+        .iter()
+        .filter_map(|t| Some((t, stats.get(&t.path)?.last_played?)))
+        .collect();
+    played.sort_by_key(|(_, last_played)| Reverse(*last_played));
+    played.into_iter().map(|(t, _)| t).collect()
+}
+
+/// Played tables, most launched first, ties broken by total play time.
+pub fn most_played<'a>(
+    tables: &'a [IndexedTable],
+    stats: &HashMap<PathBuf, PlayStats>,
+) -> Vec<&'a IndexedTable> {
+    let mut played: Vec<(&IndexedTable, &PlayStats)> = tables
+        .iter()
+        .filter_map(|t| Some((t, stats.get(&t.path)?)))
+        .collect();
+    played.sort_by(|a, b| {
+        b.1.launches
+            .cmp(&a.1.launches)
+            .then(b.1.total.cmp(&a.1.total))
+    });
+    played.into_iter().map(|(t, _)| t).collect()
+}
+
+/// Tables without any recorded launch, in the given order.
+pub fn never_played<'a>(
+    tables: &'a [IndexedTable],
+    stats: &HashMap<PathBuf, PlayStats>,
+) -> Vec<&'a IndexedTable> {
+    tables
+        .iter()
+        .filter(|t| !stats.contains_key(&t.path))
+        .collect()
+}
+
+/// Compact play time, e.g. `2h 05m`, `12m` or `40s`.
+pub fn format_play_time(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    let (hours, minutes) = (secs / 3600, (secs % 3600) / 60);
+    if hours > 0 {
+        format!("{hours}h {minutes:02}m")
+    } else if minutes > 0 {
+        format!("{minutes}m")
+    } else {
+        format!("{secs}s")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::indexer::tests::indexed_table;
+    use pretty_assertions::assert_eq;
+    use testdir::testdir;
+
+    fn record(table: &str, started_secs: u64, duration_secs: u64) -> PlayRecord {
+        PlayRecord {
+            table: PathBuf::from(table),
+            template: "Launch".to_string(),
+            started: (SystemTime::UNIX_EPOCH + Duration::from_secs(started_secs)).into(),
+            duration_secs,
+            exit_code: Some(0),
+            signal: None,
+        }
+    }
+
+    #[test]
+    fn appends_and_reads_back_skipping_broken_lines() {
+        let dir = testdir!();
+        let path = dir.join("vpxtool").join(HISTORY_FILE_NAME);
+        let first = record("/t/a.vpx", 1000, 60);
+        append_record(&path, &first).unwrap();
+        // a launch cut short by a power loss
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(b"{\"table\":\"/t/b.vp\n").unwrap();
+        let second = PlayRecord {
+            exit_code: None,
+            signal: Some(11),
+            ..record("/t/b.vpx", 2000, 5)
+        };
+        append_record(&path, &second).unwrap();
+
+        assert_eq!(read_records(&path).unwrap(), vec![first, second]);
+        assert_eq!(read_records(&dir.join("missing.jsonl")).unwrap(), vec![]);
+    }
+
+    #[test]
+    fn aggregates_and_sorts_views() {
+        let records = vec![
+            record("/t/a.vpx", 1000, 600),
+            record("/t/b.vpx", 2000, 60),
+            record("/t/b.vpx", 3000, 60),
+            record("/t/a.vpx", 500, 600),
+        ];
+        let stats = stats_by_table(&records);
+        assert_eq!(
+            stats[&PathBuf::from("/t/a.vpx")],
+            PlayStats {
+                launches: 2,
+                total: Duration::from_secs(1200),
+                last_played: Some(SystemTime::UNIX_EPOCH + Duration::from_secs(1000)),
+            }
+        );
+
+        let tables = vec![
+            indexed_table("/t/a.vpx", serde_json::json!({})),
+            indexed_table("/t/b.vpx", serde_json::json!({})),
+            indexed_table("/t/c.vpx", serde_json::json!({})),
+        ];
+        let paths = |tables: Vec<&IndexedTable>| -> Vec<String> {
+            tables
+                .iter()
+                .map(|t| t.path.display().to_string())
+                .collect()
+        };
+        assert_eq!(
+            paths(recently_played(&tables, &stats)),
+            vec!["/t/b.vpx", "/t/a.vpx"]
+        );
+        // equal launches, a was played longer
+        assert_eq!(
+            paths(most_played(&tables, &stats)),
+            vec!["/t/a.vpx", "/t/b.vpx"]
+        );
+        assert_eq!(paths(never_played(&tables, &stats)), vec!["/t/c.vpx"]);
+    }
+
+    #[test]
+    fn formats_play_time() {
+        assert_eq!(format_play_time(Duration::from_secs(40)), "40s");
+        assert_eq!(format_play_time(Duration::from_secs(12 * 60 + 5)), "12m");
+        assert_eq!(
+            format_play_time(Duration::from_secs(2 * 3600 + 5 * 60)),
+            "2h 05m"
+        );
+    }
+}
@@ -0,0 +1,100 @@
+//! Pixel difference between two captures of the same table, for
+//! `capture --compare`.
+//!
+//! Both images are compared at the size of the new capture, the baseline is
+//! resized when the resolution changed. The difference is the mean absolute
+//! RGB difference in percent: 0 for identical images, 100 for white against
+//! black. Attract mode lights keep blinking, so a few percent is normal, a
+//! black playfield or missing textures end up far above that.
+
+use image::imageops::FilterType;
+use image::{DynamicImage, Rgb, RgbImage};
+
+/// Per-pixel channel differences at or below this are drawn as unchanged in
+/// the heatmap, that hides jpeg noise.
+const HEATMAP_NOISE: u8 = 16;
+
+pub struct ImageDiff {
+    /// Mean absolute RGB difference, 0 to 100.
+    pub difference: f64,
+    /// The new capture in dimmed grayscale, with changed pixels in red.
+    pub heatmap: RgbImage,
+}
+
+pub fn diff_images(baseline: &DynamicImage, current: &DynamicImage) -> ImageDiff {
+    let current = current.to_rgb8();
+    let (width, height) = current.dimensions();
+    let baseline = if baseline.width() == width && baseline.height() == height {
+        baseline.to_rgb8()
+    } else {
+        baseline
+            .resize_exact(width, height, FilterType::Triangle)
+            .to_rgb8()
+    };
+
+    let mut total: u64 = 0;
+    let mut heatmap = RgbImage::new(width, height);
+    for ((a, b), out) in baseline
+        .pixels()
+        .zip(current.pixels())
+        .zip(heatmap.pixels_mut())
+    {
+        let deltas = [0, 1, 2].map(|c| a[c].abs_diff(b[c]));
+        total += deltas.iter().map(|d| *d as u64).sum::<u64>();
+        let delta = deltas.into_iter().max().unwrap_or(0);
+        let gray = (luma(b) / 4) as u8;
+        *out = if delta > HEATMAP_NOISE {
+            Rgb([gray.max(delta), gray, gray])
+        } else {
+            Rgb([gray, gray, gray])
+        };
+    }
+    let channels = width as u64 * height as u64 * 3;
+    let difference = if channels == 0 {
+        0.0
+    } else {
+        total as f64 * 100.0 / (channels as f64 * 255.0)
+    };
+    ImageDiff {
+        difference,
+        heatmap,
+    }
+}
+
+fn luma(pixel: &Rgb<u8>) -> u32 {
+    (pixel[0] as u32 * 299 + pixel[1] as u32 * 587 + pixel[2] as u32 * 114) / 1000
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn image(width: u32, height: u32, color: [u8; 3]) -> DynamicImage {
+        DynamicImage::ImageRgb8(RgbImage::from_pixel(width, height, Rgb(color)))
+    }
+
+    #[test]
+    fn identical_images_do_not_differ() {
+        let diff = diff_images(&image(4, 4, [10, 200, 30]), &image(4, 4, [10, 200, 30]));
+        assert_eq!(diff.difference, 0.0);
+        assert_eq!(diff.heatmap.get_pixel(0, 0), &Rgb([30, 30, 30]));
+    }
+
+    #[test]
+    fn black_playfield_differs_completely() {
+        let diff = diff_images(&image(4, 4, [255, 255, 255]), &image(4, 4, [0, 0, 0]));
+        assert_eq!(diff.difference, 100.0);
+        assert_eq!(diff.heatmap.get_pixel(3, 3), &Rgb([255, 0, 0]));
+    }
+
+    #[test]
+    fn compares_at_the_new_resolution() {
+        let mut current = RgbImage::from_pixel(2, 2, Rgb([0, 0, 0]));
+        current.put_pixel(1, 1, Rgb([255, 255, 255]));
+        let diff = diff_images(&image(8, 8, [0, 0, 0]), &DynamicImage::ImageRgb8(current));
+        assert_eq!(diff.heatmap.dimensions(), (2, 2));
+        assert_eq!(diff.difference, 25.0);
+        assert_eq!(diff.heatmap.get_pixel(0, 0), &Rgb([0, 0, 0]));
+    }
+}
@@ -0,0 +1,408 @@
+//! Pull a single image out of a vpx, or swap its pixels for a new file, without
+//! going through a full `extract`/`assemble` round trip.
+//!
+//! vpinball stores an image either as the original file bytes (the misnamed
+//! `jpeg` field, used for png/jpg/webp/...) or, for bmp, as an lzw compressed
+//! BGRA bitmap (`bits`). We export the former verbatim and the latter as a
+//! regular .bmp, and convert back the same way on replace.
+
+use image::codecs::jpeg::JpegEncoder;
+use image::imageops::FilterType;
+use image::{DynamicImage, ImageFormat, ImageReader};
+use std::io;
+use vpin::vpx::image::{ImageData, ImageDataBits, ImageDataJpeg, image_has_transparency};
+use vpin::vpx::lzw::{from_lzw_blocks, to_lzw_blocks};
+
+/// Formats vpinball accepts as a texture and the `image` crate can read.
+const SUPPORTED_FORMATS: [ImageFormat; 8] = [
+    ImageFormat::Png,
+    ImageFormat::Jpeg,
+    ImageFormat::WebP,
+    ImageFormat::Bmp,
+    ImageFormat::Gif,
+    ImageFormat::Tga,
+    ImageFormat::Hdr,
+    ImageFormat::OpenExr,
+];
+
+/// Jpeg quality when a downscaled jpg texture is encoded again.
+const OPTIMIZED_JPEG_QUALITY: u8 = 90;
+
+/// What changed when replacing an image, for reporting.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImageReplacement {
+    pub old_extension: String,
+    pub new_extension: String,
+    pub old_size: (u32, u32),
+    pub new_size: (u32, u32),
+}
+
+/// Index of the image with the given name. Like vpinball, names are matched
+/// case-insensitively.
+pub(crate) fn find_image(images: &[ImageData], name: &str) -> Option<usize> {
+    images
+        .iter()
+        .position(|image| image.name.eq_ignore_ascii_case(name))
+}
+
+/// The file extension an extracted image gets.
+pub(crate) fn image_extension(image: &ImageData) -> String {
+    if image.bits.is_some() {
+        "bmp".to_string()
+    } else {
+        image.ext().to_ascii_lowercase()
+    }
+}
+
+/// File name for an extracted image, `<name>.<ext>`.
+pub(crate) fn image_file_name(image: &ImageData) -> String {
+    format!(
+        "{}.{}",
+        crate::safe_file_name(&image.name),
+        image_extension(image)
+    )
+}
+
+/// The bytes of the image as a standalone file, see [`image_extension`] for
+/// the matching extension.
+pub(crate) fn image_file_bytes(image: &ImageData) -> io::Result<Vec<u8>> {
+    if let Some(jpeg) = &image.jpeg {
+        return Ok(jpeg.data.clone());
+    }
+    let Some(bits) = &image.bits else {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Image '{}' has no data", image.name),
+        ));
+    };
+    let bgra = from_lzw_blocks(&bits.lzw_compressed_data);
+    let rgba_image =
+        image::RgbaImage::from_raw(image.width, image.height, swap_red_and_blue(&bgra))
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!(
+                        "Bitmap data of image '{}' does not match its {}x{} size",
+                        image.name, image.width, image.height
+                    ),
+                )
+            })?;
+    // vpinball's own bmp export drops the alpha channel, which is nearly
+    // always fully opaque anyway.
+    let uses_alpha = bgra.chunks_exact(4).any(|pixel| pixel[3] != 255);
+    let dynamic_image = if uses_alpha {
+        DynamicImage::ImageRgba8(rgba_image)
+    } else {
+        DynamicImage::ImageRgb8(DynamicImage::ImageRgba8(rgba_image).to_rgb8())
+    };
+    let mut buffer = io::Cursor::new(Vec::new());
+    dynamic_image
+        .write_to(&mut buffer, ImageFormat::Bmp)
+        .map_err(|e| io::Error::other(format!("Failed to encode bitmap: {e}")))?;
+    Ok(buffer.into_inner())
+}
+
+/// Replace the pixel data of `image` with the contents of the file
+/// `file_name`. Width and height are taken from the new file, the name and
+/// the original import path are kept, only the extension of that path is
+/// updated when the format changes. Fails if the file is not an image format
+/// vpinball supports or if its extension does not match its contents.
+pub(crate) fn replace_image_data(
+    image: &mut ImageData,
+    file_name: &str,
+    data: Vec<u8>,
+) -> io::Result<ImageReplacement> {
+    let format = detect_format(file_name, &data)?;
+    let old_extension = image_extension(image);
+    let old_size = (image.width, image.height);
+
+    let new_extension = if format == ImageFormat::Jpeg && old_extension == "jpeg" {
+        // keep the original spelling
+        old_extension.clone()
+    } else {
+        format.extensions_str()[0].to_string()
+    };
+
+    if format == ImageFormat::Bmp {
+        let decoded = image::load_from_memory_with_format(&data, format)
+            .map_err(|e| invalid_image(file_name, e))?;
+        image.width = decoded.width();
+        image.height = decoded.height();
+        let bgra = swap_red_and_blue(&decoded.to_rgba8().into_raw());
+        image.bits = Some(ImageDataBits {
+            lzw_compressed_data: to_lzw_blocks(&bgra),
+        });
+        image.jpeg = None;
+    } else {
+        let (width, height) = ImageReader::with_format(io::Cursor::new(&data), format)
+            .into_dimensions()
+            .map_err(|e| invalid_image(file_name, e))?;
+        image.width = width;
+        image.height = height;
+        let (name, internal_name) = match image.jpeg.take() {
+            Some(jpeg) => (jpeg.name, jpeg.internal_name),
+            None => (image.name.clone(), None),
+        };
+        image.jpeg = Some(ImageDataJpeg {
+            path: String::new(),
+            name,
+            internal_name,
+            data,
+        });
+        image.bits = None;
+    }
+
+    if new_extension != old_extension {
+        image.path = crate::os_independent_with_extension(&image.path, &new_extension);
+    }
+    if let Some(jpeg) = &mut image.jpeg {
+        jpeg.path = image.path.clone();
+    }
+    // vpinball rebuilds the hash on load, a stale one would only be wrong.
+    image.md5_hash = None;
+    if image.is_opaque.is_some() {
+        image.is_opaque = None;
+        image.is_opaque = Some(!image_has_transparency(image));
+    }
+
+    Ok(ImageReplacement {
+        old_extension,
+        new_extension,
+        old_size,
+        new_size: (image.width, image.height),
+    })
+}
+
+/// Make a texture smaller for devices with little storage and memory:
+/// lossless (bmp/png/gif/tga) images become lossless webp and images larger
+/// than `max_size` on either side are downscaled, keeping the aspect ratio.
+/// Downscaled jpgs stay jpg. Returns `None` when the image was left alone,
+/// which is also the case when the result would not be smaller without
+/// downscaling. hdr/exr environment maps are never touched.
+pub(crate) fn optimize_image(
+    image: &mut ImageData,
+    max_size: Option<u32>,
+) -> io::Result<Option<ImageReplacement>> {
+    let extension = image_extension(image);
+    let lossless = matches!(extension.as_str(), "bmp" | "png" | "gif" | "tga");
+    let too_large = max_size.is_some_and(|max| image.width.max(image.height) > max);
+    if (!lossless && !too_large)
+        || matches!(extension.as_str(), "hdr" | "exr")
+        // a linked image without data
+        || (image.jpeg.is_none() && image.bits.is_none())
+    {
+        return Ok(None);
+    }
+    let data = image_file_bytes(image)?;
+    let decoded = match image::load_from_memory(&data) {
+        Ok(decoded) => decoded,
+        Err(e) => {
+            log::warn!("Skipping image {}: {e}", image.name);
+            return Ok(None);
+        }
+    };
+    let decoded = match max_size {
+        Some(max) if too_large => decoded.resize(max, max, FilterType::Lanczos3),
+        _ => decoded,
+    };
+    let mut buffer = io::Cursor::new(Vec::new());
+    let new_extension = if matches!(extension.as_str(), "jpg" | "jpeg") {
+        let encoder = JpegEncoder::new_with_quality(&mut buffer, OPTIMIZED_JPEG_QUALITY);
+        DynamicImage::ImageRgb8(decoded.to_rgb8())
+            .write_with_encoder(encoder)
+            .map_err(|e| io::Error::other(format!("Failed to encode jpg: {e}")))?;
+        "jpg"
+    } else {
+        // lossless according to the image crate docs
+        decoded
+            .write_to(&mut buffer, ImageFormat::WebP)
+            .map_err(|e| io::Error::other(format!("Failed to encode webp: {e}")))?;
+        "webp"
+    };
+    let optimized = buffer.into_inner();
+    if !too_large && optimized.len() >= data.len() {
+        return Ok(None);
+    }
+    let file_name = format!("{}.{new_extension}", crate::safe_file_name(&image.name));
+    replace_image_data(image, &file_name, optimized).map(Some)
+}
+
+fn detect_format(file_name: &str, data: &[u8]) -> io::Result<ImageFormat> {
+    let from_content = image::guess_format(data).map_err(|e| invalid_image(file_name, e))?;
+    if !SUPPORTED_FORMATS.contains(&from_content) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{file_name}: {from_content:?} images are not supported by vpinball"),
+        ));
+    }
+    match ImageFormat::from_path(file_name) {
+        Ok(from_extension) if from_extension != from_content => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "{file_name}: the extension suggests {from_extension:?} but the file contains {from_content:?}"
+            ),
+        )),
+        _ => Ok(from_content),
+    }
+}
+
+fn invalid_image(file_name: &str, e: image::ImageError) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("{file_name}: not a readable image: {e}"),
+    )
+}
+
+/// Converts between RGBA and BGRA.
+fn swap_red_and_blue(data: &[u8]) -> Vec<u8> {
+    data.chunks_exact(4)
+        .flat_map(|pixel| [pixel[2], pixel[1], pixel[0], pixel[3]])
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn encode(image: &DynamicImage, format: ImageFormat) -> Vec<u8> {
+        let mut buffer = io::Cursor::new(Vec::new());
+        image.write_to(&mut buffer, format).unwrap();
+        buffer.into_inner()
+    }
+
+    fn test_image(width: u32, height: u32) -> DynamicImage {
+        DynamicImage::ImageRgb8(image::RgbImage::from_fn(width, height, |x, y| {
+            image::Rgb([x as u8, y as u8, 200])
+        }))
+    }
+
+    fn png_image_data() -> ImageData {
+        let data = encode(&test_image(4, 2), ImageFormat::Png);
+        ImageData {
+            name: "Playfield".to_string(),
+            internal_name: None,
+            path: "C:\\textures\\playfield.png".to_string(),
+            width: 4,
+            height: 2,
+            link: None,
+            alpha_test_value: -1.0,
+            is_opaque: Some(true),
+            is_signed: None,
+            jpeg: Some(ImageDataJpeg {
+                path: "C:\\textures\\playfield.png".to_string(),
+                name: "Playfield".to_string(),
+                internal_name: None,
+                data,
+            }),
+            bits: None,
+            md5_hash: Some([1; 16]),
+        }
+    }
+
+    #[test]
+    fn finds_image_case_insensitive() {
+        let images = vec![png_image_data()];
+        assert_eq!(find_image(&images, "PLAYFIELD"), Some(0));
+        assert_eq!(find_image(&images, "Backglass"), None);
+    }
+
+    #[test]
+    fn file_name_is_safe() {
+        let mut image = png_image_data();
+        image.name = "Ramp/Left:1".to_string();
+        assert_eq!(image_file_name(&image), "Ramp_Left_1.png");
+    }
+
+    #[test]
+    fn replaces_with_same_format() {
+        let mut image = png_image_data();
+        let new_data = encode(&test_image(8, 6), ImageFormat::Png);
+        let replacement = replace_image_data(&mut image, "new.png", new_data.clone()).unwrap();
+        assert_eq!(
+            replacement,
+            ImageReplacement {
+                old_extension: "png".to_string(),
+                new_extension: "png".to_string(),
+                old_size: (4, 2),
+                new_size: (8, 6),
+            }
+        );
+        assert_eq!((image.width, image.height), (8, 6));
+        assert_eq!(image.path, "C:\\textures\\playfield.png");
+        assert_eq!(image.md5_hash, None);
+        assert_eq!(image.is_opaque, Some(true));
+        assert_eq!(image_file_bytes(&image).unwrap(), new_data);
+    }
+
+    #[test]
+    fn replaces_with_other_format_keeping_path() {
+        let mut image = png_image_data();
+        let new_data = encode(&test_image(3, 3), ImageFormat::WebP);
+        replace_image_data(&mut image, "new.webp", new_data).unwrap();
+        assert_eq!(image.path, "C:\\textures\\playfield.webp");
+        assert_eq!(image.jpeg.as_ref().unwrap().path, image.path);
+        assert_eq!(image_extension(&image), "webp");
+    }
+
+    #[test]
+    fn bmp_round_trips_through_bits() {
+        let mut image = png_image_data();
+        let source = test_image(5, 3);
+        replace_image_data(&mut image, "new.bmp", encode(&source, ImageFormat::Bmp)).unwrap();
+        assert!(image.jpeg.is_none());
+        assert!(image.bits.is_some());
+        assert_eq!(image_extension(&image), "bmp");
+
+        let extracted = image_file_bytes(&image).unwrap();
+        let decoded = image::load_from_memory_with_format(&extracted, ImageFormat::Bmp).unwrap();
+        assert_eq!(decoded.to_rgb8(), source.to_rgb8());
+    }
+
+    #[test]
+    fn rejects_mismatched_extension() {
+        let mut image = png_image_data();
+        let jpeg = encode(&test_image(2, 2), ImageFormat::Jpeg);
+        let err = replace_image_data(&mut image, "new.png", jpeg).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "new.png: the extension suggests Png but the file contains Jpeg"
+        );
+        assert_eq!(image, png_image_data());
+    }
+
+    #[test]
+    fn optimize_converts_and_downscales() {
+        let mut image = png_image_data();
+        replace_image_data(
+            &mut image,
+            "big.png",
+            encode(&test_image(64, 32), ImageFormat::Png),
+        )
+        .unwrap();
+        let change = optimize_image(&mut image, Some(16)).unwrap().unwrap();
+        assert_eq!(change.new_extension, "webp");
+        assert_eq!(change.new_size, (16, 8));
+        assert_eq!(image.path, "C:\\textures\\playfield.webp");
+
+        let mut jpeg = png_image_data();
+        replace_image_data(
+            &mut jpeg,
+            "big.jpg",
+            encode(&test_image(64, 32), ImageFormat::Jpeg),
+        )
+        .unwrap();
+        let change = optimize_image(&mut jpeg, Some(32)).unwrap().unwrap();
+        assert_eq!(change.new_extension, "jpg");
+        assert_eq!(change.new_size, (32, 16));
+        // small enough and already lossy
+        assert_eq!(optimize_image(&mut jpeg, Some(32)).unwrap(), None);
+    }
+
+    #[test]
+    fn rejects_non_image() {
+        let mut image = png_image_data();
+        let err = replace_image_data(&mut image, "notes.txt", b"hello".to_vec()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+}
@@ -6,7 +6,7 @@ use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fmt::Debug;
 use std::fs::Metadata;
-use std::io::{BufReader, BufWriter, Read};
+use std::io::{BufReader, BufWriter};
 use std::sync::LazyLock;
 use std::time::SystemTime;
 use std::{
@@ -22,6 +22,7 @@ use vpin::vpx::tableinfo::TableInfo;
 use vpx::gamedata::GameData;
 
 use crate::atomicwrite::atomic_write;
+use crate::scriptfile::read_script;
 
 pub const DEFAULT_INDEX_FILE_NAME: &str = "vpxtool_index.json";
 
@@ -1131,14 +1132,10 @@ fn find_wheel_path(vpx_file_path: &Path) -> Option<PathBuf> {
 /// instead of the code in the vpx file.
 ///
 /// TODO if this file changes the index entry is currently not invalidated
-fn consider_sidecar_vbs(path: &Path, game_data: GameData) -> io::Result<String> {
+pub(crate) fn consider_sidecar_vbs(path: &Path, game_data: GameData) -> io::Result<String> {
     let vbs_path = path.with_extension("vbs");
     let code = if vbs_path.exists() {
-        let vbs_file = File::open(vbs_path)?;
-        let mut reader = BufReader::new(vbs_file);
-        let mut code = String::new();
-        reader.read_to_string(&mut code)?;
-        code
+        read_script(&vbs_path)?.0
     } else {
         game_data.code.string
     };
@@ -1254,7 +1251,7 @@ pub fn read_index_json(
     }
 }
 
-fn extract_game_name<S: AsRef<str>>(code: S) -> Option<String> {
+pub(crate) fn extract_game_name<S: AsRef<str>>(code: S) -> Option<String> {
     let unified = unify_line_endings(code.as_ref());
     unified
         .lines()
@@ -1274,7 +1271,7 @@ fn extract_game_name<S: AsRef<str>>(code: S) -> Option<String> {
         .next()
 }
 
-fn requires_pinmame<S: AsRef<str>>(code: S) -> bool {
+pub(crate) fn requires_pinmame<S: AsRef<str>>(code: S) -> bool {
     let unified = unify_line_endings(code.as_ref());
     let lower = unified.to_lowercase();
     lower
@@ -1290,7 +1287,7 @@ fn unify_line_endings(code: &str) -> String {
 }
 
 #[cfg(test)]
-mod tests {
+pub(crate) mod tests {
     use super::*;
     use pretty_assertions::assert_eq;
     use serde_json::json;
@@ -1298,6 +1295,30 @@ mod tests {
     use testdir::testdir;
     use vpin::vpx;
 
+    /// An [`IndexedTable`] for `path` with every optional field empty,
+    /// `extra` overrides fields by their json name.
+    pub(crate) fn indexed_table(path: impl AsRef<Path>, extra: serde_json::Value) -> IndexedTable {
+        let mut value = json!({
+            "path": path.as_ref(),
+            "table_info": {
+                "table_name": null, "author_name": null, "table_blurb": null,
+                "table_rules": null, "author_email": null, "release_date": null,
+                "table_save_rev": null, "table_version": null, "author_website": null,
+                "table_save_date": null, "table_description": null, "properties": {}
+            },
+            "game_name": null,
+            "b2s_path": null,
+            "rom_path": null,
+            "wheel_path": null,
+            "requires_pinmame": false,
+            "last_modified": "2024-01-01T00:00:00+00:00"
+        });
+        for (key, v) in extra.as_object().unwrap() {
+            value[key] = v.clone();
+        }
+        serde_json::from_value(value).unwrap()
+    }
+
     #[test]
     fn extract_vpreg_keys_picks_up_cgamename_const() {
         // Most common shape: one const referenced from LoadValue calls.
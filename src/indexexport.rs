@@ -0,0 +1,367 @@
+//! `index export`: write the table index as the game list of another cabinet
+//! frontend, so several frontends can be kept in sync with one index.
+//!
+//! Paths in the exports are relative to a base folder, normally the folder the
+//! export is written to, and stay absolute when they are not below it. The
+//! playfield image is the one written by `capture`, manufacturer and year are
+//! parsed from the usual `Title (Manufacturer Year)` file naming.
+
+use crate::capture::existing_playfield_image;
+use crate::escape_xml;
+use crate::indexer::IndexedTable;
+use crate::listing::{Cell, Column, ListFormat, render_list};
+use crate::tableview::parse_file_name;
+use std::fmt::{Display, Formatter};
+use std::io;
+use std::path::{Component, Path, PathBuf};
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// EmulationStation / ES-DE `gamelist.xml`.
+    Gamelist,
+    /// PinballY / PinballX style game database.
+    Pinbally,
+    /// One row per table with all indexed fields.
+    Csv,
+}
+
+impl ExportFormat {
+    pub const VALUES: [&'static str; 3] = ["gamelist", "pinbally", "csv"];
+
+    /// The file name the frontend expects.
+    pub fn default_file_name(&self) -> &'static str {
+        match self {
+            ExportFormat::Gamelist => "gamelist.xml",
+            ExportFormat::Pinbally => "Visual Pinball X.xml",
+            ExportFormat::Csv => "tables.csv",
+        }
+    }
+}
+
+impl Display for ExportFormat {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            ExportFormat::Gamelist => "gamelist",
+            ExportFormat::Pinbally => "pinbally",
+            ExportFormat::Csv => "csv",
+        };
+        f.write_str(s)
+    }
+}
+
+impl FromStr for ExportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "gamelist" | "es-de" | "emulationstation" => Ok(ExportFormat::Gamelist),
+            "pinbally" | "pinballx" => Ok(ExportFormat::Pinbally),
+            "csv" => Ok(ExportFormat::Csv),
+            other => Err(format!(
+                "Unknown export format '{other}', expected gamelist, pinbally or csv"
+            )),
+        }
+    }
+}
+
+/// Render the tables in the given format, paths relative to `base`.
+pub fn export(format: ExportFormat, tables: &[IndexedTable], base: &Path) -> io::Result<String> {
+    match format {
+        ExportFormat::Gamelist => Ok(gamelist(tables, base)),
+        ExportFormat::Pinbally => Ok(pinbally(tables, base)),
+        ExportFormat::Csv => csv(tables, base),
+    }
+}
+
+/// `path` relative to `base`, `./` prefixed as frontends expect, or absolute
+/// when `path` is not below `base`.
+pub fn relative_path(path: &Path, base: &Path) -> String {
+    // the index keeps paths as they were given to `index`
+    let path = std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+    match path.strip_prefix(base) {
+        Ok(relative)
+            if relative
+                .components()
+                .all(|c| matches!(c, Component::Normal(_))) =>
+        {
+            // always forward slashes, frontends on Windows accept those too
+            let parts: Vec<String> = relative
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy().to_string())
+                .collect();
+            format!("./{}", parts.join("/"))
+        }
+        _ => path.display().to_string(),
+    }
+}
+
+fn title(table: &IndexedTable) -> String {
+    table
+        .table_info
+        .table_name
+        .clone()
+        .filter(|name| !name.trim().is_empty())
+        .unwrap_or_else(|| file_stem(&table.path))
+}
+
+fn file_stem(path: &Path) -> String {
+    path.file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
+fn playfield_image(table: &IndexedTable) -> Option<PathBuf> {
+    existing_playfield_image(&table.path).map(|(_, path)| path)
+}
+
+fn push_element(xml: &mut String, indent: &str, name: &str, value: Option<&str>) {
+    if let Some(value) = value.filter(|v| !v.trim().is_empty()) {
+        xml.push_str(&format!(
+            "{indent}<{name}>{}</{name}>\n",
+            escape_xml(value.trim())
+        ));
+    }
+}
+
+fn gamelist(tables: &[IndexedTable], base: &Path) -> String {
+    let mut xml = String::from("<?xml version=\"1.0\"?>\n<gameList>\n");
+    for table in tables {
+        let parsed = parse_file_name(&file_stem(&table.path));
+        let info = &table.table_info;
+        let relative = |path: &PathBuf| relative_path(path, base);
+        xml.push_str("  <game>\n");
+        push_element(&mut xml, "    ", "path", Some(&relative(&table.path)));
+        push_element(&mut xml, "    ", "name", Some(&title(table)));
+        push_element(
+            &mut xml,
+            "    ",
+            "desc",
+            info.table_description
+                .as_deref()
+                .or(info.table_blurb.as_deref()),
+        );
+        push_element(&mut xml, "    ", "developer", info.author_name.as_deref());
+        push_element(
+            &mut xml,
+            "    ",
+            "publisher",
+            parsed.manufacturer.as_deref(),
+        );
+        // ES-DE only reads the full timestamp format
+        let release_date = parsed.year.map(|year| format!("{year}0101T000000"));
+        push_element(&mut xml, "    ", "releasedate", release_date.as_deref());
+        push_element(
+            &mut xml,
+            "    ",
+            "image",
+            playfield_image(table).as_ref().map(relative).as_deref(),
+        );
+        push_element(
+            &mut xml,
+            "    ",
+            "marquee",
+            table.wheel_path.as_ref().map(relative).as_deref(),
+        );
+        xml.push_str("  </game>\n");
+    }
+    xml.push_str("</gameList>\n");
+    xml
+}
+
+/// PinballY finds media by game name, so only the table file and metadata
+/// are listed. The name is the table file name without extension, as
+/// PinballY expects it. Tables in a subfolder of `base` get that folder in a
+/// `folder` element, which PinballY ignores, so tables can be moved or
+/// linked into the system's table folder.
+fn pinbally(tables: &[IndexedTable], base: &Path) -> String {
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<menu>\n");
+    for table in tables {
+        let parsed = parse_file_name(&file_stem(&table.path));
+        let info = &table.table_info;
+        let name = file_stem(&table.path);
+        let folder = table
+            .path
+            .parent()
+            .map(|parent| relative_path(parent, base))
+            .unwrap_or_default();
+        let folder = folder.strip_prefix("./").unwrap_or(&folder);
+        xml.push_str(&format!("  <game name=\"{}\">\n", escape_xml(&name)));
+        push_element(&mut xml, "    ", "folder", Some(folder));
+        push_element(&mut xml, "    ", "description", Some(&title(table)));
+        push_element(&mut xml, "    ", "rom", table.game_name.as_deref());
+        push_element(
+            &mut xml,
+            "    ",
+            "manufacturer",
+            parsed.manufacturer.as_deref(),
+        );
+        let year = parsed.year.map(|year| year.to_string());
+        push_element(&mut xml, "    ", "year", year.as_deref());
+        push_element(&mut xml, "    ", "author", info.author_name.as_deref());
+        push_element(&mut xml, "    ", "version", info.table_version.as_deref());
+        push_element(&mut xml, "    ", "enabled", Some("True"));
+        xml.push_str("  </game>\n");
+    }
+    xml.push_str("</menu>\n");
+    xml
+}
+
+fn csv(tables: &[IndexedTable], base: &Path) -> io::Result<String> {
+    let columns = [
+        Column::left("NAME", "name"),
+        Column::left("PATH", "path"),
+        Column::left("ROM", "rom"),
+        Column::left("MANUFACTURER", "manufacturer"),
+        Column::left("YEAR", "year"),
+        Column::left("AUTHOR", "author"),
+        Column::left("VERSION", "version"),
+        Column::left("PINMAME", "requires_pinmame"),
+        Column::left("B2S", "b2s"),
+        Column::left("WHEEL", "wheel"),
+        Column::left("PLAYFIELD_IMAGE", "playfield_image"),
+        Column::left("ALTSOUND", "altsound"),
+        Column::left("ALTCOLOR", "altcolor"),
+        Column::left("PUP", "pup_pack"),
+    ];
+    let optional_path = |path: Option<&PathBuf>| match path {
+        Some(path) => Cell::text(relative_path(path, base)),
+        None => Cell::Empty,
+    };
+    let optional = |value: Option<&String>| Cell::non_empty(value.cloned().unwrap_or_default());
+    let rows: Vec<Vec<Cell>> = tables
+        .iter()
+        .map(|table| {
+            let parsed = parse_file_name(&file_stem(&table.path));
+            vec![
+                Cell::text(title(table)),
+                Cell::text(relative_path(&table.path, base)),
+                optional(table.game_name.as_ref()),
+                optional(parsed.manufacturer.as_ref()),
+                parsed.year.map(Cell::int).unwrap_or(Cell::Empty),
+                optional(table.table_info.author_name.as_ref()),
+                optional(table.table_info.table_version.as_ref()),
+                Cell::flag(table.requires_pinmame),
+                optional_path(table.b2s_path.as_ref()),
+                optional_path(table.wheel_path.as_ref()),
+                optional_path(playfield_image(table).as_ref()),
+                optional_path(table.altsound_path.as_ref()),
+                optional_path(table.altcolor_path.as_ref()),
+                optional_path(table.pup_pack_path.as_ref()),
+            ]
+        })
+        .collect();
+    render_list(ListFormat::Csv, &columns, &rows)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::indexer::tests::indexed_table;
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+    use testdir::testdir;
+
+    fn tables(root: &Path) -> Vec<IndexedTable> {
+        let afm = root.join("Attack from Mars (Bally 1995)");
+        std::fs::create_dir_all(afm.join("media")).unwrap();
+        std::fs::write(afm.join("media").join("table.png"), b"").unwrap();
+        vec![
+            indexed_table(
+                afm.join("Attack from Mars (Bally 1995).vpx"),
+                json!({
+                    "game_name": "afm_113b",
+                    "requires_pinmame": true,
+                    "wheel_path": afm.join("wheel.png"),
+                    "table_info": {
+                        "table_name": "Attack from Mars", "author_name": "VPW & friends",
+                        "table_blurb": null, "table_rules": null, "author_email": null,
+                        "release_date": null, "table_save_rev": null, "table_version": "2.1",
+                        "author_website": null, "table_save_date": null,
+                        "table_description": "Mars <attacks>", "properties": {}
+                    }
+                }),
+            ),
+            indexed_table(Path::new("/elsewhere/my_original.vpx"), json!({})),
+        ]
+    }
+
+    #[test]
+    fn resolves_relative_paths() {
+        let base = Path::new("/tables");
+        assert_eq!(
+            relative_path(Path::new("/tables/AFM/AFM.vpx"), base),
+            "./AFM/AFM.vpx"
+        );
+        assert_eq!(
+            relative_path(Path::new("/other/AFM.vpx"), base),
+            "/other/AFM.vpx"
+        );
+    }
+
+    #[test]
+    fn exports_gamelist() {
+        let root = testdir!();
+        let xml = export(ExportFormat::Gamelist, &tables(&root), &root).unwrap();
+        assert_eq!(
+            xml,
+            r#"<?xml version="1.0"?>
+<gameList>
+  <game>
+    <path>./Attack from Mars (Bally 1995)/Attack from Mars (Bally 1995).vpx</path>
+    <name>Attack from Mars</name>
+    <desc>Mars &lt;attacks&gt;</desc>
+    <developer>VPW &amp; friends</developer>
+    <publisher>Bally</publisher>
+    <releasedate>19950101T000000</releasedate>
+    <image>./Attack from Mars (Bally 1995)/media/table.png</image>
+    <marquee>./Attack from Mars (Bally 1995)/wheel.png</marquee>
+  </game>
+  <game>
+    <path>/elsewhere/my_original.vpx</path>
+    <name>my_original</name>
+  </game>
+</gameList>
+"#
+        );
+    }
+
+    #[test]
+    fn exports_pinbally_and_csv() {
+        let root = testdir!();
+        let tables = tables(&root);
+        let xml = export(ExportFormat::Pinbally, &tables, &root).unwrap();
+        assert_eq!(
+            xml,
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<menu>
+  <game name="Attack from Mars (Bally 1995)">
+    <folder>Attack from Mars (Bally 1995)</folder>
+    <description>Attack from Mars</description>
+    <rom>afm_113b</rom>
+    <manufacturer>Bally</manufacturer>
+    <year>1995</year>
+    <author>VPW &amp; friends</author>
+    <version>2.1</version>
+    <enabled>True</enabled>
+  </game>
+  <game name="my_original">
+    <folder>/elsewhere</folder>
+    <description>my_original</description>
+    <enabled>True</enabled>
+  </game>
+</menu>
+"#
+        );
+        let csv = export(ExportFormat::Csv, &tables, &root).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(
+            lines,
+            vec![
+                "name,path,rom,manufacturer,year,author,version,requires_pinmame,b2s,wheel,playfield_image,altsound,altcolor,pup_pack",
+                "Attack from Mars,./Attack from Mars (Bally 1995)/Attack from Mars (Bally 1995).vpx,afm_113b,Bally,1995,VPW & friends,2.1,true,,./Attack from Mars (Bally 1995)/wheel.png,./Attack from Mars (Bally 1995)/media/table.png,,,",
+                "my_original,/elsewhere/my_original.vpx,,,,,,false,,,,,,",
+            ]
+        );
+    }
+}
@@ -0,0 +1,219 @@
+//! Filtering for `index query`, answering questions like "which tables still
+//! need a ROM" straight from `vpxtool_index.json`.
+//!
+//! All filters are combined with AND, a table without any filter always
+//! matches.
+
+use crate::indexer::IndexedTable;
+use chrono::{DateTime, Local, NaiveDate, TimeZone};
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use std::time::SystemTime;
+
+/// Something the indexer detects next to a table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TableAsset {
+    Rom,
+    B2s,
+    Wheel,
+    AltSound,
+    AltColor,
+    Pup,
+}
+
+impl TableAsset {
+    pub const VALUES: [&'static str; 6] = ["rom", "b2s", "wheel", "altsound", "altcolor", "pup"];
+
+    fn is_present(&self, table: &IndexedTable) -> bool {
+        match self {
+            TableAsset::Rom => table.rom_path().is_some(),
+            TableAsset::B2s => table.b2s_path.is_some(),
+            TableAsset::Wheel => table.wheel_path.is_some(),
+            TableAsset::AltSound => table.altsound_path.is_some(),
+            TableAsset::AltColor => table.altcolor_path.is_some(),
+            TableAsset::Pup => table.pup_pack_path.is_some(),
+        }
+    }
+}
+
+impl Display for TableAsset {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            TableAsset::Rom => "rom",
+            TableAsset::B2s => "b2s",
+            TableAsset::Wheel => "wheel",
+            TableAsset::AltSound => "altsound",
+            TableAsset::AltColor => "altcolor",
+            TableAsset::Pup => "pup",
+        };
+        f.write_str(s)
+    }
+}
+
+impl FromStr for TableAsset {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "rom" => Ok(TableAsset::Rom),
+            "b2s" => Ok(TableAsset::B2s),
+            "wheel" => Ok(TableAsset::Wheel),
+            "altsound" => Ok(TableAsset::AltSound),
+            "altcolor" => Ok(TableAsset::AltColor),
+            "pup" => Ok(TableAsset::Pup),
+            other => Err(format!(
+                "Unknown asset '{other}', expected one of {}",
+                Self::VALUES.join(", ")
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct IndexQuery {
+    /// Tables that need PinMAME but for which no ROM was found.
+    pub missing_rom: bool,
+    pub requires_pinmame: bool,
+    /// Every listed asset must be present.
+    pub has: Vec<TableAsset>,
+    /// Matched case-insensitively against the game name (`cGameName`).
+    pub rom: Option<String>,
+    /// Only tables whose vpx file was modified at or after this time.
+    pub modified_since: Option<SystemTime>,
+}
+
+impl IndexQuery {
+    pub fn matches(&self, table: &IndexedTable) -> bool {
+        if self.missing_rom && !(table.requires_pinmame && table.rom_path().is_none()) {
+            return false;
+        }
+        if self.requires_pinmame && !table.requires_pinmame {
+            return false;
+        }
+        if !self.has.iter().all(|asset| asset.is_present(table)) {
+            return false;
+        }
+        if let Some(rom) = &self.rom {
+            let matches_rom = table
+                .game_name
+                .as_ref()
+                .is_some_and(|game_name| game_name.eq_ignore_ascii_case(rom));
+            if !matches_rom {
+                return false;
+            }
+        }
+        if let Some(since) = self.modified_since {
+            let last_modified: SystemTime = table.last_modified.into();
+            if last_modified < since {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// The matching tables, sorted by path.
+    pub fn filter(&self, tables: Vec<IndexedTable>) -> Vec<IndexedTable> {
+        let mut matching: Vec<IndexedTable> =
+            tables.into_iter().filter(|t| self.matches(t)).collect();
+        matching.sort_by_key(|t| t.path.to_string_lossy().to_lowercase());
+        matching
+    }
+}
+
+/// Parse a `--modified-since` value: either a date (`2024-05-01`, taken as
+/// local midnight) or a full RFC 3339 timestamp.
+pub fn parse_since(s: &str) -> Result<SystemTime, String> {
+    if let Ok(date) = NaiveDate::parse_from_str(s, "%Y-%m-%d") {
+        let midnight = date.and_hms_opt(0, 0, 0).expect("midnight is valid");
+        return Local
+            .from_local_datetime(&midnight)
+            .earliest()
+            .map(SystemTime::from)
+            .ok_or_else(|| format!("'{s}' does not exist in the local time zone"));
+    }
+    DateTime::parse_from_rfc3339(s)
+        .map(SystemTime::from)
+        .map_err(|_| format!("Invalid date '{s}', expected YYYY-MM-DD or an RFC 3339 timestamp"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::indexer::tests::indexed_table;
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+    use std::time::Duration;
+
+    fn tables() -> Vec<IndexedTable> {
+        vec![
+            indexed_table(
+                "/tables/b.vpx",
+                json!({"game_name": "AFM_113b", "requires_pinmame": true, "rom_path": "/roms/afm_113b.zip", "pup_pack_path": "/tables/pupvideos/afm"}),
+            ),
+            indexed_table(
+                "/tables/A.vpx",
+                json!({"game_name": "tz_94h", "requires_pinmame": true, "last_modified": "2024-06-01T12:00:00+00:00"}),
+            ),
+            indexed_table("/tables/c.vpx", json!({})),
+        ]
+    }
+
+    fn paths(tables: &[IndexedTable]) -> Vec<String> {
+        tables
+            .iter()
+            .map(|t| t.path.display().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn no_filters_matches_everything_sorted() {
+        let result = IndexQuery::default().filter(tables());
+        assert_eq!(
+            paths(&result),
+            vec!["/tables/A.vpx", "/tables/b.vpx", "/tables/c.vpx"]
+        );
+    }
+
+    #[test]
+    fn missing_rom() {
+        let query = IndexQuery {
+            missing_rom: true,
+            ..IndexQuery::default()
+        };
+        assert_eq!(paths(&query.filter(tables())), vec!["/tables/A.vpx"]);
+    }
+
+    #[test]
+    fn filters_combine() {
+        let query = IndexQuery {
+            requires_pinmame: true,
+            has: vec![TableAsset::Pup, TableAsset::Rom],
+            rom: Some("afm_113B".to_string()),
+            ..IndexQuery::default()
+        };
+        assert_eq!(paths(&query.filter(tables())), vec!["/tables/b.vpx"]);
+    }
+
+    #[test]
+    fn modified_since() {
+        let query = IndexQuery {
+            modified_since: Some(parse_since("2024-06-01T00:00:00Z").unwrap()),
+            ..IndexQuery::default()
+        };
+        assert_eq!(paths(&query.filter(tables())), vec!["/tables/A.vpx"]);
+    }
+
+    #[test]
+    fn parses_dates() {
+        let date = parse_since("2024-06-01").unwrap();
+        let timestamp = parse_since("2024-06-01T00:00:00Z").unwrap();
+        // local midnight is within a day of utc midnight
+        let diff = date
+            .duration_since(timestamp)
+            .or_else(|e| Ok::<_, ()>(e.duration()))
+            .unwrap();
+        assert!(diff <= Duration::from_secs(14 * 3600));
+        assert!(parse_since("yesterday").is_err());
+        assert_eq!("PUP".parse::<TableAsset>(), Ok(TableAsset::Pup));
+    }
+}
@@ -0,0 +1,342 @@
+//! Validation and change preview for importing a `<table>.info.json` sidecar
+//! (as written by `info extract` / `info edit`) back into a vpx file.
+//!
+//! `vpin`'s `json_to_info` silently ignores unknown keys and reports type
+//! errors one at a time, which makes a hand-edited file painful to fix. We
+//! walk the json ourselves first and collect every problem, then hand the
+//! validated value to `json_to_info` so the json model stays owned by `vpin`.
+
+use serde_json::Value;
+use std::collections::HashSet;
+use vpin::vpx::custominfotags::CustomInfoTags;
+use vpin::vpx::jsonmodel::json_to_info;
+use vpin::vpx::tableinfo::TableInfo;
+
+const PROPERTIES: &str = "properties";
+const PROPERTIES_ORDER: &str = "properties_order";
+
+/// The optional string fields of the info json, in the order we report them.
+const STRING_FIELDS: [&str; 11] = [
+    "table_name",
+    "table_version",
+    "release_date",
+    "author_name",
+    "author_email",
+    "author_website",
+    "table_blurb",
+    "table_description",
+    "table_rules",
+    "table_save_rev",
+    "table_save_date",
+];
+
+/// A single field that differs between the table info in the vpx and the
+/// imported json. `None` means the field is not set.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct FieldChange {
+    pub field: String,
+    pub old: Option<String>,
+    pub new: Option<String>,
+}
+
+/// Validate an info json value and convert it to the vpx model. On failure
+/// every problem found is returned, not just the first one.
+///
+/// The screenshot is not part of the json, pass the one from the vpx to keep
+/// it.
+pub(crate) fn parse_info_json(
+    json: Value,
+    screenshot: Option<Vec<u8>>,
+) -> Result<(TableInfo, CustomInfoTags), Vec<String>> {
+    let problems = validate(&json);
+    if !problems.is_empty() {
+        return Err(problems);
+    }
+    json_to_info(json, screenshot).map_err(|e| vec![e.to_string()])
+}
+
+fn validate(json: &Value) -> Vec<String> {
+    let Some(object) = json.as_object() else {
+        return vec!["expected a json object at the top level".to_string()];
+    };
+    let mut problems = Vec::new();
+    for (key, value) in object {
+        if STRING_FIELDS.contains(&key.as_str()) {
+            if !value.is_string() && !value.is_null() {
+                problems.push(format!(
+                    "'{key}' should be a string or null, found {}",
+                    type_name(value)
+                ));
+            }
+        } else if key != PROPERTIES && key != PROPERTIES_ORDER {
+            problems.push(format!("unknown key '{key}'"));
+        }
+    }
+
+    let properties = match object.get(PROPERTIES) {
+        None => {
+            problems.push(format!("missing '{PROPERTIES}'"));
+            None
+        }
+        Some(Value::Object(properties)) => {
+            for (key, value) in properties {
+                if !value.is_string() {
+                    problems.push(format!(
+                        "'{PROPERTIES}.{key}' should be a string, found {}",
+                        type_name(value)
+                    ));
+                }
+            }
+            Some(properties)
+        }
+        Some(other) => {
+            problems.push(format!(
+                "'{PROPERTIES}' should be an object, found {}",
+                type_name(other)
+            ));
+            None
+        }
+    };
+
+    let order = match object.get(PROPERTIES_ORDER) {
+        None => {
+            problems.push(format!("missing '{PROPERTIES_ORDER}'"));
+            None
+        }
+        Some(Value::Array(order)) => {
+            let mut names = Vec::new();
+            for (index, value) in order.iter().enumerate() {
+                match value.as_str() {
+                    Some(name) => names.push(name),
+                    None => problems.push(format!(
+                        "'{PROPERTIES_ORDER}[{index}]' should be a string, found {}",
+                        type_name(value)
+                    )),
+                }
+            }
+            Some(names)
+        }
+        Some(other) => {
+            problems.push(format!(
+                "'{PROPERTIES_ORDER}' should be an array, found {}",
+                type_name(other)
+            ));
+            None
+        }
+    };
+
+    // vpinball stores the custom property keys (and their ordering) in the
+    // CustomInfoTags stream; a property that is not listed there is not
+    // picked up, and a listed tag without a value makes no sense.
+    if let (Some(properties), Some(order)) = (properties, order) {
+        let mut seen = HashSet::new();
+        for name in &order {
+            if !seen.insert(*name) {
+                problems.push(format!(
+                    "'{PROPERTIES_ORDER}' lists '{name}' more than once"
+                ));
+            } else if !properties.contains_key(*name) {
+                problems.push(format!(
+                    "'{PROPERTIES_ORDER}' lists '{name}' which has no value in '{PROPERTIES}'"
+                ));
+            }
+        }
+        for key in properties.keys() {
+            if !seen.contains(key.as_str()) {
+                problems.push(format!(
+                    "'{PROPERTIES}.{key}' is missing from '{PROPERTIES_ORDER}'"
+                ));
+            }
+        }
+    }
+    problems
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "a boolean",
+        Value::Number(_) => "a number",
+        Value::String(_) => "a string",
+        Value::Array(_) => "an array",
+        Value::Object(_) => "an object",
+    }
+}
+
+/// List the fields that differ between the current and the imported table
+/// info. Custom properties are compared by key, in the order of the imported
+/// `properties_order` followed by any keys that were removed. A pure
+/// reordering of the custom properties is reported as a `properties_order`
+/// change.
+pub(crate) fn diff_info(
+    old: &TableInfo,
+    old_tags: &CustomInfoTags,
+    new: &TableInfo,
+    new_tags: &CustomInfoTags,
+) -> Vec<FieldChange> {
+    let mut changes = Vec::new();
+    for field in STRING_FIELDS {
+        let old_value = string_field(old, field);
+        let new_value = string_field(new, field);
+        if old_value != new_value {
+            changes.push(FieldChange {
+                field: field.to_string(),
+                old: old_value.cloned(),
+                new: new_value.cloned(),
+            });
+        }
+    }
+
+    let removed = old_tags.iter().filter(|tag| !new_tags.contains(tag));
+    for key in new_tags.iter().chain(removed) {
+        let old_value = old.properties.get(key);
+        let new_value = new.properties.get(key);
+        if old_value != new_value {
+            changes.push(FieldChange {
+                field: format!("{PROPERTIES}.{key}"),
+                old: old_value.cloned(),
+                new: new_value.cloned(),
+            });
+        }
+    }
+
+    let common_old: Vec<&String> = old_tags.iter().filter(|t| new_tags.contains(t)).collect();
+    let common_new: Vec<&String> = new_tags.iter().filter(|t| old_tags.contains(t)).collect();
+    if common_old != common_new {
+        changes.push(FieldChange {
+            field: PROPERTIES_ORDER.to_string(),
+            old: Some(old_tags.join(", ")),
+            new: Some(new_tags.join(", ")),
+        });
+    }
+    changes
+}
+
+fn string_field<'a>(info: &'a TableInfo, field: &str) -> Option<&'a String> {
+    match field {
+        "table_name" => info.table_name.as_ref(),
+        "table_version" => info.table_version.as_ref(),
+        "release_date" => info.release_date.as_ref(),
+        "author_name" => info.author_name.as_ref(),
+        "author_email" => info.author_email.as_ref(),
+        "author_website" => info.author_website.as_ref(),
+        "table_blurb" => info.table_blurb.as_ref(),
+        "table_description" => info.table_description.as_ref(),
+        "table_rules" => info.table_rules.as_ref(),
+        "table_save_rev" => info.table_save_rev.as_ref(),
+        "table_save_date" => info.table_save_date.as_ref(),
+        _ => unreachable!("unknown table info field {field}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+    use vpin::vpx::jsonmodel::info_to_json;
+
+    fn sample_info() -> (TableInfo, CustomInfoTags) {
+        let mut info = TableInfo {
+            table_name: Some("Test Table".to_string()),
+            author_name: Some("Me".to_string()),
+            ..TableInfo::default()
+        };
+        info.properties
+            .insert("Rom".to_string(), "test_l1".to_string());
+        (info, vec!["Rom".to_string()])
+    }
+
+    #[test]
+    fn round_trips_extracted_json() {
+        let (info, tags) = sample_info();
+        let json = info_to_json(&info, &tags);
+        let (parsed, parsed_tags) = parse_info_json(json, None).unwrap();
+        assert_eq!(parsed, info);
+        assert_eq!(parsed_tags, tags);
+        assert!(diff_info(&info, &tags, &parsed, &parsed_tags).is_empty());
+    }
+
+    #[test]
+    fn reports_every_problem() {
+        let json = json!({
+            "table_name": 12,
+            "tabel_version": "1.0",
+            "properties": {"Rom": "x", "Extra": true},
+            "properties_order": ["Rom", "Rom", "Missing"],
+        });
+        let problems = parse_info_json(json, None).unwrap_err();
+        assert_eq!(
+            problems,
+            vec![
+                "'table_name' should be a string or null, found a number",
+                "unknown key 'tabel_version'",
+                "'properties.Extra' should be a string, found a boolean",
+                "'properties_order' lists 'Rom' more than once",
+                "'properties_order' lists 'Missing' which has no value in 'properties'",
+                "'properties.Extra' is missing from 'properties_order'",
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_non_object() {
+        let problems = parse_info_json(json!([]), None).unwrap_err();
+        assert_eq!(problems, vec!["expected a json object at the top level"]);
+    }
+
+    #[test]
+    fn diff_lists_changed_fields() {
+        let (old, old_tags) = sample_info();
+        let mut new = TableInfo {
+            table_name: Some("Renamed".to_string()),
+            author_name: None,
+            table_rules: Some("Hit things".to_string()),
+            ..TableInfo::default()
+        };
+        new.properties
+            .insert("Rom".to_string(), "test_l2".to_string());
+        new.properties
+            .insert("Year".to_string(), "1992".to_string());
+        let new_tags = vec!["Year".to_string(), "Rom".to_string()];
+
+        let changes = diff_info(&old, &old_tags, &new, &new_tags);
+        let change = |field: &str, old: Option<&str>, new: Option<&str>| FieldChange {
+            field: field.to_string(),
+            old: old.map(String::from),
+            new: new.map(String::from),
+        };
+        assert_eq!(
+            changes,
+            vec![
+                change("table_name", Some("Test Table"), Some("Renamed")),
+                change("author_name", Some("Me"), None),
+                change("table_rules", None, Some("Hit things")),
+                change("properties.Year", None, Some("1992")),
+                change("properties.Rom", Some("test_l1"), Some("test_l2")),
+            ]
+        );
+    }
+
+    #[test]
+    fn diff_reports_reordering() {
+        let mut old = TableInfo::default();
+        old.properties.insert("A".to_string(), "1".to_string());
+        old.properties.insert("B".to_string(), "2".to_string());
+        let old_tags = vec!["A".to_string(), "B".to_string()];
+        let new = TableInfo {
+            properties: old.properties.clone(),
+            ..TableInfo::default()
+        };
+        let new_tags = vec!["B".to_string(), "A".to_string()];
+        let changes = diff_info(&old, &old_tags, &new, &new_tags);
+        assert_eq!(
+            changes,
+            vec![FieldChange {
+                field: "properties_order".to_string(),
+                old: Some("A, B".to_string()),
+                new: Some("B, A".to_string()),
+            }]
+        );
+    }
+}
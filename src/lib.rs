@@ -3,19 +3,37 @@ use std::io;
 use std::path::{Path, PathBuf};
 use std::process::ExitStatus;
 
+mod animated;
 mod atomicwrite;
 mod backglass;
 pub mod capture;
+mod capturereport;
 pub mod fixprint;
 mod frontend;
+mod history;
+mod imagediff;
+mod images;
+mod info;
+mod listing;
 pub mod patcher;
+mod placeholders;
+mod prune;
+mod scriptfile;
+mod scriptlint;
+mod scriptxref;
+mod smoketest;
+mod sounds;
+mod tableview;
 
 pub mod config;
 
 pub mod indexer;
+mod indexexport;
+mod indexquery;
 
 pub mod cli;
 mod colorful_theme_patched;
+mod doctor;
 pub mod scores;
 pub mod vpinball_config;
 pub mod vpxz;
@@ -67,6 +85,26 @@ pub fn strip_cr_lf(s: &str) -> String {
     s.chars().filter(|c| !c.is_ascii_whitespace()).collect()
 }
 
+/// Escape text for an XML attribute or element, dropping the control
+/// characters XML 1.0 does not allow (vpinball logs and table metadata can
+/// contain anything).
+pub(crate) fn escape_xml(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            // not allowed in XML 1.0
+            c if c.is_control() && !matches!(c, '\n' | '\r' | '\t') => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
 fn path_exists(path: impl AsRef<Path>) -> io::Result<PathBuf> {
     let path = path.as_ref();
     match metadata(path) {
@@ -96,6 +134,27 @@ fn os_independent_file_name(file_path: String) -> Option<String> {
     file_path.rsplit(['/', '\\']).next().map(|f| f.to_string())
 }
 
+/// Replace the extension of a path as stored in a vpx file, which is often a
+/// windows path. Appends the extension if the file name has none.
+fn os_independent_with_extension(file_path: &str, extension: &str) -> String {
+    let file_start = file_path.rfind(['/', '\\']).map(|i| i + 1).unwrap_or(0);
+    match file_path[file_start..].rfind('.') {
+        Some(dot) => format!("{}.{extension}", &file_path[..file_start + dot]),
+        None => format!("{file_path}.{extension}"),
+    }
+}
+
+/// Turn a vpx item name into something usable as a file name on every
+/// platform by replacing the characters windows does not allow.
+fn safe_file_name(name: &str) -> String {
+    name.chars()
+        .map(|c| match c {
+            '<' | '>' | ':' | '"' | '/' | '\\' | '|' | '?' | '*' => '_',
+            c => c,
+        })
+        .collect()
+}
+
 /// Path to file that will be removed when it goes out of scope
 struct RemoveOnDrop {
     path: PathBuf,
@@ -180,4 +239,22 @@ mod tests {
         let result = os_independent_file_name(file_path.to_string());
         assert_eq!(result, None);
     }
+
+    #[test]
+    fn test_os_independent_with_extension() {
+        assert_eq!(
+            os_independent_with_extension("C:\\a.b\\tex.png", "webp"),
+            "C:\\a.b\\tex.webp"
+        );
+        assert_eq!(
+            os_independent_with_extension("C:\\a.b\\tex", "webp"),
+            "C:\\a.b\\tex.webp"
+        );
+        assert_eq!(os_independent_with_extension("tex.png", "jpg"), "tex.jpg");
+    }
+
+    #[test]
+    fn test_safe_file_name() {
+        assert_eq!(safe_file_name("Ramp/Left:1"), "Ramp_Left_1");
+    }
 }
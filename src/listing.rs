@@ -0,0 +1,384 @@
+//! Output for the `list` subcommands (`images list`, `sounds list`, ...).
+//!
+//! Every listing is described once as a set of [`Column`]s and rows of
+//! [`Cell`]s and can then be rendered as aligned columns for humans or as
+//! tsv, csv or json for scripts. The machine formats use the column `key`
+//! (stable snake_case) instead of the display header and print raw values:
+//! no rounding, `true`/`false` instead of `Y`/`N`, and json numbers, booleans
+//! and arrays where the data has that shape.
+
+use serde_json::{Map, Number, Value};
+use std::fmt::{Display, Formatter};
+use std::io;
+use std::io::Write;
+use std::str::FromStr;
+
+/// Output format for the `list` subcommands.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ListFormat {
+    /// Aligned, human-readable columns. The default.
+    #[default]
+    Table,
+    /// Tab-separated values with a header row of column keys.
+    Tsv,
+    /// RFC 4180 comma-separated values with a header row of column keys.
+    Csv,
+    /// A json array with one object per row, keyed by column key.
+    Json,
+}
+
+impl ListFormat {
+    pub const VALUES: [&'static str; 4] = ["table", "tsv", "csv", "json"];
+}
+
+impl Display for ListFormat {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            ListFormat::Table => "table",
+            ListFormat::Tsv => "tsv",
+            ListFormat::Csv => "csv",
+            ListFormat::Json => "json",
+        };
+        f.write_str(s)
+    }
+}
+
+impl FromStr for ListFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "table" => Ok(ListFormat::Table),
+            "tsv" => Ok(ListFormat::Tsv),
+            "csv" => Ok(ListFormat::Csv),
+            "json" => Ok(ListFormat::Json),
+            other => Err(format!(
+                "Unknown list format '{other}', expected table, tsv, csv or json"
+            )),
+        }
+    }
+}
+
+/// Right- vs left-aligned column. The last column is printed without trailing
+/// padding regardless, so paths with spaces don't break awk-style splitting
+/// on the preceding fields.
+#[derive(Clone, Copy)]
+pub(crate) enum ColAlign {
+    Left,
+    Right,
+}
+
+pub(crate) struct Column {
+    /// Header in the aligned table view, e.g. `PHYSICS_MATERIAL`.
+    pub header: &'static str,
+    /// Stable key used by the machine-readable formats, e.g. `physics_material`.
+    pub key: &'static str,
+    pub align: ColAlign,
+}
+
+impl Column {
+    pub(crate) const fn left(header: &'static str, key: &'static str) -> Self {
+        Column {
+            header,
+            key,
+            align: ColAlign::Left,
+        }
+    }
+
+    pub(crate) const fn right(header: &'static str, key: &'static str) -> Self {
+        Column {
+            header,
+            key,
+            align: ColAlign::Right,
+        }
+    }
+}
+
+/// A single value in a listing.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Cell {
+    Text(String),
+    Int(i64),
+    /// Rounded to `precision` decimals in the table view only.
+    Float {
+        value: f64,
+        precision: usize,
+    },
+    /// `Y`/`N` in the table view, `-` when the item has no such concept.
+    Flag(Option<bool>),
+    /// `--`-joined in the table, tsv and csv views (matching vpinball's
+    /// editor), an array in json.
+    List(Vec<String>),
+    /// Not set. Blank in the text formats, `null` in json.
+    Empty,
+}
+
+impl Cell {
+    pub(crate) fn text(s: impl Into<String>) -> Self {
+        Cell::Text(s.into())
+    }
+
+    pub(crate) fn int(n: impl Into<i64>) -> Self {
+        Cell::Int(n.into())
+    }
+
+    pub(crate) fn flag(b: bool) -> Self {
+        Cell::Flag(Some(b))
+    }
+
+    /// `Empty` for an empty string, `Text` otherwise.
+    pub(crate) fn non_empty(s: impl Into<String>) -> Self {
+        let s = s.into();
+        if s.is_empty() {
+            Cell::Empty
+        } else {
+            Cell::Text(s)
+        }
+    }
+
+    fn display(&self) -> String {
+        match self {
+            Cell::Text(s) => s.clone(),
+            Cell::Int(n) => n.to_string(),
+            Cell::Float { value, precision } => format!("{value:.precision$}"),
+            Cell::Flag(Some(true)) => "Y".to_string(),
+            Cell::Flag(Some(false)) => "N".to_string(),
+            Cell::Flag(None) => "-".to_string(),
+            Cell::List(items) => items.join("--"),
+            Cell::Empty => String::new(),
+        }
+    }
+
+    fn raw(&self) -> String {
+        match self {
+            Cell::Float { value, .. } => value.to_string(),
+            Cell::Flag(Some(b)) => b.to_string(),
+            Cell::Flag(None) => String::new(),
+            other => other.display(),
+        }
+    }
+
+    fn json(&self) -> Value {
+        match self {
+            Cell::Text(s) => Value::String(s.clone()),
+            Cell::Int(n) => Value::Number((*n).into()),
+            Cell::Float { value, .. } => Number::from_f64(*value)
+                .map(Value::Number)
+                .unwrap_or(Value::Null),
+            Cell::Flag(Some(b)) => Value::Bool(*b),
+            Cell::List(items) => Value::Array(items.iter().cloned().map(Value::String).collect()),
+            Cell::Flag(None) | Cell::Empty => Value::Null,
+        }
+    }
+}
+
+/// Print a listing in the requested format.
+pub(crate) fn print_list(
+    format: ListFormat,
+    columns: &[Column],
+    rows: &[Vec<Cell>],
+) -> io::Result<()> {
+    crate::print!("{}", render_list(format, columns, rows)?)
+}
+
+pub(crate) fn render_list(
+    format: ListFormat,
+    columns: &[Column],
+    rows: &[Vec<Cell>],
+) -> io::Result<String> {
+    for row in rows {
+        assert_eq!(row.len(), columns.len());
+    }
+    let keys: Vec<&str> = columns.iter().map(|c| c.key).collect();
+    let out = match format {
+        ListFormat::Table => {
+            let headers: Vec<&str> = columns.iter().map(|c| c.header).collect();
+            let aligns: Vec<ColAlign> = columns.iter().map(|c| c.align).collect();
+            let rows: Vec<Vec<String>> = rows
+                .iter()
+                .map(|row| row.iter().map(Cell::display).collect())
+                .collect();
+            render_aligned_table(&headers, &aligns, &rows)
+        }
+        ListFormat::Tsv => {
+            let mut out = format!("{}\n", keys.join("\t"));
+            for row in rows {
+                let cells: Vec<String> = row.iter().map(|c| tsv_escape(&c.raw())).collect();
+                out.push_str(&cells.join("\t"));
+                out.push('\n');
+            }
+            out
+        }
+        ListFormat::Csv => {
+            let mut out = format!("{}\r\n", keys.join(","));
+            for row in rows {
+                let cells: Vec<String> = row.iter().map(|c| csv_escape(&c.raw())).collect();
+                out.push_str(&cells.join(","));
+                out.push_str("\r\n");
+            }
+            out
+        }
+        ListFormat::Json => {
+            let array: Vec<Value> = rows
+                .iter()
+                .map(|row| {
+                    let object: Map<String, Value> = keys
+                        .iter()
+                        .zip(row)
+                        .map(|(key, cell)| (key.to_string(), cell.json()))
+                        .collect();
+                    Value::Object(object)
+                })
+                .collect();
+            let mut out = serde_json::to_string_pretty(&array)?;
+            out.push('\n');
+            out
+        }
+    };
+    Ok(out)
+}
+
+/// Tabs and line breaks would split a tsv record, replace them with spaces.
+fn tsv_escape(s: &str) -> String {
+    s.replace(['\t', '\r', '\n'], " ")
+}
+
+fn csv_escape(s: &str) -> String {
+    if s.contains([',', '"', '\r', '\n']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_string()
+    }
+}
+
+pub(crate) fn print_aligned_table(
+    headers: &[&str],
+    aligns: &[ColAlign],
+    rows: &[Vec<String>],
+) -> io::Result<()> {
+    crate::print!("{}", render_aligned_table(headers, aligns, rows))
+}
+
+fn render_aligned_table(headers: &[&str], aligns: &[ColAlign], rows: &[Vec<String>]) -> String {
+    assert_eq!(headers.len(), aligns.len());
+    let mut widths: Vec<usize> = headers.iter().map(|h| h.len()).collect();
+    for row in rows {
+        assert_eq!(row.len(), headers.len());
+        for (i, cell) in row.iter().enumerate() {
+            if cell.len() > widths[i] {
+                widths[i] = cell.len();
+            }
+        }
+    }
+    let mut out = String::new();
+    let mut emit = |cells: &[&str]| {
+        let last = cells.len() - 1;
+        for (i, cell) in cells.iter().enumerate() {
+            if i > 0 {
+                out.push_str("  ");
+            }
+            if i == last {
+                out.push_str(cell);
+            } else {
+                match aligns[i] {
+                    ColAlign::Left => out.push_str(&format!("{:<w$}", cell, w = widths[i])),
+                    ColAlign::Right => out.push_str(&format!("{:>w$}", cell, w = widths[i])),
+                }
+            }
+        }
+        out.push('\n');
+    };
+    emit(headers);
+    for row in rows {
+        let refs: Vec<&str> = row.iter().map(String::as_str).collect();
+        emit(&refs);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    const COLUMNS: [Column; 4] = [
+        Column::left("NAME", "name"),
+        Column::right("SIZE", "size"),
+        Column::left("METAL", "metal"),
+        Column::left("IMAGES", "images"),
+    ];
+
+    fn rows() -> Vec<Vec<Cell>> {
+        vec![
+            vec![
+                Cell::text("Plastic, red"),
+                Cell::Float {
+                    value: 0.12345,
+                    precision: 2,
+                },
+                Cell::flag(true),
+                Cell::List(vec!["a".to_string(), "b".to_string()]),
+            ],
+            vec![
+                Cell::text("Say \"hi\"\tthere"),
+                Cell::int(42),
+                Cell::Flag(None),
+                Cell::Empty,
+            ],
+        ]
+    }
+
+    #[test]
+    fn renders_table() {
+        let out = render_list(ListFormat::Table, &COLUMNS, &rows()).unwrap();
+        assert_eq!(
+            out,
+            "NAME            SIZE  METAL  IMAGES\n\
+             Plastic, red    0.12  Y      a--b\n\
+             Say \"hi\"\tthere    42  -      \n"
+        );
+    }
+
+    #[test]
+    fn renders_tsv_with_raw_values() {
+        let out = render_list(ListFormat::Tsv, &COLUMNS, &rows()).unwrap();
+        assert_eq!(
+            out,
+            "name\tsize\tmetal\timages\n\
+             Plastic, red\t0.12345\ttrue\ta--b\n\
+             Say \"hi\" there\t42\t\t\n"
+        );
+    }
+
+    #[test]
+    fn renders_csv_with_quoting() {
+        let out = render_list(ListFormat::Csv, &COLUMNS, &rows()).unwrap();
+        assert_eq!(
+            out,
+            "name,size,metal,images\r\n\
+             \"Plastic, red\",0.12345,true,a--b\r\n\
+             \"Say \"\"hi\"\"\tthere\",42,,\r\n"
+        );
+    }
+
+    #[test]
+    fn renders_json_with_typed_values() {
+        let out = render_list(ListFormat::Json, &COLUMNS, &rows()).unwrap();
+        let json: Value = serde_json::from_str(&out).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!([
+                {"name": "Plastic, red", "size": 0.12345, "metal": true, "images": ["a", "b"]},
+                {"name": "Say \"hi\"\tthere", "size": 42, "metal": null, "images": null},
+            ])
+        );
+        let keys: Vec<&String> = json[0].as_object().unwrap().keys().collect();
+        assert_eq!(keys, vec!["name", "size", "metal", "images"]);
+    }
+
+    #[test]
+    fn parses_format() {
+        assert_eq!("CSV".parse::<ListFormat>(), Ok(ListFormat::Csv));
+        assert!("xml".parse::<ListFormat>().is_err());
+    }
+}
@@ -1,12 +1,38 @@
 //! Patcher for typical standalone vbs issues
-
+//!
+//! Next to the built-in patches, users can define their own rules in toml
+//! files in `<config dir>/vpxtool/patches/`, so fixes for new standalone
+//! incompatibilities do not have to wait for a vpxtool release:
+//!
+//! ```toml
+//! [[rule]]
+//! id = "flexdmd-show"
+//! description = "FlexDMD needs Show set before Run"
+//! # only scripts matching this are patched
+//! detect = 'FlexDMD\.Run\s*=\s*True'
+//! # skip scripts that already match this, keeps patching idempotent,
+//! # required for replace actions
+//! guard = 'FlexDMD\.Show\s*='
+//! # optional, matched case-insensitively against cGameName
+//! game_names = ["tna"]
+//! action = { type = "replace", find = '(FlexDMD\.Run\s*=\s*True)', replace = "FlexDMD.Show = True : $1" }
+//! ```
+//!
+//! An `insert_class` action (`class = "Class Foo ... End Class"`, optional
+//! `before = "<marker text>"`) adds a class definition before the first line
+//! containing the marker, or at the end of the script, unless the script
+//! already contains it.
+
+use crate::indexer::extract_game_name;
+use crate::scriptfile::{read_script, rewrite_script};
 use regex::Regex;
+use serde::Deserialize;
 use std::collections::HashSet;
 use std::fmt::Display;
-use std::fs::File;
 use std::io;
-use std::io::{Read, Write};
-use std::path::Path;
+use std::path::{Path, PathBuf};
+
+const PATCH_RULES_DIR: &str = "patches";
 
 #[derive(Debug, PartialEq, Eq, Hash)]
 pub enum LineEndingsResult {
@@ -18,26 +44,207 @@ pub enum LineEndingsResult {
 pub enum PatchType {
     DropTarget,
     StandupTarget,
+    /// A user-defined [`PatchRule`].
+    Rule {
+        id: String,
+        description: String,
+    },
 }
 impl Display for PatchType {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             PatchType::DropTarget => write!(f, "DTArray fix"),
             PatchType::StandupTarget => write!(f, "STArray fix"),
+            PatchType::Rule { id, description } => write!(f, "{id} ({description})"),
         }
     }
 }
 
-pub fn patch_vbs_file(vbs_path: &Path) -> io::Result<HashSet<PatchType>> {
-    // TODO we probably need to ensure proper encoding here in stead of going for utf8
-    let mut file = File::open(vbs_path)?;
-    let mut text = String::new();
-    file.read_to_string(&mut text)?;
+/// A user-defined patch, see the module documentation for the file format.
+#[derive(Debug)]
+pub struct PatchRule {
+    pub id: String,
+    pub description: String,
+    detect: Regex,
+    guard: Option<Regex>,
+    game_names: Vec<String>,
+    action: PatchAction,
+}
+
+#[derive(Debug)]
+enum PatchAction {
+    Replace {
+        find: Regex,
+        replace: String,
+    },
+    InsertClass {
+        class: String,
+        before: Option<String>,
+    },
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PatchRuleFile {
+    #[serde(default)]
+    rule: Vec<PatchRuleToml>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PatchRuleToml {
+    id: String,
+    description: String,
+    detect: String,
+    guard: Option<String>,
+    #[serde(default)]
+    game_names: Vec<String>,
+    action: PatchActionToml,
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum PatchActionToml {
+    Replace {
+        find: String,
+        replace: String,
+    },
+    InsertClass {
+        class: String,
+        before: Option<String>,
+    },
+}
 
-    let (patched_text, applied) = patch_script(text);
+impl PatchRule {
+    fn from_toml(rule: PatchRuleToml) -> Result<Self, String> {
+        let regex = |field: &str, pattern: &str| {
+            Regex::new(pattern).map_err(|e| format!("rule {}: invalid {field} regex: {e}", rule.id))
+        };
+        let action = match &rule.action {
+            // without a guard the rule would patch its own output again
+            PatchActionToml::Replace { .. } if rule.guard.is_none() => {
+                return Err(format!(
+                    "rule {}: replace actions need a guard that matches the patched script",
+                    rule.id
+                ));
+            }
+            PatchActionToml::Replace { find, replace } => PatchAction::Replace {
+                find: regex("find", find)?,
+                replace: replace.clone(),
+            },
+            PatchActionToml::InsertClass { class, before } => PatchAction::InsertClass {
+                class: unify_line_endings(class.trim_end()),
+                before: before.clone(),
+            },
+        };
+        Ok(PatchRule {
+            detect: regex("detect", &rule.detect)?,
+            guard: rule
+                .guard
+                .as_deref()
+                .map(|guard| regex("guard", guard))
+                .transpose()?,
+            game_names: rule.game_names,
+            action,
+            id: rule.id,
+            description: rule.description,
+        })
+    }
 
-    let mut file = File::create(vbs_path)?;
-    file.write_all(patched_text.as_bytes())?;
+    fn applies_to(&self, script: &str, game_name: Option<&str>) -> bool {
+        if !self.game_names.is_empty()
+            && !game_name.is_some_and(|game_name| {
+                self.game_names
+                    .iter()
+                    .any(|name| name.eq_ignore_ascii_case(game_name))
+            })
+        {
+            return false;
+        }
+        self.detect.is_match(script)
+            && !self
+                .guard
+                .as_ref()
+                .is_some_and(|guard| guard.is_match(script))
+    }
+
+    fn apply(&self, script: String) -> String {
+        match &self.action {
+            PatchAction::Replace { find, replace } => {
+                find.replace_all(&script, replace.as_str()).to_string()
+            }
+            PatchAction::InsertClass { class, .. } if script.contains(class.as_str()) => script,
+            PatchAction::InsertClass { class, before } => {
+                match before
+                    .as_ref()
+                    .and_then(|before| script.find(before.as_str()))
+                {
+                    Some(pos) => {
+                        let line_start = script[..pos].rfind('\n').map_or(0, |i| i + 1);
+                        format!(
+                            "{}{class}\r\n{}",
+                            &script[..line_start],
+                            &script[line_start..]
+                        )
+                    }
+                    None => format!("{script}\r\n{class}"),
+                }
+            }
+        }
+    }
+}
+
+/// `<config dir>/vpxtool/patches`, next to the config file.
+pub fn default_patch_rules_dir() -> PathBuf {
+    dirs::config_dir()
+        .unwrap()
+        .join("vpxtool")
+        .join(PATCH_RULES_DIR)
+}
+
+/// Read the rules of every `*.toml` file in `dir`, in file name order. A
+/// missing folder means no rules.
+pub fn load_patch_rules(dir: &Path) -> io::Result<Vec<PatchRule>> {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+    let mut files: Vec<PathBuf> = entries
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<io::Result<_>>()?;
+    files.retain(|path| path.extension().is_some_and(|ext| ext == "toml"));
+    files.sort();
+
+    let mut rules: Vec<PatchRule> = Vec::new();
+    for file in files {
+        let invalid = |message: String| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Invalid patch rules in {}: {message}", file.display()),
+            )
+        };
+        let text = std::fs::read_to_string(&file)?;
+        let rule_file: PatchRuleFile = toml::from_str(&text).map_err(|e| invalid(e.to_string()))?;
+        for rule in rule_file.rule {
+            let rule = PatchRule::from_toml(rule).map_err(invalid)?;
+            if rules.iter().any(|r| r.id == rule.id) {
+                return Err(invalid(format!("duplicate rule id {}", rule.id)));
+            }
+            rules.push(rule);
+        }
+    }
+    Ok(rules)
+}
+
+/// Patches the script in place, keeping its encoding. The unpatched script is
+/// kept as `<table>.vbs.orig`, see [`rewrite_script`].
+pub fn patch_vbs_file(vbs_path: &Path, rules: &[PatchRule]) -> io::Result<HashSet<PatchType>> {
+    let (text, encoding) = read_script(vbs_path)?;
+    let (patched_text, applied) = patch_script_with_rules(text.clone(), rules);
+    if patched_text != text {
+        rewrite_script(vbs_path, &patched_text, encoding)?;
+    }
     Ok(applied)
 }
 
@@ -48,17 +255,10 @@ pub fn patch_vbs_file(vbs_path: &Path) -> io::Result<HashSet<PatchType>> {
  * One example is [Aztec (Williams 1976) 1.3 by jipeji16](https://www.vpforums.org/index.php?app=downloads&showfile=15768)
  */
 pub fn unify_line_endings_vbs_file(vbs_path: &Path) -> io::Result<LineEndingsResult> {
-    // TODO we probably need to ensure proper encoding here in stead of going for utf8
-    let mut file = File::open(vbs_path)?;
-    let mut text = String::new();
-    file.read_to_string(&mut text)?;
-
+    let (text, encoding) = read_script(vbs_path)?;
     let patched_text = unify_line_endings(&text);
-
-    let mut file = File::create(vbs_path)?;
-    file.write_all(patched_text.as_bytes())?;
-
     if text != patched_text {
+        rewrite_script(vbs_path, &patched_text, encoding)?;
         Ok(LineEndingsResult::Unified)
     } else {
         Ok(LineEndingsResult::NoChanges)
@@ -85,6 +285,29 @@ pub fn patch_script(script: String) -> (String, HashSet<PatchType>) {
     (patched_script, applied_patches)
 }
 
+/// [`patch_script`] followed by the user-defined rules, in order. A rule is
+/// only reported when it changed the script.
+pub fn patch_script_with_rules(
+    script: String,
+    rules: &[PatchRule],
+) -> (String, HashSet<PatchType>) {
+    let (mut patched_script, mut applied_patches) = patch_script(script);
+    let game_name = extract_game_name(&patched_script);
+    for rule in rules {
+        if rule.applies_to(&patched_script, game_name.as_deref()) {
+            let patched = rule.apply(patched_script.clone());
+            if patched != patched_script {
+                applied_patches.insert(PatchType::Rule {
+                    id: rule.id.clone(),
+                    description: rule.description.clone(),
+                });
+                patched_script = patched;
+            }
+        }
+    }
+    (patched_script, applied_patches)
+}
+
 fn unify_line_endings(script: &str) -> String {
     // first replace all \r\n with \n
     // then replace all \r with \n (this is the main issue, as some files have mixed \r\n and \r)
@@ -272,6 +495,121 @@ End Class
         assert_eq!(expected, result);
     }
 
+    fn write_rules(dir: &Path, file_name: &str, toml: &str) {
+        std::fs::create_dir_all(dir).unwrap();
+        std::fs::write(dir.join(file_name), toml).unwrap();
+    }
+
+    #[test]
+    fn test_user_rules() {
+        let dir = testdir::testdir!().join("patches");
+        write_rules(
+            &dir,
+            "a.toml",
+            r#"
+[[rule]]
+id = "flexdmd-show"
+description = "Show FlexDMD before running it"
+detect = 'FlexDMD\.Run = True'
+guard = 'FlexDMD\.Show = True'
+action = { type = "replace", find = '(FlexDMD\.Run = True)', replace = "FlexDMD.Show = True : $1" }
+
+[[rule]]
+id = "other-game"
+description = "Only for another table"
+detect = "FlexDMD"
+guard = "Broken"
+game_names = ["afm"]
+action = { type = "replace", find = "FlexDMD", replace = "Broken" }
+"#,
+        );
+        write_rules(
+            &dir,
+            "b.toml",
+            r#"
+[[rule]]
+id = "helper-class"
+description = "Add a helper class"
+detect = "UseHelper"
+game_names = ["TNA"]
+action = { type = "insert_class", class = "Class Helper\nEnd Class\n", before = "Helper" }
+"#,
+        );
+        write_rules(&dir, "notes.txt", "not a rule");
+        let rules = load_patch_rules(&dir).unwrap();
+        assert_eq!(
+            rules.iter().map(|r| r.id.as_str()).collect::<Vec<_>>(),
+            vec!["flexdmd-show", "other-game", "helper-class"]
+        );
+
+        let script =
+            "Const cGameName = \"tna\"\r\n' helpers\r\nUseHelper\r\nFlexDMD.Run = True\r\n";
+        let (patched, applied) = patch_script_with_rules(script.to_string(), &rules);
+        let mut applied: Vec<String> = applied.iter().map(PatchType::to_string).collect();
+        applied.sort();
+        assert_eq!(
+            applied,
+            vec![
+                "flexdmd-show (Show FlexDMD before running it)",
+                "helper-class (Add a helper class)",
+            ]
+        );
+        assert_eq!(
+            patched,
+            "Const cGameName = \"tna\"\r\n' helpers\r\nClass Helper\r\nEnd Class\r\nUseHelper\r\nFlexDMD.Show = True : FlexDMD.Run = True\r\n"
+        );
+
+        // patching again changes nothing
+        let (again, applied) = patch_script_with_rules(patched.clone(), &rules);
+        assert_eq!(again, patched);
+        assert!(applied.is_empty());
+    }
+
+    #[test]
+    fn test_invalid_user_rules() {
+        let dir = testdir::testdir!();
+        assert_eq!(load_patch_rules(&dir.join("missing")).unwrap().len(), 0);
+
+        let rule = |id: &str, detect: &str| {
+            format!(
+                "[[rule]]\nid = \"{id}\"\ndescription = \"\"\ndetect = '{detect}'\nguard = \"b\"\naction = {{ type = \"replace\", find = \"a\", replace = \"b\" }}\n"
+            )
+        };
+        let bad_regex = dir.join("bad_regex");
+        write_rules(&bad_regex, "rules.toml", &rule("broken", "(unclosed"));
+        let err = load_patch_rules(&bad_regex).unwrap_err().to_string();
+        assert!(err.contains("rule broken: invalid detect regex"), "{err}");
+
+        let duplicate = dir.join("duplicate");
+        write_rules(&duplicate, "a.toml", &rule("same", "x"));
+        write_rules(&duplicate, "b.toml", &rule("same", "y"));
+        let err = load_patch_rules(&duplicate).unwrap_err().to_string();
+        assert!(err.ends_with("duplicate rule id same"), "{err}");
+
+        let no_guard = dir.join("no_guard");
+        write_rules(
+            &no_guard,
+            "rules.toml",
+            &rule("unguarded", "x").replace("guard = \"b\"\n", ""),
+        );
+        let err = load_patch_rules(&no_guard).unwrap_err().to_string();
+        assert!(
+            err.contains("rule unguarded: replace actions need a guard"),
+            "{err}"
+        );
+
+        let unknown_field = dir.join("unknown_field");
+        write_rules(
+            &unknown_field,
+            "rules.toml",
+            &format!("{}detetc = \"typo\"\n", rule("typo", "x")),
+        );
+        assert_eq!(
+            load_patch_rules(&unknown_field).unwrap_err().kind(),
+            io::ErrorKind::InvalidData
+        );
+    }
+
     #[test]
     fn test_vbs_patch() {
         let script = r#"
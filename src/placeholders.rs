@@ -0,0 +1,193 @@
+//! `{placeholder}` substitution for launch template arguments, env values and
+//! `pre_launch`/`post_launch` hooks, so per-table wrapper scripts are no
+//! longer needed.
+//!
+//! Unknown placeholders are an error, that catches typos before vpinball is
+//! started. A known placeholder without a value for the table (e.g. `{rom}`
+//! for an EM table) becomes an empty string. Use `{{` and `}}` for literal
+//! braces.
+
+use crate::indexer::IndexedTable;
+use std::path::Path;
+
+pub const PLACEHOLDERS: [&str; 5] = ["table", "table_dir", "stem", "rom", "b2s"];
+
+/// The values placeholders are replaced with when launching a table.
+#[derive(Debug, Default, PartialEq)]
+pub struct LaunchContext {
+    /// `{table}`, path of the vpx file.
+    pub table: String,
+    /// `{table_dir}`, directory containing the vpx file.
+    pub table_dir: String,
+    /// `{stem}`, vpx file name without extension.
+    pub stem: String,
+    /// `{rom}`, the PinMAME game name (`cGameName`).
+    pub rom: Option<String>,
+    /// `{b2s}`, path of the directb2s backglass.
+    pub b2s: Option<String>,
+}
+
+impl LaunchContext {
+    /// Context for a bare vpx path, `{rom}` and `{b2s}` stay empty.
+    pub fn for_path(vpx_path: &Path) -> Self {
+        LaunchContext {
+            table: vpx_path.display().to_string(),
+            table_dir: vpx_path
+                .parent()
+                .map(|p| p.display().to_string())
+                .unwrap_or_default(),
+            stem: vpx_path
+                .file_stem()
+                .map(|s| s.to_string_lossy().to_string())
+                .unwrap_or_default(),
+            rom: None,
+            b2s: None,
+        }
+    }
+
+    pub fn for_table(table: &IndexedTable) -> Self {
+        LaunchContext {
+            rom: table.game_name.clone(),
+            b2s: table.b2s_path.as_ref().map(|p| p.display().to_string()),
+            ..LaunchContext::for_path(&table.path)
+        }
+    }
+
+    fn value(&self, name: &str) -> Option<&str> {
+        match name {
+            "table" => Some(&self.table),
+            "table_dir" => Some(&self.table_dir),
+            "stem" => Some(&self.stem),
+            "rom" => Some(self.rom.as_deref().unwrap_or_default()),
+            "b2s" => Some(self.b2s.as_deref().unwrap_or_default()),
+            _ => None,
+        }
+    }
+
+    /// Replace all placeholders in `template`.
+    pub fn substitute(&self, template: &str) -> Result<String, String> {
+        let mut result = String::with_capacity(template.len());
+        for token in tokenize(template)? {
+            match token {
+                Token::Text(text) => result.push_str(text),
+                Token::Placeholder(name) => {
+                    let value = self.value(name).ok_or_else(|| {
+                        format!(
+                            "Unknown placeholder {{{name}}} in \"{template}\", expected one of {}",
+                            PLACEHOLDERS.map(|p| format!("{{{p}}}")).join(", ")
+                        )
+                    })?;
+                    result.push_str(value);
+                }
+            }
+        }
+        Ok(result)
+    }
+
+    /// Substitute every element, e.g. a command line.
+    pub fn substitute_all(&self, templates: &[String]) -> Result<Vec<String>, String> {
+        templates.iter().map(|t| self.substitute(t)).collect()
+    }
+}
+
+/// A piece of a template, literal text with `{{` and `}}` already unescaped
+/// or the name of a placeholder.
+#[derive(Debug, PartialEq)]
+enum Token<'a> {
+    Text(&'a str),
+    Placeholder(&'a str),
+}
+
+fn tokenize(template: &str) -> Result<Vec<Token<'_>>, String> {
+    let mut tokens = Vec::new();
+    let mut rest = template;
+    while let Some(index) = rest.find(['{', '}']) {
+        tokens.push(Token::Text(&rest[..index]));
+        let tail = &rest[index..];
+        if tail.starts_with("{{") || tail.starts_with("}}") {
+            tokens.push(Token::Text(&tail[..1]));
+            rest = &tail[2..];
+        } else if tail.starts_with('}') {
+            return Err(format!("Unmatched '}}' in \"{template}\""));
+        } else {
+            let Some(end) = tail.find('}') else {
+                return Err(format!("Unclosed '{{' in \"{template}\""));
+            };
+            tokens.push(Token::Placeholder(&tail[1..end]));
+            rest = &tail[end + 1..];
+        }
+    }
+    tokens.push(Token::Text(rest));
+    Ok(tokens)
+}
+
+/// True if any of the arguments positions the table itself, in which case
+/// `-play <table>` is not appended. An escaped `{{table}}` does not count, an
+/// invalid template neither, substituting reports that.
+pub fn mentions_table(arguments: &[String]) -> bool {
+    arguments
+        .iter()
+        .any(|a| tokenize(a).is_ok_and(|tokens| tokens.contains(&Token::Placeholder("table"))))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use std::path::PathBuf;
+
+    fn context() -> LaunchContext {
+        LaunchContext {
+            rom: Some("tz_94h".to_string()),
+            ..LaunchContext::for_path(&PathBuf::from("/tables/Twilight Zone.vpx"))
+        }
+    }
+
+    #[test]
+    fn substitutes_placeholders() {
+        let context = context();
+        assert_eq!(
+            context.substitute("{table_dir}/{stem}.ini").unwrap(),
+            "/tables/Twilight Zone.ini"
+        );
+        assert_eq!(
+            context
+                .substitute("cp nvram/{rom}.nv backup/{rom}.nv")
+                .unwrap(),
+            "cp nvram/tz_94h.nv backup/tz_94h.nv"
+        );
+        assert_eq!(context.substitute("b2s={b2s}").unwrap(), "b2s=");
+        assert_eq!(
+            context.substitute("{{literal}} {table}").unwrap(),
+            "{literal} /tables/Twilight Zone.vpx"
+        );
+    }
+
+    #[test]
+    fn rejects_bad_templates() {
+        let context = context();
+        assert_eq!(
+            context.substitute("{tabel}"),
+            Err(
+                "Unknown placeholder {tabel} in \"{tabel}\", expected one of {table}, \
+                 {table_dir}, {stem}, {rom}, {b2s}"
+                    .to_string()
+            )
+        );
+        assert!(context.substitute("{table").is_err());
+        assert!(context.substitute("table}").is_err());
+    }
+
+    #[test]
+    fn detects_table_argument() {
+        assert!(mentions_table(&[
+            "-play".to_string(),
+            "{table}".to_string()
+        ]));
+        assert!(!mentions_table(&[
+            "-Ini".to_string(),
+            "{table_dir}/x.ini".to_string()
+        ]));
+        assert!(!mentions_table(&["--title={{table}}".to_string()]));
+    }
+}
@@ -0,0 +1,276 @@
+//! `vpxtool prune`: find and remove images, materials and sounds nothing in
+//! the table refers to.
+//!
+//! References come from the gameitems, the table settings (gamedata) and the
+//! string literals in the script. Scripts often build asset names at runtime,
+//! like `PlaySound "fx_rubber" & n`, so a literal that is concatenated with
+//! `&` keeps every asset whose name starts with it.
+
+use crate::scriptlint::{script_lines, strip_comment};
+use regex::Regex;
+use std::collections::HashSet;
+use std::fmt::{Display, Formatter};
+use std::sync::LazyLock;
+use vpin::vpx::VPX;
+use vpin::vpx::gameitem::GameItemEnum;
+use vpin::vpx::image::ImageData;
+
+static STRING_LITERAL_REGEX: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#""((?:[^"]|"")*)"(\s*&)?"#).unwrap());
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum AssetKind {
+    Image,
+    Material,
+    Sound,
+}
+
+impl Display for AssetKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AssetKind::Image => write!(f, "image"),
+            AssetKind::Material => write!(f, "material"),
+            AssetKind::Sound => write!(f, "sound"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnusedAsset {
+    pub kind: AssetKind,
+    pub name: String,
+    /// Size of the stored data, 0 for materials.
+    pub size: u64,
+}
+
+/// String literals in the script, lowercase. Literals followed by `&` are
+/// kept as prefixes.
+#[derive(Debug, Default)]
+struct ScriptLiterals {
+    exact: HashSet<String>,
+    prefixes: Vec<String>,
+}
+
+impl ScriptLiterals {
+    fn collect(scripts: &[&str]) -> Self {
+        let mut literals = ScriptLiterals::default();
+        for line in scripts.iter().flat_map(|script| script_lines(script)) {
+            for caps in STRING_LITERAL_REGEX.captures_iter(strip_comment(line)) {
+                let literal = caps[1].replace("\"\"", "\"").to_lowercase();
+                if caps.get(2).is_some() && !literal.is_empty() {
+                    literals.prefixes.push(literal.clone());
+                }
+                literals.exact.insert(literal);
+            }
+        }
+        literals
+    }
+
+    fn mentions(&self, lower_name: &str) -> bool {
+        self.exact.contains(lower_name)
+            || self
+                .prefixes
+                .iter()
+                .any(|prefix| lower_name.starts_with(prefix.as_str()))
+    }
+}
+
+fn image_size(image: &ImageData) -> u64 {
+    if let Some(jpeg) = &image.jpeg {
+        jpeg.data.len() as u64
+    } else if let Some(bits) = &image.bits {
+        bits.lzw_compressed_data.len() as u64
+    } else {
+        0
+    }
+}
+
+/// Images, materials and sounds that are not referenced by any gameitem, the
+/// table settings or the given scripts, ordered by kind. Pass the embedded
+/// script and the sidecar vbs if there is one. Names are compared
+/// case-insensitively like vpinball does.
+pub fn find_unused_assets(vpx: &VPX, scripts: &[&str]) -> Vec<UnusedAsset> {
+    let gamedata = &vpx.gamedata;
+    let literals = ScriptLiterals::collect(scripts);
+
+    let mut images: HashSet<String> = vpx
+        .gameitems
+        .iter()
+        .flat_map(|item| item.images())
+        .chain([
+            gamedata.image.as_str(),
+            gamedata.backglass_image_full_desktop.as_str(),
+            gamedata.backglass_image_full_fullscreen.as_str(),
+            gamedata.image_color_grade.as_str(),
+            gamedata.ball_image.as_str(),
+            gamedata.ball_image_front.as_str(),
+            gamedata.screen_shot.as_str(),
+        ])
+        .chain(gamedata.backglass_image_full_single_screen.as_deref())
+        .chain(gamedata.env_image.as_deref())
+        .map(str::to_lowercase)
+        .collect();
+    images.remove("");
+
+    let materials: HashSet<String> = vpx
+        .gameitems
+        .iter()
+        .flat_map(|item| item.materials().into_iter().chain(item.physics_material()))
+        .chain([gamedata.playfield_material.as_str()])
+        .map(str::to_lowercase)
+        .collect();
+
+    let sounds: HashSet<String> = vpx
+        .gameitems
+        .iter()
+        .filter_map(|item| match item {
+            GameItemEnum::Reel(reel) => Some(reel.sound.to_lowercase()),
+            _ => None,
+        })
+        .collect();
+
+    let is_unused = |used: &HashSet<String>, name: &str| {
+        let lower = name.to_lowercase();
+        !used.contains(&lower) && !literals.mentions(&lower)
+    };
+
+    let mut unused = Vec::new();
+    for image in &vpx.images {
+        if is_unused(&images, &image.name) {
+            unused.push(UnusedAsset {
+                kind: AssetKind::Image,
+                name: image.name.clone(),
+                size: image_size(image),
+            });
+        }
+    }
+    let material_names: Vec<&str> = match &gamedata.materials {
+        Some(materials) => materials.iter().map(|m| m.name.as_str()).collect(),
+        None => gamedata
+            .materials_old
+            .iter()
+            .map(|m| m.name.as_str())
+            .collect(),
+    };
+    for name in material_names {
+        if is_unused(&materials, name) {
+            unused.push(UnusedAsset {
+                kind: AssetKind::Material,
+                name: name.to_string(),
+                size: 0,
+            });
+        }
+    }
+    for sound in &vpx.sounds {
+        if is_unused(&sounds, &sound.name) {
+            unused.push(UnusedAsset {
+                kind: AssetKind::Sound,
+                name: sound.name.clone(),
+                size: sound.data.len() as u64,
+            });
+        }
+    }
+    unused
+}
+
+/// Removes the given assets from `vpx`, keeping the counts in the gamedata
+/// in sync.
+pub fn remove_assets(vpx: &mut VPX, assets: &[UnusedAsset]) {
+    let names = |kind: AssetKind| -> HashSet<&str> {
+        assets
+            .iter()
+            .filter(|a| a.kind == kind)
+            .map(|a| a.name.as_str())
+            .collect()
+    };
+    let images = names(AssetKind::Image);
+    let materials = names(AssetKind::Material);
+    let sounds = names(AssetKind::Sound);
+
+    vpx.images
+        .retain(|image| !images.contains(image.name.as_str()));
+    vpx.gamedata.images_size = vpx.images.len() as u32;
+
+    vpx.sounds
+        .retain(|sound| !sounds.contains(sound.name.as_str()));
+    vpx.gamedata.sounds_size = vpx.sounds.len() as u32;
+
+    let gamedata = &mut vpx.gamedata;
+    // the old material records and their physics counterparts are parallel
+    // lists counted by `materials_size`
+    let keep: Vec<bool> = gamedata
+        .materials_old
+        .iter()
+        .map(|m| !materials.contains(m.name.as_str()))
+        .collect();
+    gamedata
+        .materials_old
+        .retain(|m| !materials.contains(m.name.as_str()));
+    if let Some(physics) = &mut gamedata.materials_physics_old {
+        let mut keep = keep.iter();
+        physics.retain(|_| keep.next().copied().unwrap_or(true));
+    }
+    gamedata.materials_size = gamedata.materials_old.len() as u32;
+    if let Some(new_materials) = &mut gamedata.materials {
+        new_materials.retain(|m| !materials.contains(m.name.as_str()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use std::path::Path;
+    use vpin::vpx;
+
+    #[test]
+    fn collects_script_literals() {
+        let literals = ScriptLiterals::collect(&[
+            "PlaySound \"fx_Flipper\" ' \"commented\"\r\nPlaySound \"fx_rubber\" & Int(Rnd*3)+1",
+            "x = \"say \"\"hi\"\"\"",
+        ]);
+        assert!(literals.mentions("fx_flipper"));
+        assert!(literals.mentions("fx_rubber2"));
+        assert!(literals.mentions("say \"hi\""));
+        assert!(!literals.mentions("fx_flipper2"));
+        assert!(!literals.mentions("commented"));
+    }
+
+    #[test]
+    fn finds_and_removes_unused_assets() {
+        let mut vpx = vpx::read(Path::new("testdata/completely_blank_table_10_7_4.vpx")).unwrap();
+        let code = vpx.gamedata.code.string.clone();
+        let scripts = [code.as_str()];
+        let unused = find_unused_assets(&vpx, &scripts);
+        assert!(!unused.is_empty());
+        for asset in &unused {
+            let lower = asset.name.to_lowercase();
+            assert!(
+                !vpx.gameitems.iter().any(|i| i
+                    .images()
+                    .into_iter()
+                    .chain(i.materials())
+                    .any(|n| n.to_lowercase() == lower)),
+                "{} is used",
+                asset.name
+            );
+        }
+
+        let images_before = vpx.images.len();
+        remove_assets(&mut vpx, &unused);
+        let removed_images = unused.iter().filter(|a| a.kind == AssetKind::Image).count();
+        assert_eq!(vpx.images.len(), images_before - removed_images);
+        assert_eq!(vpx.gamedata.images_size as usize, vpx.images.len());
+        assert_eq!(
+            vpx.gamedata.materials_size as usize,
+            vpx.gamedata.materials_old.len()
+        );
+
+        // the result is a valid vpx with nothing left to prune
+        let bytes = vpx::to_bytes(&vpx).unwrap();
+        let pruned = vpx::from_bytes(&bytes).unwrap();
+        assert_eq!(pruned.images.len(), vpx.images.len());
+        assert_eq!(pruned.sounds.len(), vpx.sounds.len());
+        assert_eq!(find_unused_assets(&pruned, &scripts), vec![]);
+    }
+}
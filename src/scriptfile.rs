@@ -0,0 +1,249 @@
+//! Reading and writing sidecar `.vbs` files without touching their encoding.
+//!
+//! Visual Pinball itself does not care how a script is encoded and a lot of
+//! older tables ship Windows-1252 scripts, some editors add a BOM or save as
+//! UTF-16. A script is decoded once and written back in the same encoding so
+//! that everything we did not patch stays byte for byte the same.
+
+use crate::atomicwrite::atomic_write;
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+const UTF8_BOM: [u8; 3] = [0xEF, 0xBB, 0xBF];
+const UTF16LE_BOM: [u8; 2] = [0xFF, 0xFE];
+const UTF16BE_BOM: [u8; 2] = [0xFE, 0xFF];
+
+/// Windows-1252 bytes 0x80..=0x9F. The five bytes that are undefined in
+/// Windows-1252 map to the C1 control with the same value, like browsers do,
+/// so any byte sequence round-trips.
+const WINDOWS_1252_HIGH: [char; 32] = [
+    '\u{20AC}', '\u{0081}', '\u{201A}', '\u{0192}', '\u{201E}', '\u{2026}', '\u{2020}', '\u{2021}',
+    '\u{02C6}', '\u{2030}', '\u{0160}', '\u{2039}', '\u{0152}', '\u{008D}', '\u{017D}', '\u{008F}',
+    '\u{0090}', '\u{2018}', '\u{2019}', '\u{201C}', '\u{201D}', '\u{2022}', '\u{2013}', '\u{2014}',
+    '\u{02DC}', '\u{2122}', '\u{0161}', '\u{203A}', '\u{0153}', '\u{009D}', '\u{017E}', '\u{0178}',
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScriptEncoding {
+    Utf8,
+    Utf8Bom,
+    Utf16Le,
+    Utf16Be,
+    /// Anything that is not valid UTF-8 and has no BOM.
+    Windows1252,
+}
+
+/// Detects the encoding from the BOM, falling back to UTF-8 when the bytes
+/// are valid UTF-8 and Windows-1252 otherwise.
+pub fn decode_script(bytes: &[u8]) -> io::Result<(String, ScriptEncoding)> {
+    if let Some(rest) = bytes.strip_prefix(&UTF8_BOM) {
+        let text = String::from_utf8(rest.to_vec())
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        return Ok((text, ScriptEncoding::Utf8Bom));
+    }
+    if let Some(rest) = bytes.strip_prefix(&UTF16LE_BOM) {
+        return Ok((
+            decode_utf16(rest, u16::from_le_bytes)?,
+            ScriptEncoding::Utf16Le,
+        ));
+    }
+    if let Some(rest) = bytes.strip_prefix(&UTF16BE_BOM) {
+        return Ok((
+            decode_utf16(rest, u16::from_be_bytes)?,
+            ScriptEncoding::Utf16Be,
+        ));
+    }
+    match String::from_utf8(bytes.to_vec()) {
+        Ok(text) => Ok((text, ScriptEncoding::Utf8)),
+        Err(_) => {
+            let text = bytes
+                .iter()
+                .map(|&b| match b {
+                    0x80..=0x9F => WINDOWS_1252_HIGH[(b - 0x80) as usize],
+                    _ => b as char,
+                })
+                .collect();
+            Ok((text, ScriptEncoding::Windows1252))
+        }
+    }
+}
+
+/// Fails if `text` contains characters that can not be represented, which
+/// can only happen for Windows-1252.
+pub fn encode_script(text: &str, encoding: ScriptEncoding) -> io::Result<Vec<u8>> {
+    let bytes = match encoding {
+        ScriptEncoding::Utf8 => text.as_bytes().to_vec(),
+        ScriptEncoding::Utf8Bom => [UTF8_BOM.as_slice(), text.as_bytes()].concat(),
+        ScriptEncoding::Utf16Le => UTF16LE_BOM
+            .into_iter()
+            .chain(text.encode_utf16().flat_map(u16::to_le_bytes))
+            .collect(),
+        ScriptEncoding::Utf16Be => UTF16BE_BOM
+            .into_iter()
+            .chain(text.encode_utf16().flat_map(u16::to_be_bytes))
+            .collect(),
+        ScriptEncoding::Windows1252 => text
+            .chars()
+            .map(|c| match c as u32 {
+                code @ (0..=0x7F | 0xA0..=0xFF) => Ok(code as u8),
+                _ => WINDOWS_1252_HIGH
+                    .iter()
+                    .position(|&high| high == c)
+                    .map(|i| 0x80 + i as u8)
+                    .ok_or_else(|| {
+                        io::Error::new(
+                            io::ErrorKind::InvalidData,
+                            format!("Character {c:?} can not be written as Windows-1252"),
+                        )
+                    }),
+            })
+            .collect::<io::Result<Vec<u8>>>()?,
+    };
+    Ok(bytes)
+}
+
+fn decode_utf16(bytes: &[u8], to_u16: fn([u8; 2]) -> u16) -> io::Result<String> {
+    if !bytes.len().is_multiple_of(2) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "UTF-16 script with an odd number of bytes",
+        ));
+    }
+    let units: Vec<u16> = bytes
+        .chunks_exact(2)
+        .map(|pair| to_u16([pair[0], pair[1]]))
+        .collect();
+    String::from_utf16(&units).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+pub fn read_script(path: &Path) -> io::Result<(String, ScriptEncoding)> {
+    decode_script(&fs::read(path)?)
+}
+
+/// The backup `script patch` keeps of the script as it was before the first
+/// patch, `<table>.vbs.orig`.
+pub fn script_backup_path(vbs_path: &Path) -> PathBuf {
+    let mut path = vbs_path.as_os_str().to_owned();
+    path.push(".orig");
+    PathBuf::from(path)
+}
+
+/// Replaces the script with `text` in its original encoding. Before the
+/// first change the original file is kept as [`script_backup_path`]. An
+/// existing backup is left alone while the script is still the one we wrote
+/// last, so it holds the unpatched script. A script that was edited or
+/// extracted again since then gets a fresh backup.
+pub fn rewrite_script(vbs_path: &Path, text: &str, encoding: ScriptEncoding) -> io::Result<()> {
+    let bytes = encode_script(text, encoding)?;
+    let backup_path = script_backup_path(vbs_path);
+    if !backup_path.exists() || !same_modified(vbs_path, &backup_path)? {
+        let original = fs::read(vbs_path)?;
+        atomic_write(&backup_path, |file| file.write_all(&original))?;
+    }
+    atomic_write(vbs_path, |file| file.write_all(&bytes))?;
+    // the backup gets the modification time of what we wrote, any later
+    // change to the script makes them differ
+    let modified = fs::metadata(vbs_path)?.modified()?;
+    File::options()
+        .write(true)
+        .open(&backup_path)?
+        .set_modified(modified)
+}
+
+fn same_modified(a: &Path, b: &Path) -> io::Result<bool> {
+    Ok(fs::metadata(a)?.modified()? == fs::metadata(b)?.modified()?)
+}
+
+/// Restores the script from its backup, removing the backup.
+pub fn restore_script_backup(vbs_path: &Path) -> io::Result<()> {
+    let backup_path = script_backup_path(vbs_path);
+    if !backup_path.exists() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("No backup found at {}", backup_path.display()),
+        ));
+    }
+    fs::rename(&backup_path, vbs_path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn round_trips_all_encodings() {
+        let text = "' café – “quoted” €\r\nDim x\r\n";
+        for encoding in [
+            ScriptEncoding::Utf8,
+            ScriptEncoding::Utf8Bom,
+            ScriptEncoding::Utf16Le,
+            ScriptEncoding::Utf16Be,
+            ScriptEncoding::Windows1252,
+        ] {
+            let bytes = encode_script(text, encoding).unwrap();
+            assert_eq!(decode_script(&bytes).unwrap(), (text.to_string(), encoding));
+        }
+    }
+
+    #[test]
+    fn windows_1252_is_byte_faithful() {
+        let bytes: Vec<u8> = (0..=255).collect();
+        let (text, encoding) = decode_script(&bytes).unwrap();
+        assert_eq!(encoding, ScriptEncoding::Windows1252);
+        assert_eq!(text.chars().nth(0x80), Some('€'));
+        assert_eq!(encode_script(&text, encoding).unwrap(), bytes);
+        assert!(encode_script("日本", ScriptEncoding::Windows1252).is_err());
+    }
+
+    #[test]
+    fn rewrite_keeps_first_backup() {
+        let dir = testdir::testdir!();
+        let vbs_path = dir.join("table.vbs");
+        fs::write(&vbs_path, b"caf\xE9 1").unwrap();
+
+        let (text, encoding) = read_script(&vbs_path).unwrap();
+        rewrite_script(&vbs_path, &text.replace('1', "2"), encoding).unwrap();
+        rewrite_script(&vbs_path, &text.replace('1', "3"), encoding).unwrap();
+        assert_eq!(fs::read(&vbs_path).unwrap(), b"caf\xE9 3");
+        assert_eq!(
+            fs::read(script_backup_path(&vbs_path)).unwrap(),
+            b"caf\xE9 1"
+        );
+
+        restore_script_backup(&vbs_path).unwrap();
+        assert_eq!(fs::read(&vbs_path).unwrap(), b"caf\xE9 1");
+        assert!(!script_backup_path(&vbs_path).exists());
+        assert!(restore_script_backup(&vbs_path).is_err());
+    }
+
+    #[test]
+    fn rewrite_after_edit_refreshes_backup() {
+        let dir = testdir::testdir!();
+        let vbs_path = dir.join("table.vbs");
+        fs::write(&vbs_path, "x = 1").unwrap();
+        rewrite_script(&vbs_path, "x = 2", ScriptEncoding::Utf8).unwrap();
+
+        // edited by hand after patching
+        fs::write(&vbs_path, "x = 2 : y = 1").unwrap();
+        let later = fs::metadata(&vbs_path).unwrap().modified().unwrap()
+            + std::time::Duration::from_secs(60);
+        File::options()
+            .write(true)
+            .open(&vbs_path)
+            .unwrap()
+            .set_modified(later)
+            .unwrap();
+
+        rewrite_script(&vbs_path, "x = 2 : y = 2", ScriptEncoding::Utf8).unwrap();
+        assert_eq!(
+            fs::read_to_string(script_backup_path(&vbs_path)).unwrap(),
+            "x = 2 : y = 1"
+        );
+        restore_script_backup(&vbs_path).unwrap();
+        assert_eq!(fs::read_to_string(&vbs_path).unwrap(), "x = 2 : y = 1");
+    }
+}
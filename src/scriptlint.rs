@@ -0,0 +1,328 @@
+//! `vpxtool script lint`: a read-only check of table scripts for constructs
+//! that break on vpinball standalone (Linux, macOS, mobile), so a library can
+//! be triaged before anything gets patched.
+//!
+//! Comments and string contents are not matched against, except for the
+//! ProgID given to `CreateObject` and the file name given to `ExecuteGlobal`.
+
+use regex::Regex;
+use serde::Serialize;
+use std::fmt::{Display, Formatter};
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+
+/// COM objects that only exist on Windows, lowercase ProgIDs.
+const WINDOWS_ONLY_COM_OBJECTS: [&str; 12] = [
+    "adodb.connection",
+    "adodb.stream",
+    "excel.application",
+    "microsoft.xmlhttp",
+    "msxml2.domdocument",
+    "msxml2.serverxmlhttp",
+    "msxml2.xmlhttp",
+    "sapi.spvoice",
+    "shell.application",
+    "wmplayer.ocx",
+    "wscript.network",
+    "word.application",
+];
+
+static CREATE_OBJECT_REGEX: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"(?i)\bCreateObject\s*\(\s*"([^"]*)""#).unwrap());
+static EXECUTE_GLOBAL_FILE_REGEX: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"(?i)\bExecuteGlobal\b[^"]*"([^"]+\.vbs)""#).unwrap());
+static DT_ARRAY_REGEX: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\bDTArray\(\w+\)\(\d\)").unwrap());
+static ST_ARRAY_REGEX: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\bSTArray\(\w+\)\(\d\)").unwrap());
+static LINE_ENDING_REGEX: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\r\n|\r|\n").unwrap());
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    Error,
+    Warning,
+    Info,
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+            Severity::Info => write!(f, "info"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Finding {
+    pub rule: &'static str,
+    pub severity: Severity,
+    /// 1-based
+    pub line: usize,
+    /// 1-based, in characters
+    pub column: usize,
+    pub message: String,
+}
+
+impl Finding {
+    fn new(
+        rule: &'static str,
+        severity: Severity,
+        line: usize,
+        column: usize,
+        message: impl Into<String>,
+    ) -> Self {
+        Finding {
+            rule,
+            severity,
+            line,
+            column,
+            message: message.into(),
+        }
+    }
+}
+
+/// Lint a table script. Files loaded with `ExecuteGlobal` are looked up,
+/// case-insensitively like standalone does, in `script_dirs`: typically the
+/// table's folder and vpinball's `scripts` folder.
+pub fn lint_script(code: &str, script_dirs: &[PathBuf]) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    if let Some(finding) = mixed_line_endings(code) {
+        findings.push(finding);
+    }
+    for (index, line) in script_lines(code).enumerate() {
+        let line_number = index + 1;
+        let code_part = strip_comment(line);
+        let column = |byte: usize| line[..byte].chars().count() + 1;
+        let without_strings = blank_strings(code_part);
+
+        if let Some(m) = DT_ARRAY_REGEX.find(&without_strings) {
+            findings.push(Finding::new(
+                "dt-array",
+                Severity::Warning,
+                line_number,
+                column(m.start()),
+                "drop targets defined as arrays fail on standalone, fixed by `script patch`",
+            ));
+        }
+        if let Some(m) = ST_ARRAY_REGEX.find(&without_strings) {
+            findings.push(Finding::new(
+                "st-array",
+                Severity::Warning,
+                line_number,
+                column(m.start()),
+                "stand-up targets defined as arrays fail on standalone, fixed by `script patch`",
+            ));
+        }
+        for caps in CREATE_OBJECT_REGEX.captures_iter(code_part) {
+            let prog_id = &caps[1];
+            let start = column(caps.get(0).unwrap().start());
+            let lower = prog_id.to_lowercase();
+            if lower == "scripting.filesystemobject" {
+                findings.push(Finding::new(
+                    "file-system-object",
+                    Severity::Warning,
+                    line_number,
+                    start,
+                    "FileSystemObject is only partially supported on standalone, paths are case-sensitive",
+                ));
+            } else if lower == "wscript.shell" {
+                findings.push(Finding::new(
+                    "wscript-shell",
+                    Severity::Warning,
+                    line_number,
+                    start,
+                    "WScript.Shell (registry, running programs) is not available on standalone",
+                ));
+            } else if WINDOWS_ONLY_COM_OBJECTS.contains(&lower.as_str()) {
+                findings.push(Finding::new(
+                    "windows-com",
+                    Severity::Error,
+                    line_number,
+                    start,
+                    format!("COM object {prog_id} only exists on Windows"),
+                ));
+            }
+        }
+        if let Some(caps) = EXECUTE_GLOBAL_FILE_REGEX.captures(code_part) {
+            let file_name = &caps[1];
+            if !script_dirs
+                .iter()
+                .any(|dir| file_exists_case_insensitive(dir, file_name))
+            {
+                findings.push(Finding::new(
+                    "missing-execute-global",
+                    Severity::Error,
+                    line_number,
+                    column(caps.get(0).unwrap().start()),
+                    format!("ExecuteGlobal loads {file_name}, which was not found"),
+                ));
+            }
+        }
+    }
+    findings
+}
+
+/// vbscript expects CRLF, a mix with bare CR or LF breaks the standalone
+/// parser. Reported once, at the first line ending that differs from the
+/// first one in the script.
+fn mixed_line_endings(code: &str) -> Option<Finding> {
+    let mut first: Option<&str> = None;
+    let mut line = 1;
+    let mut chars = code.char_indices().peekable();
+    while let Some((_, c)) = chars.next() {
+        let ending = match c {
+            '\r' if chars.peek().is_some_and(|(_, next)| *next == '\n') => {
+                chars.next();
+                "CRLF"
+            }
+            '\r' => "CR",
+            '\n' => "LF",
+            _ => continue,
+        };
+        match first {
+            None => first = Some(ending),
+            Some(first) if first != ending => {
+                return Some(Finding::new(
+                    "mixed-line-endings",
+                    Severity::Warning,
+                    line,
+                    1,
+                    format!(
+                        "line ends with {ending} while earlier lines end with {first}, fix with the frontend's VBScript > Unify line endings"
+                    ),
+                ));
+            }
+            Some(_) => {}
+        }
+        line += 1;
+    }
+    None
+}
+
+/// The lines of a script, ending in CRLF, bare CR or LF. `str::lines` does
+/// not split on a bare CR, which would put every line number after one off.
+pub(crate) fn script_lines(code: &str) -> impl Iterator<Item = &str> {
+    LINE_ENDING_REGEX.split(code)
+}
+
+/// The line up to a `'` comment that is not inside a string, or empty for a
+/// `Rem` comment.
+pub(crate) fn strip_comment(line: &str) -> &str {
+    let trimmed = line.trim_start();
+    if trimmed
+        .get(..3)
+        .is_some_and(|start| start.eq_ignore_ascii_case("rem"))
+        && trimmed[3..].chars().next().is_none_or(char::is_whitespace)
+    {
+        return "";
+    }
+    let mut in_string = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            '"' => in_string = !in_string,
+            '\'' if !in_string => return &line[..i],
+            _ => {}
+        }
+    }
+    line
+}
+
+/// String contents replaced by spaces, keeping byte offsets intact.
+fn blank_strings(code: &str) -> String {
+    let mut in_string = false;
+    code.chars()
+        .map(|c| match c {
+            '"' => {
+                in_string = !in_string;
+                String::from(c)
+            }
+            _ if in_string => " ".repeat(c.len_utf8()),
+            _ => String::from(c),
+        })
+        .collect()
+}
+
+fn file_exists_case_insensitive(dir: &Path, file_name: &str) -> bool {
+    if dir.join(file_name).is_file() {
+        return true;
+    }
+    std::fs::read_dir(dir)
+        .map(|entries| {
+            entries.flatten().any(|entry| {
+                entry
+                    .file_name()
+                    .to_string_lossy()
+                    .eq_ignore_ascii_case(file_name)
+                    && entry.path().is_file()
+            })
+        })
+        .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn finds_standalone_problems() {
+        let dir = testdir::testdir!();
+        std::fs::write(dir.join("Controller.vbs"), "").unwrap();
+        let code = [
+            "Option Explicit",
+            "ExecuteGlobal GetTextFile(\"controller.vbs\")",
+            "ExecuteGlobal GetTextFile(\"missing.vbs\")",
+            "Set fso = CreateObject(\"Scripting.FileSystemObject\")",
+            "Set sh = CreateObject(\"WScript.Shell\") : Set v = CreateObject(\"SAPI.SpVoice\")",
+            "Set c = CreateObject(\"VPinMAME.Controller\")",
+            "' Set x = CreateObject(\"SAPI.SpVoice\")",
+            "msg = \"don't DTArray(i)(0)\" : DTArray(i)(4) = 1",
+            "Rem STArray(i)(0)",
+        ]
+        .join("\r\n");
+        let rules: Vec<(&str, Severity, usize, usize)> = lint_script(&code, &[dir])
+            .iter()
+            .map(|f| (f.rule, f.severity, f.line, f.column))
+            .collect();
+        assert_eq!(
+            rules,
+            vec![
+                ("missing-execute-global", Severity::Error, 3, 1),
+                ("file-system-object", Severity::Warning, 4, 11),
+                ("wscript-shell", Severity::Warning, 5, 10),
+                ("windows-com", Severity::Error, 5, 50),
+                ("dt-array", Severity::Warning, 8, 31),
+            ]
+        );
+    }
+
+    #[test]
+    fn finds_mixed_line_endings() {
+        assert_eq!(mixed_line_endings("a\r\nb\r\nc"), None);
+        assert_eq!(mixed_line_endings("a\nb\n"), None);
+        let finding = mixed_line_endings("a\r\nb\r\nc\rd\r\n").unwrap();
+        assert_eq!((finding.rule, finding.line), ("mixed-line-endings", 3));
+    }
+
+    #[test]
+    fn counts_bare_cr_as_line_ending() {
+        let code = "a\r\nb\rc\r\nSet v = CreateObject(\"SAPI.SpVoice\")";
+        let lines: Vec<(&str, usize)> = lint_script(code, &[])
+            .iter()
+            .map(|f| (f.rule, f.line))
+            .collect();
+        assert_eq!(lines, vec![("mixed-line-endings", 2), ("windows-com", 4)]);
+    }
+
+    #[test]
+    fn strips_comments() {
+        assert_eq!(strip_comment("x = 1 ' note"), "x = 1 ");
+        assert_eq!(strip_comment("s = \"it's\" 'c"), "s = \"it's\" ");
+        assert_eq!(strip_comment("  REM note"), "");
+        assert_eq!(strip_comment("Remove = 1"), "Remove = 1");
+        assert_eq!(strip_comment("éé = 1"), "éé = 1");
+    }
+}
@@ -0,0 +1,633 @@
+//! `vpxtool script xref`: cross-references the identifiers in a table script
+//! with the table's gameitems and collections.
+//!
+//! This is a tokenizer, not a VBScript parser. A name counts as declared
+//! when the script declares or assigns it anywhere, scopes are not tracked.
+//! References built at runtime, like `Eval("Light" & i)`, can not be seen,
+//! which is why unreferenced items are only reported as info.
+
+use crate::scriptlint::{Severity, script_lines, strip_comment};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use vpin::vpx::gameitem::GameItemEnum;
+
+/// Objects provided by vpinball, vbscript and the commonly loaded
+/// `controller.vbs`/`core.vbs`, lowercase. Names starting with `vpm` or
+/// `cvpm` are skipped as well.
+const GLOBAL_OBJECTS: [&str; 10] = [
+    "activeball",
+    "activetable",
+    "b2scontroller",
+    "controller",
+    "debug",
+    "err",
+    "me",
+    "nvramcallback",
+    "rendermode",
+    "wscript",
+];
+
+/// Events vpinball fires on gameitems by calling `Sub <item>_<event>`.
+const ITEM_EVENTS: [&str; 11] = [
+    "animate",
+    "collide",
+    "dropped",
+    "hit",
+    "limitbos",
+    "limiteos",
+    "raised",
+    "slingshot",
+    "spin",
+    "timer",
+    "unhit",
+];
+
+/// What the script needs to know about a gameitem.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct XrefItem {
+    pub name: String,
+    pub type_name: String,
+    /// The item's timer is enabled in the editor.
+    pub timer_enabled: bool,
+}
+
+impl XrefItem {
+    pub fn from_gameitem(item: &GameItemEnum) -> Self {
+        let timer = match item {
+            GameItemEnum::Wall(i) => Some(&i.timer),
+            GameItemEnum::Flipper(i) => Some(&i.timer),
+            GameItemEnum::Timer(i) => Some(&i.timer),
+            GameItemEnum::Plunger(i) => Some(&i.timer),
+            GameItemEnum::TextBox(i) => Some(&i.timer),
+            GameItemEnum::Bumper(i) => Some(&i.timer),
+            GameItemEnum::Trigger(i) => Some(&i.timer),
+            GameItemEnum::Light(i) => Some(&i.timer),
+            GameItemEnum::Kicker(i) => Some(&i.timer),
+            GameItemEnum::Gate(i) => Some(&i.timer),
+            GameItemEnum::Spinner(i) => Some(&i.timer),
+            GameItemEnum::Ramp(i) => Some(&i.timer),
+            GameItemEnum::Reel(i) => Some(&i.timer),
+            GameItemEnum::LightSequencer(i) => Some(&i.timer),
+            GameItemEnum::Flasher(i) => Some(&i.timer),
+            GameItemEnum::Rubber(i) => Some(&i.timer),
+            GameItemEnum::HitTarget(i) => Some(&i.timer),
+            GameItemEnum::Ball(i) => Some(&i.timer),
+            GameItemEnum::PartGroup(i) => Some(&i.timer),
+            GameItemEnum::Decal(_) | GameItemEnum::Primitive(_) | GameItemEnum::Generic(..) => None,
+        };
+        XrefItem {
+            name: item.name().to_string(),
+            type_name: item.type_name(),
+            timer_enabled: timer.is_some_and(|t| t.is_enabled),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct XrefCollection {
+    pub name: String,
+    pub items: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct XrefFinding {
+    pub rule: &'static str,
+    pub severity: Severity,
+    pub name: String,
+    /// 1-based, not set for items that are never referenced
+    pub line: Option<usize>,
+    /// 1-based, in characters
+    pub column: Option<usize>,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token<'a> {
+    Ident(&'a str),
+    Str(&'a str),
+    Dot,
+    Open,
+    Close,
+    Comma,
+    Equals,
+    /// `:` separating statements on one line
+    Separator,
+    Other,
+}
+
+#[derive(Debug)]
+struct Positioned<'a> {
+    token: Token<'a>,
+    line: usize,
+    column: usize,
+}
+
+fn tokenize_line(line: &str, line_number: usize) -> Vec<Positioned<'_>> {
+    let code = strip_comment(line);
+    let mut tokens = Vec::new();
+    let mut chars = code.char_indices().peekable();
+    let mut column = 0;
+    while let Some((start, c)) = chars.next() {
+        column += 1;
+        let token_column = column;
+        let token = match c {
+            c if c.is_ascii_alphabetic() => {
+                let mut end = start + 1;
+                while let Some(&(i, next)) = chars.peek() {
+                    if !(next.is_ascii_alphanumeric() || next == '_') {
+                        break;
+                    }
+                    end = i + next.len_utf8();
+                    column += 1;
+                    chars.next();
+                }
+                Token::Ident(&code[start..end])
+            }
+            c if c.is_ascii_digit() => {
+                // numbers, including `1.5` and `&H1F` style literals
+                while chars
+                    .peek()
+                    .is_some_and(|&(_, next)| next.is_ascii_alphanumeric() || next == '.')
+                {
+                    column += 1;
+                    chars.next();
+                }
+                Token::Other
+            }
+            '"' => {
+                let mut end = code.len();
+                for (i, next) in chars.by_ref() {
+                    column += 1;
+                    if next == '"' {
+                        end = i;
+                        break;
+                    }
+                }
+                Token::Str(&code[start + 1..end])
+            }
+            '.' => Token::Dot,
+            '(' => Token::Open,
+            ')' => Token::Close,
+            ',' => Token::Comma,
+            '=' => Token::Equals,
+            ':' => Token::Separator,
+            c if c.is_whitespace() => continue,
+            _ => Token::Other,
+        };
+        tokens.push(Positioned {
+            token,
+            line: line_number,
+            column: token_column,
+        });
+    }
+    tokens
+}
+
+/// Statements, split on new lines and `:`, joining `_` line continuations.
+fn statements(code: &str) -> Vec<Vec<Positioned<'_>>> {
+    let mut statements = Vec::new();
+    let mut current = Vec::new();
+    for (index, line) in script_lines(code).enumerate() {
+        let mut tokens = tokenize_line(line, index + 1);
+        // a trailing `_` is tokenized as Other since identifiers start with a letter
+        let continued =
+            strip_comment(line).trim_end().ends_with(" _") || strip_comment(line).trim_end() == "_";
+        if continued {
+            tokens.pop();
+        }
+        for token in tokens {
+            if token.token == Token::Separator {
+                statements.push(std::mem::take(&mut current));
+            } else {
+                current.push(token);
+            }
+        }
+        if !continued {
+            statements.push(std::mem::take(&mut current));
+        }
+    }
+    statements.push(current);
+    statements.retain(|s| !s.is_empty());
+    statements
+}
+
+fn ident<'a>(token: Option<&Positioned<'a>>) -> Option<&'a str> {
+    match token.map(|t| &t.token) {
+        Some(Token::Ident(name)) => Some(name),
+        _ => None,
+    }
+}
+
+fn is_keyword(token: Option<&Positioned>, keyword: &str) -> bool {
+    ident(token).is_some_and(|name| name.eq_ignore_ascii_case(keyword))
+}
+
+#[derive(Default)]
+struct ScriptNames<'a> {
+    /// lowercase names the script declares or assigns
+    declared: HashSet<String>,
+    /// lowercase names of classes, their members are not event handlers
+    classes: HashSet<String>,
+    /// every identifier and string, lowercase
+    mentioned: HashSet<String>,
+    /// subs outside of classes, lowercase name to the name as written and position
+    subs: HashMap<String, (&'a str, usize, usize)>,
+    /// `X.` references, in order of first use
+    dotted: Vec<&'a Positioned<'a>>,
+    /// lowercase names used as `X.Enabled`
+    enabled: HashSet<String>,
+    /// lowercase names used as `X.TimerEnabled`
+    timer_enabled: HashSet<String>,
+}
+
+fn collect_names<'a>(statements: &'a [Vec<Positioned<'a>>]) -> ScriptNames<'a> {
+    let mut names = ScriptNames::default();
+    let mut in_class = false;
+    for statement in statements {
+        for (i, token) in statement.iter().enumerate() {
+            match token.token {
+                Token::Ident(name) => {
+                    let lower = name.to_lowercase();
+                    // event handlers mention their item as `<item>_<event>`
+                    for (underscore, _) in lower.match_indices('_') {
+                        names.mentioned.insert(lower[..underscore].to_string());
+                    }
+                    names.mentioned.insert(lower);
+                    let after_dot = i > 0 && statement[i - 1].token == Token::Dot;
+                    let before_dot = statement.get(i + 1).map(|t| &t.token) == Some(&Token::Dot);
+                    if before_dot && !after_dot {
+                        names.dotted.push(token);
+                        match ident(statement.get(i + 2))
+                            .map(str::to_lowercase)
+                            .as_deref()
+                        {
+                            Some("enabled") => {
+                                names.enabled.insert(name.to_lowercase());
+                            }
+                            Some("timerenabled") => {
+                                names.timer_enabled.insert(name.to_lowercase());
+                            }
+                            _ => {}
+                        }
+                    }
+                }
+                Token::Str(text) => {
+                    names.mentioned.insert(text.trim().to_lowercase());
+                }
+                _ => {}
+            }
+        }
+
+        let mut rest = &statement[..];
+        // `Private Sub`, `Public Default Function`, ... but not `Private x`
+        while ["public", "private", "default"]
+            .iter()
+            .any(|k| is_keyword(rest.first(), k))
+            && ["sub", "function", "property", "default", "const"]
+                .iter()
+                .any(|k| is_keyword(rest.get(1), k))
+        {
+            rest = &rest[1..];
+        }
+        let Some(first) = ident(rest.first()).map(str::to_lowercase) else {
+            continue;
+        };
+        match first.as_str() {
+            "class" => {
+                if let Some(name) = ident(rest.get(1)) {
+                    names.classes.insert(name.to_lowercase());
+                    names.declared.insert(name.to_lowercase());
+                }
+                in_class = true;
+            }
+            "end" if is_keyword(rest.get(1), "class") => in_class = false,
+            "sub" | "function" | "property" => {
+                let name_index = if first == "property" { 2 } else { 1 };
+                if let Some(name) = ident(rest.get(name_index)) {
+                    names.declared.insert(name.to_lowercase());
+                    if !in_class && first == "sub" {
+                        let token = &rest[name_index];
+                        names.subs.entry(name.to_lowercase()).or_insert((
+                            name,
+                            token.line,
+                            token.column,
+                        ));
+                    }
+                }
+                // parameters
+                for token in &rest[name_index..] {
+                    if let Token::Ident(name) = token.token
+                        && !["byval", "byref", "optional"]
+                            .iter()
+                            .any(|k| name.eq_ignore_ascii_case(k))
+                    {
+                        names.declared.insert(name.to_lowercase());
+                    }
+                }
+            }
+            "dim" | "redim" | "const" | "static" | "public" | "private" => {
+                let mut depth = 0;
+                let mut expect_name = true;
+                for token in &rest[1..] {
+                    match token.token {
+                        Token::Ident(name)
+                            if expect_name
+                                && depth == 0
+                                && !name.eq_ignore_ascii_case("preserve") =>
+                        {
+                            names.declared.insert(name.to_lowercase());
+                            expect_name = false;
+                        }
+                        Token::Open => depth += 1,
+                        Token::Close => depth -= 1,
+                        Token::Comma if depth == 0 => expect_name = true,
+                        _ => {}
+                    }
+                }
+            }
+            "set" => {
+                if let Some(name) = ident(rest.get(1)) {
+                    names.declared.insert(name.to_lowercase());
+                }
+            }
+            "for" => {
+                let index = if is_keyword(rest.get(1), "each") {
+                    2
+                } else {
+                    1
+                };
+                if let Some(name) = ident(rest.get(index)) {
+                    names.declared.insert(name.to_lowercase());
+                }
+            }
+            _ => {
+                // implicit variables, `x = 1` or `x(1) = 2`
+                let assigned = match rest.get(1).map(|t| &t.token) {
+                    Some(Token::Equals) => true,
+                    Some(Token::Open) => {
+                        let mut depth = 0;
+                        rest[1..].iter().any(|t| {
+                            match t.token {
+                                Token::Open => depth += 1,
+                                Token::Close => depth -= 1,
+                                Token::Equals if depth == 0 => return true,
+                                _ => {}
+                            }
+                            false
+                        })
+                    }
+                    _ => false,
+                };
+                if assigned {
+                    names.declared.insert(first);
+                }
+            }
+        }
+    }
+    names
+}
+
+/// Cross-reference `code` with the table's gameitems and collections.
+/// Findings are ordered: missing items, event subs for missing items,
+/// missing timer subs, then unreferenced items.
+pub fn xref_script(
+    code: &str,
+    table_name: &str,
+    items: &[XrefItem],
+    collections: &[XrefCollection],
+) -> Vec<XrefFinding> {
+    let statements = statements(code);
+    let names = collect_names(&statements);
+
+    let mut known: HashSet<String> = items.iter().map(|i| i.name.to_lowercase()).collect();
+    known.extend(collections.iter().map(|c| c.name.to_lowercase()));
+    known.insert(table_name.to_lowercase());
+    let is_known = |lower: &str| {
+        known.contains(lower)
+            || names.declared.contains(lower)
+            || GLOBAL_OBJECTS.contains(&lower)
+            || lower.starts_with("vpm")
+            || lower.starts_with("cvpm")
+    };
+
+    let mut findings = Vec::new();
+    let mut reported = HashSet::new();
+    for token in &names.dotted {
+        let Token::Ident(name) = token.token else {
+            continue;
+        };
+        let lower = name.to_lowercase();
+        if !is_known(&lower) && reported.insert(lower) {
+            findings.push(XrefFinding {
+                rule: "missing-item",
+                severity: Severity::Error,
+                name: name.to_string(),
+                line: Some(token.line),
+                column: Some(token.column),
+                message: format!("{name} is not a gameitem, collection or script variable"),
+            });
+        }
+    }
+    let mut subs: Vec<_> = names.subs.values().collect();
+    subs.sort_by_key(|(_, line, column)| (*line, *column));
+    for (sub, line, column) in subs {
+        let Some((item, event)) = sub.rsplit_once('_') else {
+            continue;
+        };
+        let lower = item.to_lowercase();
+        if ITEM_EVENTS.contains(&event.to_lowercase().as_str())
+            && !known.contains(&lower)
+            && !names.classes.contains(&lower)
+            && reported.insert(lower)
+        {
+            findings.push(XrefFinding {
+                rule: "orphaned-event-sub",
+                severity: Severity::Warning,
+                name: item.to_string(),
+                line: Some(*line),
+                column: Some(*column),
+                message: format!("{sub} is never called, {item} is not a gameitem or collection"),
+            });
+        }
+    }
+
+    // timers that run from the start or are started by the script
+    let uses_timer = |item: &XrefItem| {
+        let lower = item.name.to_lowercase();
+        item.timer_enabled
+            || names.timer_enabled.contains(&lower)
+            || (item.type_name == "Timer" && names.enabled.contains(&lower))
+    };
+    for item in items.iter().filter(|i| uses_timer(i)) {
+        let timer_sub = format!("{}_timer", item.name.to_lowercase());
+        if !names.subs.contains_key(&timer_sub) {
+            findings.push(XrefFinding {
+                rule: "missing-timer-sub",
+                severity: Severity::Warning,
+                name: item.name.clone(),
+                line: None,
+                column: None,
+                message: format!(
+                    "{} {} has its timer enabled but there is no Sub {}_Timer",
+                    item.type_name, item.name, item.name
+                ),
+            });
+        }
+    }
+
+    // items in a collection the script uses are referenced through it
+    let mut referenced = names.mentioned.clone();
+    for collection in collections {
+        if names.mentioned.contains(&collection.name.to_lowercase()) {
+            referenced.extend(collection.items.iter().map(|i| i.to_lowercase()));
+        }
+    }
+    for item in items.iter().filter(|i| !i.name.is_empty()) {
+        if !referenced.contains(&item.name.to_lowercase()) {
+            findings.push(XrefFinding {
+                rule: "unreferenced-item",
+                severity: Severity::Info,
+                name: item.name.clone(),
+                line: None,
+                column: None,
+                message: format!("{} {} is never referenced", item.type_name, item.name),
+            });
+        }
+    }
+    findings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn item(name: &str, type_name: &str, timer_enabled: bool) -> XrefItem {
+        XrefItem {
+            name: name.to_string(),
+            type_name: type_name.to_string(),
+            timer_enabled,
+        }
+    }
+
+    #[test]
+    fn finds_missing_and_unreferenced_items() {
+        let code = [
+            "Option Explicit",
+            "Dim bsTrough : Set bsTrough = New cvpmBallStack",
+            "Private lastKey, lights(3)",
+            "Sub Table1_KeyDown(ByVal keycode)",
+            "  If keycode = LeftFlipperKey Then LeftFlipper.RotateToEnd : Flipper3.RotateToEnd",
+            "  bsTrough.AddBall Me : lastKey = keycode : Timer3.Enabled = True",
+            "End Sub",
+            "Sub Bumper1_Hit() : vpmTimer.PulseSw 10 : End Sub",
+            "Sub Kicker9_Hit()",
+            "  Light23.State = 1 ' Light99.State = 1",
+            "  lights(1).State = 0 : Wall1. _",
+            "     IsDropped = 1",
+            "End Sub",
+            "Sub Timer1_Timer() : End Sub",
+            "Sub AllLights_Hit(idx) : End Sub",
+            "Class Helper",
+            "  Sub Other_Hit() : End Sub",
+            "End Class",
+        ]
+        .join("\r\n");
+        let items = [
+            item("LeftFlipper", "Flipper", false),
+            item("Bumper1", "Bumper", true),
+            item("Light23", "Light", false),
+            item("Light24", "Light", false),
+            item("Light25", "Light", false),
+            item("Wall1", "Wall", false),
+            item("Timer1", "Timer", true),
+            item("Timer2", "Timer", true),
+            item("Timer3", "Timer", false),
+            item("Primitive1", "Primitive", false),
+        ];
+        let collections = [XrefCollection {
+            name: "AllLights".to_string(),
+            items: vec!["Light24".to_string()],
+        }];
+        let findings: Vec<(&str, Severity, String, Option<usize>)> =
+            xref_script(&code, "Table1", &items, &collections)
+                .into_iter()
+                .map(|f| (f.rule, f.severity, f.name, f.line))
+                .collect();
+        assert_eq!(
+            findings,
+            vec![
+                (
+                    "missing-item",
+                    Severity::Error,
+                    "Flipper3".to_string(),
+                    Some(5)
+                ),
+                (
+                    "orphaned-event-sub",
+                    Severity::Warning,
+                    "Kicker9".to_string(),
+                    Some(9)
+                ),
+                (
+                    "missing-timer-sub",
+                    Severity::Warning,
+                    "Bumper1".to_string(),
+                    None
+                ),
+                (
+                    "missing-timer-sub",
+                    Severity::Warning,
+                    "Timer2".to_string(),
+                    None
+                ),
+                (
+                    "missing-timer-sub",
+                    Severity::Warning,
+                    "Timer3".to_string(),
+                    None
+                ),
+                (
+                    "unreferenced-item",
+                    Severity::Info,
+                    "Light25".to_string(),
+                    None
+                ),
+                (
+                    "unreferenced-item",
+                    Severity::Info,
+                    "Timer2".to_string(),
+                    None
+                ),
+                (
+                    "unreferenced-item",
+                    Severity::Info,
+                    "Primitive1".to_string(),
+                    None
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn tokenizes_strings_numbers_and_columns() {
+        let tokens: Vec<(Token, usize)> = tokenize_line("x = \"a.b\" & 1.5 : y.z", 1)
+            .into_iter()
+            .map(|t| (t.token, t.column))
+            .collect();
+        assert_eq!(
+            tokens,
+            vec![
+                (Token::Ident("x"), 1),
+                (Token::Equals, 3),
+                (Token::Str("a.b"), 5),
+                (Token::Other, 11),
+                (Token::Other, 13),
+                (Token::Separator, 17),
+                (Token::Ident("y"), 19),
+                (Token::Dot, 20),
+                (Token::Ident("z"), 21),
+            ]
+        );
+    }
+}
@@ -0,0 +1,313 @@
+//! `vpxtool smoketest`: run every table headless against a vpinball build and
+//! report which ones crash, hang or raise script errors, so a vpinball upgrade
+//! can be checked against a whole table library before it goes on the cab.
+//!
+//! Tables are run in vpinball's attract capture mode for a single frame, the
+//! only mode that plays a table without input and exits by itself.
+
+use crate::capture::{CaptureOptions, capture_dir, log_tail, remove_capture_dir, run_capture};
+use crate::config::ResolvedConfig;
+use crate::{describe_exit, escape_xml};
+use regex::Regex;
+use serde::Serialize;
+use std::fmt::{Display, Formatter};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::ExitStatus;
+use std::str::FromStr;
+use std::sync::LazyLock;
+use std::time::{Duration, Instant};
+
+/// vpinball logs VBScript compile and runtime errors with "Script Error" or
+/// "Script error" in the line, the standalone builds as "VBScript ... error".
+static SCRIPT_ERROR_REGEX: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?i)script error|vbscript\b.*\berror").unwrap());
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum SmokeOutcome {
+    Ok,
+    /// vpinball exited with an error or was killed, e.g. by a segfault.
+    Crashed {
+        exit: String,
+    },
+    /// Still running after the timeout, killed.
+    Hung,
+    /// vpinball exited cleanly but the log has a script error.
+    ScriptError {
+        message: String,
+    },
+}
+
+impl SmokeOutcome {
+    pub fn is_ok(&self) -> bool {
+        matches!(self, SmokeOutcome::Ok)
+    }
+}
+
+impl Display for SmokeOutcome {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SmokeOutcome::Ok => write!(f, "ok"),
+            SmokeOutcome::Crashed { exit } => write!(f, "crashed, vpinball {exit}"),
+            SmokeOutcome::Hung => write!(f, "hung"),
+            SmokeOutcome::ScriptError { message } => write!(f, "script error: {message}"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SmokeResult {
+    pub table: PathBuf,
+    #[serde(flatten)]
+    pub outcome: SmokeOutcome,
+    pub duration_secs: f64,
+    /// The last lines of the vpinball output when the table did not pass.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub log_tail: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ReportFormat {
+    #[default]
+    Junit,
+    Json,
+}
+
+impl ReportFormat {
+    pub const VALUES: [&'static str; 2] = ["junit", "json"];
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ReportFormat::Junit => "xml",
+            ReportFormat::Json => "json",
+        }
+    }
+}
+
+impl FromStr for ReportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "junit" => Ok(ReportFormat::Junit),
+            "json" => Ok(ReportFormat::Json),
+            other => Err(format!(
+                "Unknown report format '{other}', expected junit or json"
+            )),
+        }
+    }
+}
+
+/// Classify a finished run, `status` is `None` when it was killed after the
+/// timeout.
+pub fn classify(status: Option<ExitStatus>, log: &str) -> SmokeOutcome {
+    match status {
+        None => SmokeOutcome::Hung,
+        Some(status) if !status.success() => SmokeOutcome::Crashed {
+            exit: describe_exit(status),
+        },
+        Some(_) => match log.lines().find(|line| SCRIPT_ERROR_REGEX.is_match(line)) {
+            Some(line) => SmokeOutcome::ScriptError {
+                message: line.trim().to_string(),
+            },
+            None => SmokeOutcome::Ok,
+        },
+    }
+}
+
+/// Run one table with `config.vpx_executable`, killing it after `timeout`.
+pub fn smoketest_table(
+    config: &ResolvedConfig,
+    vpx_path: &Path,
+    timeout: Option<Duration>,
+) -> io::Result<SmokeResult> {
+    let options = CaptureOptions {
+        timeout,
+        ..CaptureOptions::default()
+    };
+    let capture_dir = capture_dir(vpx_path);
+    remove_capture_dir(&capture_dir);
+    let started = Instant::now();
+    let run = run_capture(config, vpx_path, &options);
+    remove_capture_dir(&capture_dir);
+    let run = run?;
+    let outcome = classify(run.status, &run.log);
+    let log_tail = if outcome.is_ok() {
+        Vec::new()
+    } else {
+        log_tail(&run.log)
+    };
+    Ok(SmokeResult {
+        table: vpx_path.to_path_buf(),
+        outcome,
+        duration_secs: started.elapsed().as_secs_f64(),
+        log_tail,
+    })
+}
+
+pub fn json_report(results: &[SmokeResult]) -> io::Result<String> {
+    serde_json::to_string_pretty(results).map_err(io::Error::other)
+}
+
+/// A JUnit XML report, one test case per table, as understood by most CI
+/// systems. Crashes are errors, hangs and script errors failures.
+pub fn junit_report(results: &[SmokeResult]) -> String {
+    let count = |f: fn(&SmokeOutcome) -> bool| results.iter().filter(|r| f(&r.outcome)).count();
+    let errors = count(|o| matches!(o, SmokeOutcome::Crashed { .. }));
+    let failures = count(|o| matches!(o, SmokeOutcome::Hung | SmokeOutcome::ScriptError { .. }));
+    let time: f64 = results.iter().map(|r| r.duration_secs).sum();
+
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    xml.push_str(&format!(
+        "<testsuite name=\"vpxtool smoketest\" tests=\"{}\" failures=\"{failures}\" errors=\"{errors}\" time=\"{time:.3}\">\n",
+        results.len()
+    ));
+    for result in results {
+        let name = result
+            .table
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+        xml.push_str(&format!(
+            "  <testcase classname=\"smoketest\" name=\"{}\" file=\"{}\" time=\"{:.3}\"",
+            escape_xml(&name),
+            escape_xml(&result.table.display().to_string()),
+            result.duration_secs
+        ));
+        let (element, kind) = match &result.outcome {
+            SmokeOutcome::Ok => {
+                xml.push_str("/>\n");
+                continue;
+            }
+            SmokeOutcome::Crashed { .. } => ("error", "crashed"),
+            SmokeOutcome::Hung => ("failure", "hung"),
+            SmokeOutcome::ScriptError { .. } => ("failure", "script_error"),
+        };
+        xml.push_str(&format!(
+            ">\n    <{element} type=\"{kind}\" message=\"{}\">{}</{element}>\n  </testcase>\n",
+            escape_xml(&result.outcome.to_string()),
+            escape_xml(&result.log_tail.join("\n"))
+        ));
+    }
+    xml.push_str("</testsuite>\n");
+    xml
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn result(table: &str, outcome: SmokeOutcome) -> SmokeResult {
+        SmokeResult {
+            table: PathBuf::from(table),
+            outcome,
+            duration_secs: 1.5,
+            log_tail: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn writes_junit_report() {
+        let results = vec![
+            result("/t/Ok.vpx", SmokeOutcome::Ok),
+            SmokeResult {
+                log_tail: vec!["Line: 12 <Variable is undefined>".to_string()],
+                ..result(
+                    "/t/Bad & Broken.vpx",
+                    SmokeOutcome::ScriptError {
+                        message: "Script Error".to_string(),
+                    },
+                )
+            },
+            result(
+                "/t/Crash.vpx",
+                SmokeOutcome::Crashed {
+                    exit: "was killed by signal 11 (SIGSEGV, crash)".to_string(),
+                },
+            ),
+        ];
+        assert_eq!(
+            junit_report(&results),
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<testsuite name="vpxtool smoketest" tests="3" failures="1" errors="1" time="4.500">
+  <testcase classname="smoketest" name="Ok.vpx" file="/t/Ok.vpx" time="1.500"/>
+  <testcase classname="smoketest" name="Bad &amp; Broken.vpx" file="/t/Bad &amp; Broken.vpx" time="1.500">
+    <failure type="script_error" message="script error: Script Error">Line: 12 &lt;Variable is undefined&gt;</failure>
+  </testcase>
+  <testcase classname="smoketest" name="Crash.vpx" file="/t/Crash.vpx" time="1.500">
+    <error type="crashed" message="crashed, vpinball was killed by signal 11 (SIGSEGV, crash)"></error>
+  </testcase>
+</testsuite>
+"#
+        );
+    }
+
+    #[test]
+    fn writes_json_report() {
+        let json = json_report(&[result("/t/Hang.vpx", SmokeOutcome::Hung)]).unwrap();
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&json).unwrap(),
+            serde_json::json!([{"table": "/t/Hang.vpx", "status": "hung", "duration_secs": 1.5}])
+        );
+    }
+
+    /// Runs stub scripts in place of vpinball.
+    #[cfg(unix)]
+    #[test]
+    fn classifies_stub_runs() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = testdir::testdir!();
+        let vpx = dir.join("table.vpx");
+        std::fs::write(&vpx, b"").unwrap();
+        let run = |name: &str, script: &str| {
+            let stub = dir.join(name);
+            std::fs::write(&stub, format!("#!/bin/sh\n{script}\n")).unwrap();
+            std::fs::set_permissions(&stub, std::fs::Permissions::from_mode(0o755)).unwrap();
+            let config = ResolvedConfig {
+                vpx_executable: stub,
+                launch_templates: Vec::new(),
+                vpx_config: dir.join("VPinballX.ini"),
+                tables_folder: dir.clone(),
+                tables_index_path: dir.join("vpxtool_index.json"),
+                tables_scan_max_depth: None,
+                diff: None,
+                editor: None,
+                vpxz_excludes: Vec::new(),
+            };
+            smoketest_table(&config, &vpx, Some(Duration::from_secs(1))).unwrap()
+        };
+
+        assert_eq!(run("ok.sh", "echo started").outcome, SmokeOutcome::Ok);
+        let script_error = run(
+            "script_error.sh",
+            "echo 'Script Error: Line: 12 Variable is undefined'",
+        );
+        assert_eq!(
+            script_error.outcome,
+            SmokeOutcome::ScriptError {
+                message: "Script Error: Line: 12 Variable is undefined".to_string()
+            }
+        );
+        assert_eq!(
+            script_error.log_tail,
+            vec!["Script Error: Line: 12 Variable is undefined"]
+        );
+        assert_eq!(
+            run("crash.sh", "kill -SEGV $$").outcome,
+            SmokeOutcome::Crashed {
+                exit: "was killed by signal 11 (SIGSEGV, crash)".to_string()
+            }
+        );
+        assert_eq!(
+            run("exit.sh", "exit 3").outcome,
+            SmokeOutcome::Crashed {
+                exit: "exited with code 3".to_string()
+            }
+        );
+        assert_eq!(run("hang.sh", "exec sleep 30").outcome, SmokeOutcome::Hung);
+        assert!(!dir.join("Capture").exists());
+    }
+}
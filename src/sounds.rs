@@ -0,0 +1,366 @@
+//! Get the audio out of a vpx and swap or add sounds, the operations the
+//! vpinball editor's sound manager offers.
+//!
+//! vpinball keeps ogg and mp3 sounds as the original file, but strips wav
+//! files down to their `WAVEFORMATEX` format block and the raw sample data.
+//! On extract vpin's [`write_sound`](vpin::vpx::sound::write_sound) writes a
+//! fresh RIFF header for those, the same as `vpxtool extract`. On
+//! replace/import we walk the RIFF chunks ourselves so files with extra
+//! chunks (`LIST`, `fact`, `cue `, ...) are accepted and only the samples end
+//! up in the table.
+
+use std::io;
+use vpin::vpx::sound::{OutputTarget, SoundData, WaveForm};
+
+const WAVE_FORMAT_PCM: u16 = 1;
+const WAVE_FORMAT_IEEE_FLOAT: u16 = 3;
+
+/// Sound file formats vpinball can play.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SoundFormat {
+    Wav,
+    Ogg,
+    Mp3,
+}
+
+impl SoundFormat {
+    fn extension(&self) -> &'static str {
+        match self {
+            SoundFormat::Wav => "wav",
+            SoundFormat::Ogg => "ogg",
+            SoundFormat::Mp3 => "mp3",
+        }
+    }
+
+    fn from_extension(extension: &str) -> Option<Self> {
+        match extension.to_ascii_lowercase().as_str() {
+            "wav" => Some(SoundFormat::Wav),
+            "ogg" => Some(SoundFormat::Ogg),
+            "mp3" => Some(SoundFormat::Mp3),
+            _ => None,
+        }
+    }
+
+    /// Sniff the format from the first bytes of a file.
+    fn detect(data: &[u8]) -> Option<Self> {
+        if data.len() >= 12 && &data[0..4] == b"RIFF" && &data[8..12] == b"WAVE" {
+            Some(SoundFormat::Wav)
+        } else if data.starts_with(b"OggS") {
+            Some(SoundFormat::Ogg)
+        } else if data.starts_with(b"ID3")
+            || (data.len() >= 2 && data[0] == 0xFF && data[1] & 0xE0 == 0xE0)
+        {
+            // ID3 tag or a bare mpeg audio frame sync
+            Some(SoundFormat::Mp3)
+        } else {
+            None
+        }
+    }
+}
+
+/// What changed when replacing a sound, for reporting.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct SoundReplacement {
+    pub old_extension: String,
+    pub new_extension: String,
+    pub old_size: usize,
+    pub new_size: usize,
+}
+
+/// Index of the sound with the given name, matched case-insensitively like
+/// vpinball does.
+pub(crate) fn find_sound(sounds: &[SoundData], name: &str) -> Option<usize> {
+    sounds
+        .iter()
+        .position(|sound| sound.name.eq_ignore_ascii_case(name))
+}
+
+/// The extension of a stored sound, lower case. Like vpinball we treat a
+/// path without extension as wav, this includes the legacy
+/// `* Backglass Output *` paths.
+pub(crate) fn sound_extension(sound: &SoundData) -> String {
+    crate::os_independent_file_name(sound.path.clone())
+        .and_then(|file_name| {
+            file_name
+                .rsplit_once('.')
+                .map(|(_, extension)| extension.to_ascii_lowercase())
+        })
+        .unwrap_or_else(|| "wav".to_string())
+}
+
+/// File name for an extracted sound, `<name>.<ext>`.
+pub(crate) fn sound_file_name(sound: &SoundData) -> String {
+    format!(
+        "{}.{}",
+        crate::safe_file_name(&sound.name),
+        sound_extension(sound)
+    )
+}
+
+/// Create a new sound from a file, with vpinball's defaults for a freshly
+/// imported sound: played on the table, centered, at full volume.
+pub(crate) fn new_sound(name: &str, file_path: &str, data: Vec<u8>) -> io::Result<SoundData> {
+    let mut sound = SoundData {
+        name: name.to_string(),
+        path: file_path.to_string(),
+        wave_form: WaveForm::default(),
+        data: Vec::new(),
+        internal_name: String::new(),
+        fade: 0,
+        volume: 0,
+        balance: 0,
+        output_target: OutputTarget::Table,
+    };
+    let file_name = crate::os_independent_file_name(file_path.to_string()).unwrap_or_default();
+    let format = check_format(&file_name, &data)?;
+    set_sound_data(&mut sound, format, &file_name, data)?;
+    Ok(sound)
+}
+
+/// Replace the audio of `sound` with the contents of the file `file_name`.
+/// Name, output target, pan, fade and volume are kept, as is the original
+/// import path apart from its extension which follows the new format.
+pub(crate) fn replace_sound_data(
+    sound: &mut SoundData,
+    file_name: &str,
+    data: Vec<u8>,
+) -> io::Result<SoundReplacement> {
+    let format = check_format(file_name, &data)?;
+    let old_extension = sound_extension(sound);
+    let old_size = sound.data.len();
+    set_sound_data(sound, format, file_name, data)?;
+    if format.extension() != old_extension {
+        sound.path = crate::os_independent_with_extension(&sound.path, format.extension());
+    }
+    Ok(SoundReplacement {
+        old_extension,
+        new_extension: format.extension().to_string(),
+        old_size,
+        new_size: sound.data.len(),
+    })
+}
+
+fn set_sound_data(
+    sound: &mut SoundData,
+    format: SoundFormat,
+    file_name: &str,
+    data: Vec<u8>,
+) -> io::Result<()> {
+    match format {
+        SoundFormat::Wav => {
+            let (wave_form, samples) = parse_wav(&data).map_err(|e| {
+                io::Error::new(io::ErrorKind::InvalidData, format!("{file_name}: {e}"))
+            })?;
+            sound.wave_form = wave_form;
+            sound.data = samples;
+        }
+        SoundFormat::Ogg | SoundFormat::Mp3 => {
+            sound.wave_form = WaveForm::default();
+            sound.data = data;
+        }
+    }
+    Ok(())
+}
+
+/// Check that the file is a sound format vpinball supports and that the
+/// extension matches the contents.
+fn check_format(file_name: &str, data: &[u8]) -> io::Result<SoundFormat> {
+    let Some(from_content) = SoundFormat::detect(data) else {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{file_name}: not a wav, ogg or mp3 file"),
+        ));
+    };
+    let from_extension = file_name
+        .rsplit_once('.')
+        .and_then(|(_, extension)| SoundFormat::from_extension(extension));
+    match from_extension {
+        Some(from_extension) if from_extension != from_content => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "{file_name}: the extension suggests {} but the file contains {}",
+                from_extension.extension(),
+                from_content.extension()
+            ),
+        )),
+        _ => Ok(from_content),
+    }
+}
+
+/// Split a wav file into its format and sample data, skipping any other
+/// chunks.
+fn parse_wav(data: &[u8]) -> Result<(WaveForm, Vec<u8>), String> {
+    let mut wave_form = None;
+    let mut samples = None;
+    let mut pos = 12;
+    while pos + 8 <= data.len() {
+        let id = &data[pos..pos + 4];
+        let size = u32::from_le_bytes(data[pos + 4..pos + 8].try_into().unwrap()) as usize;
+        let body_start = pos + 8;
+        // Some writers put a bogus size on the last (data) chunk, clamp it.
+        let body_end = body_start.saturating_add(size).min(data.len());
+        let body = &data[body_start..body_end];
+        match id {
+            b"fmt " => {
+                if body.len() < 16 {
+                    return Err("fmt chunk is too short".to_string());
+                }
+                let u16_at = |i: usize| u16::from_le_bytes([body[i], body[i + 1]]);
+                let u32_at = |i: usize| u32::from_le_bytes(body[i..i + 4].try_into().unwrap());
+                wave_form = Some(WaveForm {
+                    format_tag: u16_at(0),
+                    channels: u16_at(2),
+                    samples_per_sec: u32_at(4),
+                    avg_bytes_per_sec: u32_at(8),
+                    block_align: u16_at(12),
+                    bits_per_sample: u16_at(14),
+                    // vpinball can't store format specific extra bytes
+                    cb_size: 0,
+                });
+            }
+            b"data" => samples = Some(body.to_vec()),
+            _ => {}
+        }
+        // chunks are word aligned
+        pos = body_start.saturating_add(size).saturating_add(size % 2);
+    }
+    let wave_form = wave_form.ok_or("missing fmt chunk")?;
+    let samples = samples.ok_or("missing data chunk")?;
+    match wave_form.format_tag {
+        WAVE_FORMAT_PCM | WAVE_FORMAT_IEEE_FLOAT => {}
+        0xFFFE => {
+            return Err(
+                "WAVE_FORMAT_EXTENSIBLE is not supported, save as plain PCM wav".to_string(),
+            );
+        }
+        other => {
+            return Err(format!(
+                "compressed wav (format tag {other}) is not supported, save as PCM wav or ogg"
+            ));
+        }
+    }
+    if wave_form.channels == 0 || wave_form.block_align == 0 || wave_form.samples_per_sec == 0 {
+        return Err("invalid fmt chunk".to_string());
+    }
+    Ok((wave_form, samples))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use vpin::vpx::sound::write_sound;
+
+    fn pcm_wave_form() -> WaveForm {
+        WaveForm {
+            format_tag: WAVE_FORMAT_PCM,
+            channels: 2,
+            samples_per_sec: 22050,
+            avg_bytes_per_sec: 88200,
+            block_align: 4,
+            bits_per_sample: 16,
+            cb_size: 0,
+        }
+    }
+
+    fn stored_sound() -> SoundData {
+        SoundData {
+            name: "Flipper_L01".to_string(),
+            path: "C:\\sounds\\flipper_l01.wav".to_string(),
+            wave_form: pcm_wave_form(),
+            data: vec![1, 2, 3, 4, 5, 6, 7, 8],
+            internal_name: String::new(),
+            fade: 10,
+            volume: 20,
+            balance: 30,
+            output_target: OutputTarget::Backglass,
+        }
+    }
+
+    #[test]
+    fn extracted_wav_parses_back() {
+        let sound = stored_sound();
+        let bytes = write_sound(&sound);
+        assert_eq!(&bytes[0..4], b"RIFF");
+        assert_eq!(
+            u32::from_le_bytes(bytes[4..8].try_into().unwrap()) as usize,
+            bytes.len() - 8
+        );
+        let (wave_form, samples) = parse_wav(&bytes).unwrap();
+        assert_eq!(wave_form, pcm_wave_form());
+        assert_eq!(samples, sound.data);
+    }
+
+    #[test]
+    fn parse_skips_extra_chunks() {
+        let mut bytes = write_sound(&SoundData {
+            data: vec![9, 9, 9, 9],
+            ..stored_sound()
+        });
+        // odd sized LIST chunk, followed by its pad byte, after the data
+        bytes.extend_from_slice(b"LIST");
+        bytes.extend_from_slice(&3u32.to_le_bytes());
+        bytes.extend_from_slice(&[1, 2, 3, 0]);
+        let (_, samples) = parse_wav(&bytes).unwrap();
+        assert_eq!(samples, vec![9, 9, 9, 9]);
+    }
+
+    #[test]
+    fn parse_rejects_compressed_wav() {
+        let mut bytes = write_sound(&stored_sound());
+        // the format tag in the fmt chunk
+        bytes[20..22].copy_from_slice(&2u16.to_le_bytes());
+        assert_eq!(
+            parse_wav(&bytes).unwrap_err(),
+            "compressed wav (format tag 2) is not supported, save as PCM wav or ogg"
+        );
+    }
+
+    #[test]
+    fn replace_keeps_settings_and_path() {
+        let mut sound = stored_sound();
+        let ogg = b"OggS\0\x02rest of the file".to_vec();
+        let replacement = replace_sound_data(&mut sound, "new.ogg", ogg.clone()).unwrap();
+        assert_eq!(replacement.old_extension, "wav");
+        assert_eq!(replacement.new_extension, "ogg");
+        assert_eq!(sound.path, "C:\\sounds\\flipper_l01.ogg");
+        assert_eq!(sound.name, "Flipper_L01");
+        assert_eq!((sound.fade, sound.volume, sound.balance), (10, 20, 30));
+        assert_eq!(sound.output_target, OutputTarget::Backglass);
+        assert_eq!(write_sound(&sound), ogg);
+    }
+
+    #[test]
+    fn replace_rejects_mismatched_extension() {
+        let mut sound = stored_sound();
+        let err = replace_sound_data(&mut sound, "new.wav", b"OggS....".to_vec()).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "new.wav: the extension suggests wav but the file contains ogg"
+        );
+        assert_eq!(sound, stored_sound());
+    }
+
+    #[test]
+    fn new_sound_from_wav() {
+        let bytes = write_sound(&SoundData {
+            data: vec![1, 2, 3, 4],
+            ..stored_sound()
+        });
+        let sound = new_sound("Knocker", "/home/me/knocker.wav", bytes).unwrap();
+        assert_eq!(sound.name, "Knocker");
+        assert_eq!(sound.path, "/home/me/knocker.wav");
+        assert_eq!(sound.data, vec![1, 2, 3, 4]);
+        assert_eq!(sound.output_target, OutputTarget::Table);
+        assert_eq!(sound_file_name(&sound), "Knocker.wav");
+    }
+
+    #[test]
+    fn legacy_backglass_path_is_wav() {
+        let sound = SoundData {
+            path: "* Backglass Output *".to_string(),
+            ..stored_sound()
+        };
+        assert_eq!(sound_extension(&sound), "wav");
+    }
+}
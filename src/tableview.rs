@@ -0,0 +1,322 @@
+//! Sort orders and filters for the frontend table list.
+//!
+//! Manufacturer and year are not stored in the vpx file in any reliable way,
+//! they are parsed from the usual `Title (Manufacturer Year)` file naming
+//! used by most table releases. Filtering reuses [`IndexQuery`].
+
+use crate::indexer::IndexedTable;
+use crate::indexquery::{IndexQuery, TableAsset};
+use regex::Regex;
+use std::cmp::{Ordering, Reverse};
+use std::fmt::{Display, Formatter};
+use std::sync::LazyLock;
+
+static MANUFACTURER_YEAR_REGEX: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\(\s*([^()]*?)[\s,-]*((?:19|20)\d{2})\s*\)").unwrap());
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SortOrder {
+    #[default]
+    Name,
+    Manufacturer,
+    Year,
+    LastModified,
+    Rom,
+}
+
+impl SortOrder {
+    pub const ALL: [SortOrder; 5] = [
+        SortOrder::Name,
+        SortOrder::Manufacturer,
+        SortOrder::Year,
+        SortOrder::LastModified,
+        SortOrder::Rom,
+    ];
+}
+
+impl Display for SortOrder {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            SortOrder::Name => "name",
+            SortOrder::Manufacturer => "manufacturer",
+            SortOrder::Year => "year",
+            SortOrder::LastModified => "last modified",
+            SortOrder::Rom => "rom",
+        };
+        f.write_str(s)
+    }
+}
+
+/// Manufacturer and year from a `Title (Manufacturer Year)` file name.
+#[derive(Debug, PartialEq, Eq)]
+pub struct ParsedFileName {
+    pub manufacturer: Option<String>,
+    pub year: Option<u16>,
+}
+
+pub fn parse_file_name(file_stem: &str) -> ParsedFileName {
+    match MANUFACTURER_YEAR_REGEX.captures(file_stem) {
+        Some(captures) => ParsedFileName {
+            manufacturer: Some(captures[1].trim().to_string()).filter(|m| !m.is_empty()),
+            year: captures[2].parse().ok(),
+        },
+        None => ParsedFileName {
+            manufacturer: None,
+            year: None,
+        },
+    }
+}
+
+/// The toggleable filters of the frontend list.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ListFilter {
+    Asset(TableAsset),
+    RequiresPinmame,
+    MissingRom,
+}
+
+impl ListFilter {
+    /// In menu order, the badge assets (B/S/C/P) first.
+    pub const ALL: [ListFilter; 6] = [
+        ListFilter::Asset(TableAsset::B2s),
+        ListFilter::Asset(TableAsset::AltSound),
+        ListFilter::Asset(TableAsset::AltColor),
+        ListFilter::Asset(TableAsset::Pup),
+        ListFilter::RequiresPinmame,
+        ListFilter::MissingRom,
+    ];
+}
+
+impl Display for ListFilter {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            ListFilter::Asset(TableAsset::B2s) => "B - has backglass",
+            ListFilter::Asset(TableAsset::AltSound) => "S - has altsound",
+            ListFilter::Asset(TableAsset::AltColor) => "C - has altcolor",
+            ListFilter::Asset(TableAsset::Pup) => "P - has pup pack",
+            ListFilter::Asset(TableAsset::Rom) => "has rom",
+            ListFilter::Asset(TableAsset::Wheel) => "has wheel",
+            ListFilter::RequiresPinmame => "requires PinMAME",
+            ListFilter::MissingRom => "missing rom",
+        };
+        f.write_str(s)
+    }
+}
+
+pub fn to_query(filters: &[ListFilter]) -> IndexQuery {
+    let mut query = IndexQuery::default();
+    for filter in filters {
+        match filter {
+            ListFilter::Asset(asset) => query.has.push(*asset),
+            ListFilter::RequiresPinmame => query.requires_pinmame = true,
+            ListFilter::MissingRom => query.missing_rom = true,
+        }
+    }
+    query
+}
+
+fn display_name(table: &IndexedTable) -> String {
+    table
+        .table_info
+        .table_name
+        .as_deref()
+        .filter(|name| !name.is_empty())
+        .map(str::to_string)
+        .unwrap_or_else(|| file_stem(table))
+        .to_lowercase()
+}
+
+fn file_stem(table: &IndexedTable) -> String {
+    table
+        .path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
+/// Orders `Some` before `None`, so tables with unknown values end up last.
+fn some_first<T: Ord>(a: &Option<T>, b: &Option<T>) -> Ordering {
+    match (a, b) {
+        (Some(a), Some(b)) => a.cmp(b),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => Ordering::Equal,
+    }
+}
+
+/// Filter and sort the tables for the frontend list. Ties are broken by name.
+pub fn table_view<'a>(
+    tables: &'a [IndexedTable],
+    sort: SortOrder,
+    filters: &[ListFilter],
+) -> Vec<&'a IndexedTable> {
+    let query = to_query(filters);
+    let mut view: Vec<&IndexedTable> = tables.iter().filter(|t| query.matches(t)).collect();
+    view.sort_by_cached_key(|t| display_name(t));
+    match sort {
+        SortOrder::Name => {}
+        SortOrder::Manufacturer => view.sort_by_cached_key(|t| {
+            let parsed = parse_file_name(&file_stem(t));
+            (
+                parsed.manufacturer.is_none(),
+                parsed.manufacturer.map(|m| m.to_lowercase()),
+                parsed.year,
+            )
+        }),
+        SortOrder::Year => view.sort_by(|a, b| {
+            some_first(
+                &parse_file_name(&file_stem(a)).year,
+                &parse_file_name(&file_stem(b)).year,
+            )
+        }),
+        SortOrder::LastModified => view.sort_by_key(|t| Reverse(t.last_modified)),
+        SortOrder::Rom => view.sort_by(|a, b| {
+            some_first(
+                &a.game_name.as_ref().map(|g| g.to_lowercase()),
+                &b.game_name.as_ref().map(|g| g.to_lowercase()),
+            )
+        }),
+    }
+    view
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::indexer::tests::indexed_table;
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+
+    fn tables() -> Vec<IndexedTable> {
+        vec![
+            indexed_table(
+                "/t/Twilight Zone (Bally 1993) VPW 2.0.vpx",
+                json!({"game_name": "tz_94h", "requires_pinmame": true, "b2s_path": "/t/tz.directb2s"}),
+            ),
+            indexed_table(
+                "/t/Attack from Mars (Bally 1995).vpx",
+                json!({"game_name": "afm_113b", "requires_pinmame": true, "rom_path": "/roms/afm_113b.zip", "last_modified": "2024-06-01T00:00:00+00:00"}),
+            ),
+            indexed_table(
+                "/t/Fathom (Bally 1981).vpx",
+                json!({"b2s_path": "/t/f.directb2s"}),
+            ),
+            indexed_table("/t/Big Indian (Gottlieb 1974).vpx", json!({})),
+            indexed_table("/t/my_original.vpx", json!({})),
+        ]
+    }
+
+    fn names(view: &[&IndexedTable]) -> Vec<String> {
+        view.iter().map(|t| display_name(t)).collect()
+    }
+
+    #[test]
+    fn parses_manufacturer_and_year() {
+        assert_eq!(
+            parse_file_name("Twilight Zone (Bally 1993) VPW 2.0"),
+            ParsedFileName {
+                manufacturer: Some("Bally".to_string()),
+                year: Some(1993)
+            }
+        );
+        assert_eq!(
+            parse_file_name("JP's Spider-Man (Original 2021)"),
+            ParsedFileName {
+                manufacturer: Some("Original".to_string()),
+                year: Some(2021)
+            }
+        );
+        assert_eq!(
+            parse_file_name("Gorgar (1979)"),
+            ParsedFileName {
+                manufacturer: None,
+                year: Some(1979)
+            }
+        );
+        assert_eq!(
+            parse_file_name("my_original"),
+            ParsedFileName {
+                manufacturer: None,
+                year: None
+            }
+        );
+    }
+
+    #[test]
+    fn sorts() {
+        let tables = tables();
+        assert_eq!(
+            names(&table_view(&tables, SortOrder::Name, &[])),
+            vec![
+                "attack from mars (bally 1995)",
+                "big indian (gottlieb 1974)",
+                "fathom (bally 1981)",
+                "my_original",
+                "twilight zone (bally 1993) vpw 2.0",
+            ]
+        );
+        assert_eq!(
+            names(&table_view(&tables, SortOrder::Manufacturer, &[])),
+            vec![
+                "fathom (bally 1981)",
+                "twilight zone (bally 1993) vpw 2.0",
+                "attack from mars (bally 1995)",
+                "big indian (gottlieb 1974)",
+                "my_original",
+            ]
+        );
+        assert_eq!(
+            names(&table_view(&tables, SortOrder::Year, &[])),
+            vec![
+                "big indian (gottlieb 1974)",
+                "fathom (bally 1981)",
+                "twilight zone (bally 1993) vpw 2.0",
+                "attack from mars (bally 1995)",
+                "my_original",
+            ]
+        );
+        assert_eq!(
+            names(&table_view(&tables, SortOrder::LastModified, &[]))[0],
+            "attack from mars (bally 1995)"
+        );
+        assert_eq!(
+            names(&table_view(&tables, SortOrder::Rom, &[]))[..2],
+            [
+                "attack from mars (bally 1995)",
+                "twilight zone (bally 1993) vpw 2.0"
+            ]
+        );
+    }
+
+    #[test]
+    fn filters() {
+        let tables = tables();
+        assert_eq!(
+            names(&table_view(
+                &tables,
+                SortOrder::Name,
+                &[ListFilter::Asset(TableAsset::B2s)]
+            )),
+            vec!["fathom (bally 1981)", "twilight zone (bally 1993) vpw 2.0"]
+        );
+        assert_eq!(
+            names(&table_view(
+                &tables,
+                SortOrder::Name,
+                &[ListFilter::RequiresPinmame]
+            )),
+            vec![
+                "attack from mars (bally 1995)",
+                "twilight zone (bally 1993) vpw 2.0"
+            ]
+        );
+        assert_eq!(
+            names(&table_view(
+                &tables,
+                SortOrder::Name,
+                &[ListFilter::MissingRom, ListFilter::Asset(TableAsset::B2s)]
+            )),
+            vec!["twilight zone (bally 1993) vpw 2.0"]
+        );
+    }
+}
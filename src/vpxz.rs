@@ -10,14 +10,33 @@
 //!
 //! Optionally inject the matching pinmame rom zip from a configured pinmame
 //! folder if it is not already present in the source tree.
+//!
+//! With [`VpxzOptimizeOptions`] the vpx is not copied byte-for-byte but
+//! rewritten with smaller textures, and PUP videos and altsound packs can be
+//! left out, to keep archives manageable on phone storage.
+//!
+//! Exporting a whole set of tables writes a [`VpxzManifest`] next to the
+//! archives.
+//!
+//! [`import_vpxz`] is the reverse: unpack a shared archive into the tables
+//! folder of a desktop cab. [`verify_vpxz`] checks an archive made elsewhere
+//! against the same rules before it goes to a device.
 
+use crate::RemoveOnDrop;
+use crate::atomicwrite::atomic_write;
+use crate::images::{ImageReplacement, optimize_image};
 use crate::indexer;
-use crate::indexer::Progress;
+use crate::indexer::{IsoSystemTime, Progress};
+use crate::scriptfile::decode_script;
 use globset::{Glob, GlobSet, GlobSetBuilder};
+use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, HashSet};
 use std::fs::File;
-use std::io::{self, BufReader, BufWriter};
+use std::io::{self, BufReader, BufWriter, Read, Write};
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::SystemTime;
+use vpin::vpx::VerifyResult;
 use zip::ZipWriter;
 use zip::write::SimpleFileOptions;
 
@@ -32,17 +51,50 @@ pub struct VpxzExportOptions<'a> {
     /// walk with the file count, then `set_position` once per file as it is
     /// processed (included or excluded), and `finish_and_clear` at the end.
     pub progress: Option<&'a dyn Progress>,
+    /// Write a size-optimized copy of the vpx instead of the original.
+    pub optimize: Option<VpxzOptimizeOptions>,
+    /// Build the report without writing the archive.
+    pub dry_run: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct VpxzOptimizeOptions {
+    /// Downscale textures with a side larger than this many pixels.
+    pub max_texture_size: Option<u32>,
+    /// Leave out `pupvideos/` folders.
+    pub drop_pup_videos: bool,
+    /// Leave out `altsound/` folders.
+    pub drop_altsound: bool,
 }
 
 #[derive(Debug)]
 pub struct VpxzReport {
     pub output: PathBuf,
-    /// Archive-relative paths actually written, in insertion order.
-    pub included: Vec<String>,
+    /// Files actually written, in insertion order.
+    pub included: Vec<IncludedFile>,
     /// Source-relative paths dropped, paired with the reason they were dropped.
     pub excluded: Vec<(String, ExcludeReason)>,
     /// Source path of a rom we injected from outside the tree, if any.
     pub injected_rom: Option<PathBuf>,
+    /// Textures of the vpx that were converted or downscaled.
+    pub optimized_images: Vec<OptimizedImage>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IncludedFile {
+    /// Archive-relative path
+    pub path: String,
+    /// Size of the source file in bytes.
+    pub original_size: u64,
+    /// Size in the archive before compression, smaller than `original_size`
+    /// for an optimized vpx.
+    pub size: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OptimizedImage {
+    pub name: String,
+    pub change: ImageReplacement,
 }
 
 impl VpxzReport {
@@ -52,12 +104,21 @@ impl VpxzReport {
         let suffix = format!("pinmame/roms/{}.zip", rom_name.to_lowercase());
         self.included
             .iter()
-            .any(|p| p.to_lowercase().ends_with(&suffix))
+            .any(|f| f.path.to_lowercase().ends_with(&suffix))
+    }
+
+    pub fn original_size(&self) -> u64 {
+        self.included.iter().map(|f| f.original_size).sum()
+    }
+
+    pub fn size(&self) -> u64 {
+        self.included.iter().map(|f| f.size).sum()
     }
 }
 
 /// Why a file under the vpx's parent folder did not end up in the archive.
-#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum ExcludeReason {
     /// Another `.vpx` file in the tree; the mobile importer rejects archives
     /// that contain more than one .vpx.
@@ -70,7 +131,12 @@ pub enum ExcludeReason {
     /// A previously generated `.vpxz` archive sitting in the tree.
     VpxzArchive,
     /// Matched a user-configured glob in `vpxz_excludes`.
+    #[serde(rename = "vpxz_excludes")]
     UserGlob,
+    /// Inside a `pupvideos/` folder, dropped when optimizing.
+    PupVideos,
+    /// Inside an `altsound/` folder, dropped when optimizing.
+    Altsound,
 }
 
 impl std::fmt::Display for ExcludeReason {
@@ -81,6 +147,8 @@ impl std::fmt::Display for ExcludeReason {
             ExcludeReason::UnrelatedDirectb2s => "unrelated_directb2s",
             ExcludeReason::VpxzArchive => "vpxz_archive",
             ExcludeReason::UserGlob => "vpxz_excludes",
+            ExcludeReason::PupVideos => "pup_videos",
+            ExcludeReason::Altsound => "altsound",
         };
         f.write_str(s)
     }
@@ -92,36 +160,21 @@ pub fn export_vpxz(
     output_path: &Path,
     options: &VpxzExportOptions,
 ) -> io::Result<VpxzReport> {
-    let stem = vpx_path
-        .file_stem()
-        .and_then(|s| s.to_str())
-        .ok_or_else(|| {
-            io::Error::new(
-                io::ErrorKind::InvalidInput,
-                format!("vpx path has no usable file stem: {}", vpx_path.display()),
-            )
-        })?
-        .to_string();
-    let parent = vpx_path
-        .parent()
-        .ok_or_else(|| {
-            io::Error::new(
-                io::ErrorKind::InvalidInput,
-                format!("vpx path has no parent directory: {}", vpx_path.display()),
-            )
-        })?
-        .to_path_buf();
-
+    let (stem, parent) = stem_and_parent(vpx_path)?;
     let exclude_set = build_glob_set(options.exclude_globs)?;
     let auto_excluded_paths = collect_auto_excluded_paths(&parent, vpx_path, &stem)?;
 
-    let file = File::create(output_path)?;
-    let mut zip = ZipWriter::new(BufWriter::new(file));
+    let mut zip = if options.dry_run {
+        None
+    } else {
+        Some(ZipWriter::new(BufWriter::new(File::create(output_path)?)))
+    };
     let file_opts =
         SimpleFileOptions::default().compression_method(zip::CompressionMethod::Deflated);
 
     let mut included = Vec::new();
     let mut excluded = Vec::new();
+    let mut optimized_images = Vec::new();
     let mut rom_already_in_tree = false;
 
     let entries = walkdir(&parent)?;
@@ -134,10 +187,14 @@ pub fn export_vpxz(
         let rel = abs.strip_prefix(&parent).unwrap();
         let rel_str = rel.to_string_lossy().into_owned();
 
-        if let Some(reason) = auto_excluded_paths.get(&abs) {
-            excluded.push((rel_str, *reason));
-        } else if exclude_set.is_match(rel) {
-            excluded.push((rel_str, ExcludeReason::UserGlob));
+        if let Some(reason) = exclude_reason(
+            &abs,
+            rel,
+            &auto_excluded_paths,
+            &exclude_set,
+            options.optimize.as_ref(),
+        ) {
+            excluded.push((rel_str, reason));
         } else {
             let rel_archive = to_archive_path(rel);
             let archive_path = format!("{stem}/{rel_archive}");
@@ -149,8 +206,28 @@ pub fn export_vpxz(
             {
                 rom_already_in_tree = true;
             }
-            add_file(&mut zip, &abs, &archive_path, file_opts)?;
-            included.push(archive_path);
+            let original_size = std::fs::metadata(&abs)?.len();
+            let size = match &options.optimize {
+                Some(optimize) if abs == vpx_path => {
+                    let (bytes, images) = optimize_vpx(&abs, optimize)?;
+                    optimized_images = images;
+                    if let Some(zip) = &mut zip {
+                        add_bytes(zip, &bytes, &archive_path, file_opts)?;
+                    }
+                    bytes.len() as u64
+                }
+                _ => {
+                    if let Some(zip) = &mut zip {
+                        add_file(zip, &abs, &archive_path, file_opts)?;
+                    }
+                    original_size
+                }
+            };
+            included.push(IncludedFile {
+                path: archive_path,
+                original_size,
+                size,
+            });
         }
 
         if let Some(p) = options.progress {
@@ -173,12 +250,21 @@ pub fn export_vpxz(
                 )
             })?;
         let archive_path = format!("{stem}/pinmame/roms/{rom_name}");
-        add_file(&mut zip, rom_zip, &archive_path, file_opts)?;
-        included.push(archive_path);
+        if let Some(zip) = &mut zip {
+            add_file(zip, rom_zip, &archive_path, file_opts)?;
+        }
+        let size = std::fs::metadata(rom_zip)?.len();
+        included.push(IncludedFile {
+            path: archive_path,
+            original_size: size,
+            size,
+        });
         injected_rom = Some(rom_zip.to_path_buf());
     }
 
-    zip.finish().map_err(io::Error::other)?;
+    if let Some(zip) = zip {
+        zip.finish().map_err(io::Error::other)?;
+    }
     if let Some(p) = options.progress {
         p.finish_and_clear();
     }
@@ -188,9 +274,663 @@ pub fn export_vpxz(
         included,
         excluded,
         injected_rom,
+        optimized_images,
+    })
+}
+
+/// Whether the archive at `archive_path` was written with the same options,
+/// as recorded in `exported`, the manifest entry of the earlier export, and
+/// after the last change to any of the files [`export_vpxz`] would put in it.
+pub fn archive_up_to_date(
+    vpx_path: &Path,
+    archive_path: &Path,
+    options: &VpxzExportOptions,
+    exported: Option<&VpxzManifestEntry>,
+) -> io::Result<bool> {
+    if exported.and_then(|entry| entry.options.as_ref()) != Some(&ManifestOptions::from(options)) {
+        return Ok(false);
+    }
+    let archive_modified = match std::fs::metadata(archive_path) {
+        Ok(metadata) => metadata.modified()?,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(false),
+        Err(e) => return Err(e),
+    };
+    let (stem, parent) = stem_and_parent(vpx_path)?;
+    let exclude_set = build_glob_set(options.exclude_globs)?;
+    let auto_excluded_paths = collect_auto_excluded_paths(&parent, vpx_path, &stem)?;
+    let mut sources: Vec<PathBuf> = walkdir(&parent)?
+        .into_iter()
+        .map(|entry| entry.path)
+        .filter(|abs| {
+            let rel = abs.strip_prefix(&parent).unwrap();
+            exclude_reason(
+                abs,
+                rel,
+                &auto_excluded_paths,
+                &exclude_set,
+                options.optimize.as_ref(),
+            )
+            .is_none()
+        })
+        .collect();
+    if let Some(rom_zip) = options.rom_zip
+        && rom_zip.is_file()
+    {
+        sources.push(rom_zip.to_path_buf());
+    }
+    for source in sources {
+        if std::fs::metadata(&source)?.modified()? > archive_modified {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+fn stem_and_parent(vpx_path: &Path) -> io::Result<(String, PathBuf)> {
+    let stem = vpx_path
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("vpx path has no usable file stem: {}", vpx_path.display()),
+            )
+        })?
+        .to_string();
+    let parent = vpx_path
+        .parent()
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("vpx path has no parent directory: {}", vpx_path.display()),
+            )
+        })?
+        .to_path_buf();
+    Ok((stem, parent))
+}
+
+fn exclude_reason(
+    abs: &Path,
+    rel: &Path,
+    auto_excluded_paths: &HashMap<PathBuf, ExcludeReason>,
+    exclude_set: &GlobSet,
+    optimize: Option<&VpxzOptimizeOptions>,
+) -> Option<ExcludeReason> {
+    auto_excluded_paths
+        .get(abs)
+        .copied()
+        .or_else(|| exclude_set.is_match(rel).then_some(ExcludeReason::UserGlob))
+        .or_else(|| optimize.and_then(|optimize| optimize_exclude_reason(rel, optimize)))
+}
+
+/// Why optimizing drops a file, if it does.
+fn optimize_exclude_reason(rel: &Path, optimize: &VpxzOptimizeOptions) -> Option<ExcludeReason> {
+    let parent = rel.parent()?;
+    let in_folder = |name: &str| {
+        parent
+            .components()
+            .any(|c| c.as_os_str().to_string_lossy().eq_ignore_ascii_case(name))
+    };
+    if optimize.drop_pup_videos && in_folder("pupvideos") {
+        Some(ExcludeReason::PupVideos)
+    } else if optimize.drop_altsound && in_folder("altsound") {
+        Some(ExcludeReason::Altsound)
+    } else {
+        None
+    }
+}
+
+/// The vpx with its textures optimized, see [`optimize_image`]. When no
+/// texture changed the original bytes are returned untouched.
+fn optimize_vpx(
+    vpx_path: &Path,
+    optimize: &VpxzOptimizeOptions,
+) -> io::Result<(Vec<u8>, Vec<OptimizedImage>)> {
+    let mut vpx = vpin::vpx::read(vpx_path)?;
+    let mut optimized = Vec::new();
+    for image in vpx.images.iter_mut() {
+        if let Some(change) = optimize_image(image, optimize.max_texture_size)? {
+            optimized.push(OptimizedImage {
+                name: image.name.clone(),
+                change,
+            });
+        }
+    }
+    if optimized.is_empty() {
+        return Ok((std::fs::read(vpx_path)?, optimized));
+    }
+    Ok((vpin::vpx::to_bytes(&vpx)?, optimized))
+}
+
+pub const MANIFEST_FILE_NAME: &str = "manifest.json";
+
+static VERIFY_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum ManifestStatus {
+    Exported,
+    /// The archive was newer than its sources and was not written again.
+    UpToDate,
+    Failed,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct ManifestExclude {
+    pub path: String,
+    pub reason: ExcludeReason,
+}
+
+/// The options an archive was exported with, an archive made with other
+/// options is not up to date.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct ManifestOptions {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub exclude_globs: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rom_zip: Option<PathBuf>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub optimize: Option<VpxzOptimizeOptions>,
+}
+
+impl From<&VpxzExportOptions<'_>> for ManifestOptions {
+    fn from(options: &VpxzExportOptions) -> Self {
+        ManifestOptions {
+            exclude_globs: options.exclude_globs.to_vec(),
+            rom_zip: options.rom_zip.map(Path::to_path_buf),
+            optimize: options.optimize,
+        }
+    }
+}
+
+/// One table of a batch export.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct VpxzManifestEntry {
+    pub table: PathBuf,
+    /// File name of the archive, next to the manifest.
+    pub archive: String,
+    pub status: ManifestStatus,
+    /// Archive size in bytes.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size: Option<u64>,
+    /// Whether the rom is in the archive, PinMAME tables only.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rom_bundled: Option<bool>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub excluded: Vec<ManifestExclude>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Missing for failed exports and in manifests of older versions.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub options: Option<ManifestOptions>,
+}
+
+impl VpxzManifestEntry {
+    /// `rom_name` is the game name of a PinMAME table.
+    pub fn new(
+        table: &Path,
+        status: ManifestStatus,
+        report: &VpxzReport,
+        rom_name: Option<&str>,
+        options: &VpxzExportOptions,
+    ) -> io::Result<Self> {
+        Ok(VpxzManifestEntry {
+            table: table.to_path_buf(),
+            archive: archive_file_name(&report.output),
+            status,
+            size: Some(std::fs::metadata(&report.output)?.len()),
+            rom_bundled: rom_name.map(|rom_name| report.rom_bundled(rom_name)),
+            excluded: report
+                .excluded
+                .iter()
+                .map(|(path, reason)| ManifestExclude {
+                    path: path.clone(),
+                    reason: *reason,
+                })
+                .collect(),
+            error: None,
+            options: Some(options.into()),
+        })
+    }
+
+    pub fn failed(table: &Path, archive_path: &Path, error: &io::Error) -> Self {
+        VpxzManifestEntry {
+            table: table.to_path_buf(),
+            archive: archive_file_name(archive_path),
+            status: ManifestStatus::Failed,
+            size: None,
+            rom_bundled: None,
+            excluded: Vec::new(),
+            error: Some(error.to_string()),
+            options: None,
+        }
+    }
+}
+
+fn archive_file_name(archive_path: &Path) -> String {
+    archive_path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
+/// `manifest.json` of a batch export, listing every archive in the output
+/// folder.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct VpxzManifest {
+    pub created: IsoSystemTime,
+    pub archives: Vec<VpxzManifestEntry>,
+}
+
+impl VpxzManifest {
+    pub fn new(archives: Vec<VpxzManifestEntry>) -> Self {
+        VpxzManifest {
+            created: SystemTime::now().into(),
+            archives,
+        }
+    }
+
+    /// The entries of `previous` with the ones of tables in `archives`
+    /// replaced, so exporting part of the tables keeps the rest listed.
+    /// Ordered by table.
+    pub fn merge(previous: Option<VpxzManifest>, archives: Vec<VpxzManifestEntry>) -> Self {
+        let mut merged: Vec<VpxzManifestEntry> = previous
+            .map(|manifest| manifest.archives)
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|entry| !archives.iter().any(|e| e.table == entry.table))
+            .collect();
+        merged.extend(archives);
+        merged.sort_by(|a, b| a.table.cmp(&b.table));
+        VpxzManifest::new(merged)
+    }
+
+    pub fn read(manifest_path: &Path) -> io::Result<Self> {
+        let reader = BufReader::new(File::open(manifest_path)?);
+        serde_json::from_reader(reader).map_err(|e| {
+            io::Error::other(format!(
+                "Unable to read vpxz manifest {}: {e}",
+                manifest_path.display()
+            ))
+        })
+    }
+
+    pub fn write(&self, manifest_path: &Path) -> io::Result<()> {
+        atomic_write(manifest_path, |file| {
+            serde_json::to_writer_pretty(file, self).map_err(io::Error::other)
+        })
+    }
+
+    /// The entry of an earlier export of `table`, unless it failed.
+    pub fn exported(&self, table: &Path) -> Option<&VpxzManifestEntry> {
+        self.archives
+            .iter()
+            .find(|e| e.table == table && e.status != ManifestStatus::Failed)
+    }
+}
+
+pub struct VpxzImportOptions<'a> {
+    /// Move `pinmame/roms/*.zip` here instead of keeping them next to the
+    /// table. Roms that already exist there are kept.
+    pub roms_folder: Option<&'a Path>,
+    /// Extract into an existing table folder, overwriting files.
+    pub force: bool,
+}
+
+#[derive(Debug)]
+pub struct VpxzImportReport {
+    /// `<tables folder>/<archive stem>`
+    pub table_dir: PathBuf,
+    pub vpx_path: PathBuf,
+    /// Files written into the table folder.
+    pub extracted: usize,
+    /// Roms moved to the roms folder.
+    pub moved_roms: Vec<PathBuf>,
+    /// Roms not moved because the roms folder already had them.
+    pub existing_roms: Vec<PathBuf>,
+}
+
+/// Unpack a vpxz archive into `<tables_folder>/<archive stem>/`.
+///
+/// The archive is checked before anything is written: entries that would end
+/// up outside the table folder (`../`, absolute paths) are refused and it must
+/// contain exactly one `.vpx`. Archives written by [`export_vpxz`] wrap
+/// everything in a `<stem>/` folder, that single top-level folder is dropped.
+///
+/// When extracting fails halfway, the table folder is removed again if this
+/// call created it, as are the roms it moved.
+pub fn import_vpxz(
+    vpxz_path: &Path,
+    tables_folder: &Path,
+    options: &VpxzImportOptions,
+) -> io::Result<VpxzImportReport> {
+    let stem = vpxz_path
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("vpxz path has no usable file stem: {}", vpxz_path.display()),
+            )
+        })?
+        .to_string();
+    let table_dir = tables_folder.join(&stem);
+    if table_dir.exists() && !options.force {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("{} already exists", table_dir.display()),
+        ));
+    }
+
+    let reader = BufReader::new(File::open(vpxz_path)?);
+    let mut archive = zip::ZipArchive::new(reader).map_err(io::Error::other)?;
+
+    // (index, path relative to the archive root) of every file
+    let mut files: Vec<(usize, PathBuf)> = Vec::new();
+    for i in 0..archive.len() {
+        let entry = archive.by_index(i).map_err(io::Error::other)?;
+        let Some(path) = entry.enclosed_name() else {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "Refusing {}: entry {} points outside of the archive",
+                    vpxz_path.display(),
+                    entry.name()
+                ),
+            ));
+        };
+        if entry.is_file() {
+            files.push((i, path));
+        }
+    }
+    let files = strip_common_root(files);
+
+    let vpx_files: Vec<&PathBuf> = files
+        .iter()
+        .map(|(_, path)| path)
+        .filter(|path| path_has_extension(path, "vpx"))
+        .collect();
+    let [vpx_file] = vpx_files[..] else {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "{} should contain exactly one .vpx file, found {}",
+                vpxz_path.display(),
+                vpx_files.len()
+            ),
+        ));
+    };
+    let vpx_path = table_dir.join(vpx_file);
+
+    let created_table_dir = !table_dir.exists();
+    let mut extracted = 0;
+    let mut moved_roms = Vec::new();
+    let mut existing_roms = Vec::new();
+    let result = (|| -> io::Result<()> {
+        for (index, path) in &files {
+            let target = match options.roms_folder {
+                Some(roms_folder) if is_pinmame_rom(path) => {
+                    let rom = roms_folder.join(path.file_name().unwrap_or_default());
+                    if rom.exists() {
+                        existing_roms.push(rom);
+                        continue;
+                    }
+                    moved_roms.push(rom.clone());
+                    rom
+                }
+                _ => {
+                    extracted += 1;
+                    table_dir.join(path)
+                }
+            };
+            if let Some(parent) = target.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            let mut entry = archive.by_index(*index).map_err(io::Error::other)?;
+            let mut writer = BufWriter::new(File::create(&target)?);
+            io::copy(&mut entry, &mut writer)?;
+            writer.flush()?;
+        }
+        Ok(())
+    })();
+    if let Err(e) = result {
+        // a half-filled table folder would block the next import
+        if created_table_dir {
+            let _ = std::fs::remove_dir_all(&table_dir);
+        }
+        for rom in &moved_roms {
+            let _ = std::fs::remove_file(rom);
+        }
+        return Err(e);
+    }
+
+    Ok(VpxzImportReport {
+        table_dir,
+        vpx_path,
+        extracted,
+        moved_roms,
+        existing_roms,
     })
 }
 
+/// Something in a vpxz archive the mobile importer chokes on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VpxzProblem {
+    /// An entry pointing outside of the archive (`../`, absolute path).
+    UnsafePath(String),
+    /// The importer needs exactly one .vpx, this is the number found.
+    VpxCount(usize),
+    NestedVpxz(String),
+    /// A `.directb2s` whose stem does not match the vpx's stem.
+    UnrelatedDirectb2s(String),
+    /// The script loads PinMAME but does not set `cGameName`.
+    UnknownRom,
+    /// The script loads PinMAME but the rom is not at this archive path.
+    MissingRom(String),
+    /// The vpx is damaged, see `vpx::verify`.
+    InvalidVpx(String),
+}
+
+impl std::fmt::Display for VpxzProblem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            VpxzProblem::UnsafePath(path) => {
+                write!(f, "entry {path} points outside of the archive")
+            }
+            VpxzProblem::VpxCount(count) => {
+                write!(f, "expected exactly one .vpx file, found {count}")
+            }
+            VpxzProblem::NestedVpxz(path) => write!(f, "nested vpxz archive {path}"),
+            VpxzProblem::UnrelatedDirectb2s(path) => {
+                write!(f, "{path} does not match the name of the vpx")
+            }
+            VpxzProblem::UnknownRom => {
+                write!(f, "the script needs PinMAME but does not set cGameName")
+            }
+            VpxzProblem::MissingRom(path) => {
+                write!(f, "the script needs PinMAME but {path} is missing")
+            }
+            VpxzProblem::InvalidVpx(message) => write!(f, "the vpx is damaged: {message}"),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct VpxzVerifyReport {
+    /// Archive path of the vpx, when there is exactly one.
+    pub vpx: Option<String>,
+    /// Rom name of a PinMAME table.
+    pub rom_name: Option<String>,
+    pub problems: Vec<VpxzProblem>,
+}
+
+impl VpxzVerifyReport {
+    pub fn is_ok(&self) -> bool {
+        self.problems.is_empty()
+    }
+}
+
+/// Check a vpxz archive against what [`export_vpxz`] produces and the mobile
+/// importer expects: exactly one `.vpx`, no nested `.vpxz`, only the matching
+/// `.directb2s` and for PinMAME tables the rom in `pinmame/roms/` next to the
+/// vpx. A `.vbs` next to the vpx is used instead of the embedded script, as
+/// vpinball does. The vpx itself is checked with `vpx::verify`, for that it is
+/// extracted to a temporary file.
+///
+/// Only errors reading the archive are returned as `Err`.
+pub fn verify_vpxz(vpxz_path: &Path) -> io::Result<VpxzVerifyReport> {
+    let reader = BufReader::new(File::open(vpxz_path)?);
+    let mut archive = zip::ZipArchive::new(reader).map_err(io::Error::other)?;
+
+    let mut problems = Vec::new();
+    let mut files: Vec<(usize, PathBuf)> = Vec::new();
+    for i in 0..archive.len() {
+        let entry = archive.by_index(i).map_err(io::Error::other)?;
+        match entry.enclosed_name() {
+            Some(path) if entry.is_file() => files.push((i, path)),
+            Some(_) => {}
+            None => problems.push(VpxzProblem::UnsafePath(entry.name().to_string())),
+        }
+    }
+    for (_, path) in &files {
+        if path_has_extension(path, "vpxz") {
+            problems.push(VpxzProblem::NestedVpxz(to_archive_path(path)));
+        }
+    }
+    let vpx_files: Vec<&(usize, PathBuf)> = files
+        .iter()
+        .filter(|(_, path)| path_has_extension(path, "vpx"))
+        .collect();
+    let [(vpx_index, vpx_path)] = vpx_files[..] else {
+        problems.push(VpxzProblem::VpxCount(vpx_files.len()));
+        return Ok(VpxzVerifyReport {
+            vpx: None,
+            rom_name: None,
+            problems,
+        });
+    };
+    let vpx_archive_path = to_archive_path(vpx_path);
+    let stem = vpx_path.file_stem().unwrap_or_default();
+    for (_, path) in &files {
+        if path_has_extension(path, "directb2s") && path.file_stem() != Some(stem) {
+            problems.push(VpxzProblem::UnrelatedDirectb2s(to_archive_path(path)));
+        }
+    }
+
+    // unique per call, verifications can run in parallel
+    let temp_file = RemoveOnDrop::new(std::env::temp_dir().join(format!(
+        "vpxtool-verify-{}-{}.vpx",
+        std::process::id(),
+        VERIFY_COUNTER.fetch_add(1, Ordering::Relaxed)
+    )));
+    let temp_path = temp_file.path().to_path_buf();
+    let extracted = (|| -> io::Result<()> {
+        let mut entry = archive.by_index(*vpx_index).map_err(io::Error::other)?;
+        let mut writer = BufWriter::new(File::create(&temp_path)?);
+        io::copy(&mut entry, &mut writer)?;
+        writer.flush()
+    })();
+    let checked = extracted.map(|()| {
+        let verified = vpin::vpx::verify(&temp_path);
+        let code = vpin::vpx::open(&temp_path)
+            .and_then(|mut vpx_file| vpx_file.read_gamedata())
+            .map(|gamedata| gamedata.code.string);
+        (verified, code)
+    });
+    drop(temp_file);
+    let (verified, code) = checked?;
+    // the message names the temporary file
+    let in_archive =
+        |message: String| message.replace(&temp_path.display().to_string(), &vpx_archive_path);
+    if let VerifyResult::Failed(_, message) = verified {
+        problems.push(VpxzProblem::InvalidVpx(in_archive(message)));
+    }
+
+    let sidecar_vbs = vpx_path.with_extension("vbs");
+    let code = match files.iter().find(|(_, path)| *path == sidecar_vbs) {
+        Some((index, _)) => {
+            let mut entry = archive.by_index(*index).map_err(io::Error::other)?;
+            let mut bytes = Vec::new();
+            entry.read_to_end(&mut bytes)?;
+            decode_script(&bytes).map(|(text, _)| text)
+        }
+        None => code,
+    };
+    let rom_name = match code {
+        Ok(code) if indexer::requires_pinmame(&code) => match indexer::extract_game_name(&code) {
+            Some(rom_name) => {
+                let rom_path = to_archive_path(
+                    &vpx_path
+                        .with_file_name("pinmame")
+                        .join("roms")
+                        .join(format!("{rom_name}.zip")),
+                );
+                if !files
+                    .iter()
+                    .any(|(_, path)| to_archive_path(path).eq_ignore_ascii_case(&rom_path))
+                {
+                    problems.push(VpxzProblem::MissingRom(rom_path));
+                }
+                Some(rom_name)
+            }
+            None => {
+                problems.push(VpxzProblem::UnknownRom);
+                None
+            }
+        },
+        Ok(_) => None,
+        Err(e) => {
+            // already reported when the vpx did not verify
+            if !problems
+                .iter()
+                .any(|p| matches!(p, VpxzProblem::InvalidVpx(_)))
+            {
+                problems.push(VpxzProblem::InvalidVpx(in_archive(e.to_string())));
+            }
+            None
+        }
+    };
+
+    Ok(VpxzVerifyReport {
+        vpx: Some(vpx_archive_path),
+        rom_name,
+        problems,
+    })
+}
+
+/// Drop the top-level folder when every file is inside the same one.
+fn strip_common_root(files: Vec<(usize, PathBuf)>) -> Vec<(usize, PathBuf)> {
+    let root = |path: &Path| {
+        let mut components = path.components();
+        let first = components.next()?;
+        // a file at the top level has no folder to strip
+        components.next()?;
+        Some(first.as_os_str().to_os_string())
+    };
+    let Some(common) = files.first().and_then(|(_, path)| root(path)) else {
+        return files;
+    };
+    if !files
+        .iter()
+        .all(|(_, path)| root(path).as_ref() == Some(&common))
+    {
+        return files;
+    }
+    files
+        .into_iter()
+        .map(|(i, path)| (i, path.strip_prefix(&common).unwrap().to_path_buf()))
+        .collect()
+}
+
+/// `pinmame/roms/<name>.zip`, case-insensitive.
+fn is_pinmame_rom(path: &Path) -> bool {
+    let parts: Vec<String> = path
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy().to_lowercase())
+        .collect();
+    matches!(&parts[..], [pinmame, roms, rom] if pinmame == "pinmame" && roms == "roms" && rom.ends_with(".zip"))
+}
+
 /// Locate the rom zip for a vpx: read the script for the rom name, then probe
 /// the configured / global pinmame folders. Returns `Ok(None)` for non-PinMAME
 /// tables or when no rom file can be located.
@@ -403,6 +1143,17 @@ fn add_file(
     Ok(())
 }
 
+fn add_bytes(
+    zip: &mut ZipWriter<BufWriter<File>>,
+    data: &[u8],
+    archive_path: &str,
+    options: SimpleFileOptions,
+) -> io::Result<()> {
+    zip.start_file(archive_path, options)
+        .map_err(io::Error::other)?;
+    zip.write_all(data)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -451,6 +1202,8 @@ mod tests {
                 exclude_globs: &[],
                 rom_zip: None,
                 progress: None,
+                optimize: None,
+                dry_run: false,
             },
         )
         .unwrap();
@@ -486,6 +1239,8 @@ mod tests {
                 exclude_globs: &[],
                 rom_zip: None,
                 progress: None,
+                optimize: None,
+                dry_run: false,
             },
         )
         .unwrap();
@@ -523,6 +1278,8 @@ mod tests {
                 exclude_globs: &[],
                 rom_zip: None,
                 progress: None,
+                optimize: None,
+                dry_run: false,
             },
         )
         .unwrap();
@@ -554,6 +1311,8 @@ mod tests {
                 exclude_globs: &["Downloads/".to_string(), "**/Thumbs.db".to_string()],
                 rom_zip: None,
                 progress: None,
+                optimize: None,
+                dry_run: false,
             },
         )
         .unwrap();
@@ -588,6 +1347,8 @@ mod tests {
                 exclude_globs: &[],
                 rom_zip: Some(&rom),
                 progress: None,
+                optimize: None,
+                dry_run: false,
             },
         )
         .unwrap();
@@ -620,6 +1381,8 @@ mod tests {
                 exclude_globs: &[],
                 rom_zip: Some(&global_rom),
                 progress: None,
+                optimize: None,
+                dry_run: false,
             },
         )
         .unwrap();
@@ -652,6 +1415,8 @@ mod tests {
                 exclude_globs: &[],
                 rom_zip: None,
                 progress: None,
+                optimize: None,
+                dry_run: false,
             },
         )
         .unwrap();
@@ -662,6 +1427,446 @@ mod tests {
         assert!(reasons.contains(&ExcludeReason::VpxzArchive));
     }
 
+    fn write_archive(path: &Path, entries: &[(&str, &[u8])]) {
+        let mut zip = ZipWriter::new(BufWriter::new(File::create(path).unwrap()));
+        for (name, bytes) in entries {
+            zip.start_file(*name, SimpleFileOptions::default()).unwrap();
+            zip.write_all(bytes).unwrap();
+        }
+        zip.finish().unwrap();
+    }
+
+    #[test]
+    fn imports_exported_archive() {
+        let dir = testdir!();
+        let table_dir = dir.join("source/Table");
+        let vpx = table_dir.join("Table.vpx");
+        write_bytes(&vpx, b"vpx");
+        write_bytes(&table_dir.join("Table.directb2s"), b"b2s");
+        write_bytes(&table_dir.join("pinmame/roms/mygame.zip"), b"rom");
+        write_bytes(&table_dir.join("pinmame/nvram/mygame.nv"), b"nv");
+        let vpxz = dir.join("Table.vpxz");
+        export_vpxz(
+            &vpx,
+            &vpxz,
+            &VpxzExportOptions {
+                exclude_globs: &[],
+                rom_zip: None,
+                progress: None,
+                optimize: None,
+                dry_run: false,
+            },
+        )
+        .unwrap();
+
+        let tables = dir.join("tables");
+        let roms = dir.join("pinmame/roms");
+        write_bytes(&roms.join("other.zip"), b"other");
+        let report = import_vpxz(
+            &vpxz,
+            &tables,
+            &VpxzImportOptions {
+                roms_folder: Some(&roms),
+                force: false,
+            },
+        )
+        .unwrap();
+
+        assert_eq!(report.vpx_path, tables.join("Table/Table.vpx"));
+        assert_eq!(std::fs::read(&report.vpx_path).unwrap(), b"vpx");
+        assert!(tables.join("Table/Table.directb2s").is_file());
+        assert!(tables.join("Table/pinmame/nvram/mygame.nv").is_file());
+        assert!(!tables.join("Table/pinmame/roms/mygame.zip").exists());
+        assert_eq!(report.moved_roms, vec![roms.join("mygame.zip")]);
+        assert_eq!(std::fs::read(roms.join("mygame.zip")).unwrap(), b"rom");
+        assert_eq!(report.extracted, 3);
+
+        // importing again needs force
+        let again = import_vpxz(
+            &vpxz,
+            &tables,
+            &VpxzImportOptions {
+                roms_folder: Some(&roms),
+                force: false,
+            },
+        );
+        assert_eq!(again.unwrap_err().kind(), io::ErrorKind::AlreadyExists);
+        let forced = import_vpxz(
+            &vpxz,
+            &tables,
+            &VpxzImportOptions {
+                roms_folder: Some(&roms),
+                force: true,
+            },
+        )
+        .unwrap();
+        assert_eq!(forced.existing_roms, vec![roms.join("mygame.zip")]);
+    }
+
+    #[test]
+    fn import_refuses_path_traversal() {
+        let dir = testdir!();
+        let vpxz = dir.join("Evil.vpxz");
+        write_archive(
+            &vpxz,
+            &[("Evil/Evil.vpx", b"vpx"), ("Evil/../../escaped.txt", b"x")],
+        );
+        let err = import_vpxz(
+            &vpxz,
+            &dir.join("tables"),
+            &VpxzImportOptions {
+                roms_folder: None,
+                force: false,
+            },
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("points outside"), "{err}");
+        assert!(!dir.join("tables").exists());
+        assert!(!dir.join("escaped.txt").exists());
+    }
+
+    #[test]
+    fn import_requires_exactly_one_vpx() {
+        let dir = testdir!();
+        let vpxz = dir.join("Two.vpxz");
+        write_archive(&vpxz, &[("a.vpx", b"a"), ("b.vpx", b"b")]);
+        let options = VpxzImportOptions {
+            roms_folder: None,
+            force: false,
+        };
+        let err = import_vpxz(&vpxz, &dir.join("tables"), &options).unwrap_err();
+        assert!(err.to_string().contains("found 2"), "{err}");
+
+        // files at the top level are kept where they are
+        let vpxz = dir.join("Flat.vpxz");
+        write_archive(&vpxz, &[("Flat.vpx", b"a"), ("media/wheel.png", b"w")]);
+        let report = import_vpxz(&vpxz, &dir.join("tables"), &options).unwrap();
+        assert_eq!(report.vpx_path, dir.join("tables/Flat/Flat.vpx"));
+        assert!(dir.join("tables/Flat/media/wheel.png").is_file());
+    }
+
+    #[test]
+    fn failed_import_leaves_no_table_folder() {
+        let dir = testdir!();
+        let vpxz = dir.join("Table.vpxz");
+        write_archive(
+            &vpxz,
+            &[
+                ("Table/Table.vpx", b"vpx"),
+                ("Table/pinmame/roms/game.zip", b"rom"),
+            ],
+        );
+        // a file where the roms folder should be
+        let roms_folder = dir.join("roms");
+        write_bytes(&roms_folder, b"not a folder");
+        let options = VpxzImportOptions {
+            roms_folder: Some(&roms_folder),
+            force: false,
+        };
+        import_vpxz(&vpxz, &dir.join("tables"), &options).unwrap_err();
+        assert!(!dir.join("tables/Table").exists());
+
+        // the next import is not refused
+        let options = VpxzImportOptions {
+            roms_folder: None,
+            force: false,
+        };
+        let report = import_vpxz(&vpxz, &dir.join("tables"), &options).unwrap();
+        assert!(report.vpx_path.is_file());
+    }
+
+    #[test]
+    fn optimizes_vpx_and_drops_media() {
+        let dir = testdir!();
+        let table_dir = dir.join("Table");
+        let vpx_path = table_dir.join("Table.vpx");
+        std::fs::create_dir_all(&table_dir).unwrap();
+        vpin::vpx::new_minimal_vpx(&vpx_path).unwrap();
+        let mut vpx = vpin::vpx::read(&vpx_path).unwrap();
+        let mut png = io::Cursor::new(Vec::new());
+        image::DynamicImage::ImageRgb8(image::RgbImage::from_fn(512, 256, |x, y| {
+            image::Rgb([(x * y) as u8, y as u8, x as u8])
+        }))
+        .write_to(&mut png, image::ImageFormat::Png)
+        .unwrap();
+        let mut playfield = vpin::vpx::image::ImageData {
+            name: "Playfield".to_string(),
+            path: "playfield.png".to_string(),
+            ..Default::default()
+        };
+        crate::images::replace_image_data(&mut playfield, "playfield.png", png.into_inner())
+            .unwrap();
+        vpx.add_or_replace_image(playfield);
+        vpin::vpx::write(&vpx_path, &vpx).unwrap();
+        write_bytes(&table_dir.join("pupvideos/game/intro.mp4"), b"video");
+        write_bytes(&table_dir.join("pinmame/altsound/game/a.ogg"), b"sound");
+        write_bytes(&table_dir.join("pinmame/roms/game.zip"), b"rom");
+
+        let out = dir.join("Table.vpxz");
+        let options = VpxzExportOptions {
+            exclude_globs: &[],
+            rom_zip: None,
+            progress: None,
+            optimize: Some(VpxzOptimizeOptions {
+                max_texture_size: Some(16),
+                drop_pup_videos: true,
+                drop_altsound: true,
+            }),
+            dry_run: true,
+        };
+        let report = export_vpxz(&vpx_path, &out, &options).unwrap();
+        assert!(!out.exists());
+        assert_eq!(
+            report.excluded,
+            vec![
+                (
+                    Path::new("pinmame/altsound/game/a.ogg")
+                        .display()
+                        .to_string(),
+                    ExcludeReason::Altsound
+                ),
+                (
+                    Path::new("pupvideos/game/intro.mp4").display().to_string(),
+                    ExcludeReason::PupVideos
+                ),
+            ]
+        );
+        assert_eq!(report.optimized_images.len(), 1);
+        assert_eq!(report.optimized_images[0].change.new_size, (16, 8));
+        let vpx_file = report
+            .included
+            .iter()
+            .find(|f| f.path == "Table/Table.vpx")
+            .unwrap();
+        assert!(vpx_file.size < vpx_file.original_size);
+
+        let report = export_vpxz(
+            &vpx_path,
+            &out,
+            &VpxzExportOptions {
+                dry_run: false,
+                ..options
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            archive_entries(&out),
+            BTreeSet::from([
+                "Table/Table.vpx".to_string(),
+                "Table/pinmame/roms/game.zip".to_string(),
+            ])
+        );
+        let archived = vpin::vpx::from_bytes(&archive_bytes(&out, "Table/Table.vpx")).unwrap();
+        assert_eq!(archived.images[0].ext(), "webp");
+        assert_eq!(
+            (archived.images[0].width, archived.images[0].height),
+            (16, 8)
+        );
+        assert_eq!(report.size(), vpx_file.size + 3);
+    }
+
+    #[test]
+    fn archive_up_to_date_compares_with_included_files() {
+        let dir = testdir!();
+        let table_dir = dir.join("Table");
+        let vpx = table_dir.join("Table.vpx");
+        write_bytes(&vpx, b"vpx");
+        write_bytes(&table_dir.join("Other.vpx"), b"other");
+        let out = dir.join("Table.vpxz");
+        let options = VpxzExportOptions {
+            exclude_globs: &[],
+            rom_zip: None,
+            progress: None,
+            optimize: None,
+            dry_run: false,
+        };
+        assert!(!archive_up_to_date(&vpx, &out, &options, None).unwrap());
+
+        let report = export_vpxz(&vpx, &out, &options).unwrap();
+        let entry = VpxzManifestEntry::new(&vpx, ManifestStatus::Exported, &report, None, &options)
+            .unwrap();
+        let exported = Some(&entry);
+        let set_modified = |path: &Path, offset: u64| {
+            let time = SystemTime::now() + std::time::Duration::from_secs(offset);
+            File::options()
+                .write(true)
+                .open(path)
+                .unwrap()
+                .set_modified(time)
+                .unwrap();
+        };
+        set_modified(&out, 60);
+        assert!(archive_up_to_date(&vpx, &out, &options, exported).unwrap());
+        // not known to be exported with these options
+        assert!(!archive_up_to_date(&vpx, &out, &options, None).unwrap());
+        let optimized = VpxzExportOptions {
+            optimize: Some(VpxzOptimizeOptions::default()),
+            ..options
+        };
+        assert!(!archive_up_to_date(&vpx, &out, &optimized, exported).unwrap());
+        // excluded from the archive, does not matter
+        set_modified(&table_dir.join("Other.vpx"), 120);
+        assert!(archive_up_to_date(&vpx, &out, &options, exported).unwrap());
+        set_modified(&vpx, 120);
+        assert!(!archive_up_to_date(&vpx, &out, &options, exported).unwrap());
+    }
+
+    #[test]
+    fn manifest_round_trips() {
+        let dir = testdir!();
+        let out = dir.join("Table.vpxz");
+        write_bytes(&out, b"zip");
+        let report = VpxzReport {
+            output: out.clone(),
+            included: vec![IncludedFile {
+                path: "Table/pinmame/roms/game.zip".to_string(),
+                original_size: 3,
+                size: 3,
+            }],
+            excluded: vec![("Other.vpx".to_string(), ExcludeReason::OtherVpx)],
+            injected_rom: None,
+            optimized_images: Vec::new(),
+        };
+        let table = Path::new("/t/Table/Table.vpx");
+        let excludes = ["*.bak".to_string()];
+        let options = VpxzExportOptions {
+            exclude_globs: &excludes,
+            rom_zip: None,
+            progress: None,
+            optimize: None,
+            dry_run: false,
+        };
+        let manifest = VpxzManifest::new(vec![
+            VpxzManifestEntry::new(
+                table,
+                ManifestStatus::Exported,
+                &report,
+                Some("GAME"),
+                &options,
+            )
+            .unwrap(),
+            VpxzManifestEntry::failed(
+                Path::new("/t/Broken/Broken.vpx"),
+                &dir.join("Broken.vpxz"),
+                &io::Error::other("no parent"),
+            ),
+        ]);
+        let manifest_path = dir.join(MANIFEST_FILE_NAME);
+        manifest.write(&manifest_path).unwrap();
+
+        let json: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(&manifest_path).unwrap()).unwrap();
+        assert_eq!(
+            json["archives"][0],
+            serde_json::json!({
+                "table": "/t/Table/Table.vpx",
+                "archive": "Table.vpxz",
+                "status": "exported",
+                "size": 3,
+                "rom_bundled": true,
+                "excluded": [{"path": "Other.vpx", "reason": "other_vpx"}],
+                "options": {"exclude_globs": ["*.bak"]},
+            })
+        );
+        let read = VpxzManifest::read(&manifest_path).unwrap();
+        assert_eq!(read, manifest);
+        assert!(read.exported(table).is_some());
+        assert!(read.exported(Path::new("/t/Broken/Broken.vpx")).is_none());
+
+        // a later export of only the broken table keeps the other entry
+        let fixed = VpxzManifestEntry {
+            table: PathBuf::from("/t/Broken/Broken.vpx"),
+            status: ManifestStatus::Exported,
+            error: None,
+            ..read.archives[0].clone()
+        };
+        let merged = VpxzManifest::merge(Some(read.clone()), vec![fixed.clone()]);
+        assert_eq!(merged.archives, vec![fixed, read.archives[0].clone()]);
+    }
+
+    #[test]
+    fn verifies_exported_pinmame_table() {
+        let dir = testdir!();
+        let table_dir = dir.join("Table");
+        let vpx_path = table_dir.join("Table.vpx");
+        std::fs::create_dir_all(&table_dir).unwrap();
+        vpin::vpx::new_minimal_vpx(&vpx_path).unwrap();
+        let mut vpx = vpin::vpx::read(&vpx_path).unwrap();
+        vpx.set_script(
+            "LoadVPM \"01560000\", \"S11.VBS\", 3.26\r\nConst cGameName = \"mygame\"\r\n"
+                .to_string(),
+        );
+        vpin::vpx::write(&vpx_path, &vpx).unwrap();
+        let rom_zip = dir.join("roms/mygame.zip");
+        write_bytes(&rom_zip, b"rom");
+
+        let export = |rom_zip: Option<&Path>| {
+            let out = dir.join("Table.vpxz");
+            export_vpxz(
+                &vpx_path,
+                &out,
+                &VpxzExportOptions {
+                    exclude_globs: &[],
+                    rom_zip,
+                    progress: None,
+                    optimize: None,
+                    dry_run: false,
+                },
+            )
+            .unwrap();
+            verify_vpxz(&out).unwrap()
+        };
+
+        let report = export(Some(&rom_zip));
+        assert_eq!(report.problems, vec![]);
+        assert_eq!(report.vpx.as_deref(), Some("Table/Table.vpx"));
+        assert_eq!(report.rom_name.as_deref(), Some("mygame"));
+
+        let report = export(None);
+        assert_eq!(
+            report.problems,
+            vec![VpxzProblem::MissingRom(
+                "Table/pinmame/roms/mygame.zip".to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn verify_reports_problems() {
+        let dir = testdir!();
+        let vpxz = dir.join("Table.vpxz");
+        write_archive(
+            &vpxz,
+            &[
+                ("T/T.vpx", b"not a vpx"),
+                ("T/Other.directb2s", b"b2s"),
+                ("T/T.directb2s", b"b2s"),
+                ("T/old.vpxz", b"zip"),
+                ("../evil.txt", b"evil"),
+            ],
+        );
+        let report = verify_vpxz(&vpxz).unwrap();
+        assert!(!report.is_ok());
+        assert_eq!(
+            report.problems[..3],
+            [
+                VpxzProblem::UnsafePath("../evil.txt".to_string()),
+                VpxzProblem::NestedVpxz("T/old.vpxz".to_string()),
+                VpxzProblem::UnrelatedDirectb2s("T/Other.directb2s".to_string()),
+            ]
+        );
+        let [VpxzProblem::InvalidVpx(message)] = &report.problems[3..] else {
+            panic!("expected an invalid vpx, got {:?}", report.problems);
+        };
+        assert!(message.contains("T/T.vpx"), "{message}");
+
+        write_archive(&vpxz, &[("A.vpx", b"a"), ("B.vpx", b"b")]);
+        assert_eq!(
+            verify_vpxz(&vpxz).unwrap().problems,
+            vec![VpxzProblem::VpxCount(2)]
+        );
+    }
+
     #[test]
     fn default_output_path_uses_grandparent_and_stem() {
         let p = Path::new("/tables/My Table/Table v1.1.vpx");